@@ -0,0 +1,512 @@
+use std::{
+    fs::File,
+    io::{Error, ErrorKind, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use latex_gen::{
+    BackwardForm, GradientOption, GraphOption, Indexes, InferenceModel, JacobianForm, LatexEngine,
+    LatexResult, Loss, Optimizer, OptimizerOption, OutputFormat, ParseMode, TraceInput,
+    TractResult, ValueOption, VerifyOption,
+};
+use structopt::StructOpt;
+
+// comma separated numbers ex) 1,3,224,224
+#[derive(Debug, Clone)]
+struct NumList(Vec<usize>);
+
+impl FromStr for NumList {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|x| !x.trim().is_empty())
+            .map(|x| {
+                x.trim()
+                    .parse::<usize>()
+                    .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{}: {}", x, e)))
+            })
+            .collect::<Result<Vec<usize>, Error>>()
+            .map(NumList)
+    }
+}
+
+#[derive(StructOpt)]
+struct ModelArgs {
+    /// onnx model path
+    #[structopt(parse(from_os_str))]
+    model: PathBuf,
+    /// override input shape, repeat for each input ex) --input-shape 1,3,224,224
+    #[structopt(long = "input-shape")]
+    input_shapes: Vec<NumList>,
+    /// depth of forward formula, full expansion if not given
+    #[structopt(long)]
+    depth: Option<usize>,
+    /// only use input symbols for forward formula
+    #[structopt(long)]
+    brief: bool,
+    /// render weight and bias values as matrix or statistics
+    #[structopt(long)]
+    values: bool,
+    /// digits after decimal point of rendered values
+    #[structopt(long, default_value = "3")]
+    precision: usize,
+    /// rows and cols of value matrix shown before dots
+    #[structopt(long, default_value = "4")]
+    max_rows: usize,
+    #[structopt(long, default_value = "4")]
+    max_cols: usize,
+    /// tensors with more elements only get statistics
+    #[structopt(long, default_value = "64")]
+    max_elements: usize,
+    /// use value matrix instead of symbol in forward formula
+    #[structopt(long)]
+    inline_values: bool,
+    /// evaluate model with random input from seed and attach node values
+    #[structopt(long)]
+    trace_seed: Option<u64>,
+    /// attach numeric gradient of constants, input from trace seed or 0
+    #[structopt(long)]
+    gradient: bool,
+    /// keep every gradient value in output
+    #[structopt(long)]
+    full_gradient: bool,
+}
+
+impl ModelArgs {
+    fn shapes(&self) -> Vec<Vec<usize>> {
+        self.input_shapes.iter().map(|s| s.0.clone()).collect()
+    }
+    // model for backward, verify and jacobian with the same input facts as parse
+    fn inference_model(&self, engine: &LatexEngine) -> TractResult<InferenceModel> {
+        engine.model_from_path_with(&self.model, &self.shapes())
+    }
+    fn value_option(&self) -> Option<ValueOption> {
+        if !self.values && !self.inline_values {
+            return None;
+        }
+        Some(ValueOption {
+            precision: self.precision,
+            max_rows: self.max_rows,
+            max_cols: self.max_cols,
+            max_elements: self.max_elements,
+            inline: self.inline_values,
+        })
+    }
+    fn parse(&self, engine: &mut LatexEngine) -> TractResult<LatexResult> {
+        let shapes = self.shapes();
+        let mode = if self.brief {
+            ParseMode::Brief
+        } else {
+            ParseMode::Full(self.depth)
+        };
+        engine.value_option = self.value_option();
+        engine.trace_input = self.trace_seed.map(TraceInput::Random);
+        if self.gradient || self.full_gradient {
+            let mut option = GradientOption::new(TraceInput::Random(self.trace_seed.unwrap_or(0)));
+            option.full = self.full_gradient;
+            engine.gradient_option = Some(option);
+        }
+        engine.parse_from_path_with(&self.model, &shapes, mode)
+    }
+}
+
+#[derive(StructOpt)]
+struct OutputArgs {
+    /// output file, stdout if not given
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+    /// group dot and mermaid nodes by first n parts of node name
+    #[structopt(long)]
+    cluster_depth: Option<usize>,
+}
+
+impl OutputArgs {
+    fn write(&self, content: &str) -> TractResult<()> {
+        match self.output {
+            Some(ref path) => File::create(path)?.write_all(content.as_bytes())?,
+            None => print!("{}", content),
+        }
+        Ok(())
+    }
+    fn graph_option(&self) -> GraphOption {
+        GraphOption::new(self.cluster_depth)
+    }
+}
+
+#[derive(StructOpt)]
+#[structopt(name = "onnx-latex", about = "generate latex formulas from onnx model")]
+enum Command {
+    /// print nodes of model
+    Info {
+        /// onnx model path
+        #[structopt(parse(from_os_str))]
+        model: PathBuf,
+        #[structopt(flatten)]
+        output: OutputArgs,
+    },
+    /// generate forward formulas
+    Forward {
+        #[structopt(flatten)]
+        model: ModelArgs,
+        /// json, compact, tex, markdown, html, dot, mermaid
+        #[structopt(short, long, default_value = "json")]
+        format: OutputFormat,
+        #[structopt(flatten)]
+        output: OutputArgs,
+    },
+    /// generate forward and backward formulas
+    Backward {
+        #[structopt(flatten)]
+        model: ModelArgs,
+        /// only generate backward of this node
+        #[structopt(long)]
+        node: Option<usize>,
+        /// weight index ex) --weight-idxs 1,2
+        #[structopt(long, default_value = "")]
+        weight_idxs: NumList,
+        /// layer index ex) --layer-idxs 3,4
+        #[structopt(long, default_value = "")]
+        layer_idxs: NumList,
+        /// depth of backward chain, expand to total error if not given
+        #[structopt(long)]
+        back_depth: Option<usize>,
+        /// node is bias, normalization, slope or input with this element index ex) --param-idxs 0,3
+        #[structopt(long)]
+        param_idxs: Option<NumList>,
+        /// loss at the head of chain, mse, cross_entropy, bce, l1
+        #[structopt(long)]
+        loss: Option<Loss>,
+        /// custom loss ron template, #_0 is output and #_1 is target
+        #[structopt(long, conflicts_with = "loss")]
+        loss_template: Option<String>,
+        /// element or matrix
+        #[structopt(long, default_value = "element")]
+        form: BackwardForm,
+        /// add update equations, sgd, momentum, rmsprop, adam
+        #[structopt(long)]
+        optimizer: Option<Optimizer>,
+        /// symbol or value of learning rate
+        #[structopt(long)]
+        learning_rate: Option<String>,
+        /// symbol or value of momentum, 0<=x<1
+        #[structopt(long)]
+        momentum: Option<String>,
+        /// symbol or value of rmsprop decay, 0<=x<1
+        #[structopt(long)]
+        rho: Option<String>,
+        /// symbol or value of first adam decay, 0<=x<1
+        #[structopt(long)]
+        beta1: Option<String>,
+        /// symbol or value of second adam decay, 0<=x<1
+        #[structopt(long)]
+        beta2: Option<String>,
+        /// symbol or value of epsilon, x>0
+        #[structopt(long)]
+        epsilon: Option<String>,
+        /// json, compact, tex, markdown, html, dot, mermaid
+        #[structopt(short, long, default_value = "json")]
+        format: OutputFormat,
+        #[structopt(flatten)]
+        output: OutputArgs,
+    },
+    /// compare backward formula with finite difference for one weight element
+    Verify {
+        #[structopt(flatten)]
+        model: ModelArgs,
+        /// layer node which owns the weight
+        #[structopt(long)]
+        node: usize,
+        /// weight index ex) --weight-idxs 1,2
+        #[structopt(long)]
+        weight_idxs: NumList,
+        /// seed of random input
+        #[structopt(long, default_value = "0")]
+        seed: u64,
+        /// step of finite difference
+        #[structopt(long, default_value = "0.001")]
+        eps: f32,
+        /// loss of both sides, mse, cross_entropy, bce, l1
+        #[structopt(long)]
+        loss: Option<Loss>,
+        #[structopt(flatten)]
+        output: OutputArgs,
+    },
+    /// jacobian of output node with respect to input or intermediate node
+    Jacobian {
+        #[structopt(flatten)]
+        model: ModelArgs,
+        /// node with respect to
+        #[structopt(long)]
+        wrt: usize,
+        /// differentiated node, last layer if not given
+        #[structopt(long)]
+        of: Option<usize>,
+        /// chain or matrix
+        #[structopt(long, default_value = "chain")]
+        form: JacobianForm,
+        #[structopt(flatten)]
+        output: OutputArgs,
+    },
+    /// export model graph
+    Export {
+        #[structopt(flatten)]
+        model: ModelArgs,
+        /// dot, mermaid
+        #[structopt(short, long, default_value = "dot")]
+        format: OutputFormat,
+        #[structopt(flatten)]
+        output: OutputArgs,
+    },
+}
+
+fn main() -> TractResult<()> {
+    let command = Command::from_args();
+    let mut engine = LatexEngine::new();
+    match command {
+        Command::Info { model, output } => output.write(&latex_gen::gen_model_info(model)?),
+        Command::Forward {
+            model,
+            format,
+            output,
+        } => {
+            let result = model.parse(&mut engine)?;
+            output.write(&result.render(&format, &output.graph_option()))
+        }
+        Command::Backward {
+            model,
+            node,
+            weight_idxs,
+            layer_idxs,
+            back_depth,
+            param_idxs,
+            loss,
+            loss_template,
+            form,
+            optimizer,
+            learning_rate,
+            momentum,
+            rho,
+            beta1,
+            beta2,
+            epsilon,
+            format,
+            output,
+        } => {
+            engine.optimizer = match optimizer {
+                Some(o) => {
+                    let option = OptimizerOption {
+                        learning_rate,
+                        momentum,
+                        rho,
+                        beta1,
+                        beta2,
+                        epsilon,
+                        ..OptimizerOption::new(o)
+                    };
+                    option.validate()?;
+                    Some(option)
+                }
+                None => None,
+            };
+            engine.loss = loss_template.map(Loss::Custom).or(loss);
+            engine.backward_form = form;
+            let mut result = model.parse(&mut engine)?;
+            let indexes = Indexes::new(weight_idxs.0, layer_idxs.0);
+            let inf_model = model.inference_model(&engine)?;
+            let math_ops = LatexEngine::math_op_vecs(&inf_model);
+            match node {
+                Some(n) => {
+                    let last_point = result.senario.last().cloned().ok_or_else(|| {
+                        Error::new(ErrorKind::NotFound, "model has no layer to backward")
+                    })?;
+                    result.loss = engine.gen_loss(&result, last_point)?;
+                    let (s, v) = match param_idxs {
+                        Some(p) => engine.gen_param_back(
+                            &math_ops,
+                            &inf_model,
+                            &result,
+                            (n, last_point),
+                            &p.0,
+                        )?,
+                        None => engine.gen_each_back(
+                            &math_ops,
+                            &inf_model,
+                            &result,
+                            (n, last_point),
+                            &indexes,
+                            back_depth,
+                        )?,
+                    };
+                    let update = engine.gen_node_update(&result, n, &s).unwrap_or_default();
+                    if let Some(f) = result.symbol_map[n].as_mut() {
+                        f.backward_symbol = s;
+                        f.backward_value = v;
+                        f.update = update;
+                    }
+                }
+                None => {
+                    engine.gen_back_model(
+                        &mut result,
+                        &inf_model,
+                        &math_ops,
+                        &indexes,
+                        back_depth,
+                    )?;
+                }
+            }
+            output.write(&result.render(&format, &output.graph_option()))
+        }
+        Command::Verify {
+            model,
+            node,
+            weight_idxs,
+            seed,
+            eps,
+            loss,
+            output,
+        } => {
+            engine.loss = loss;
+            let result = model.parse(&mut engine)?;
+            let inf_model = model.inference_model(&engine)?;
+            let indexes = Indexes::new(weight_idxs.0, Vec::new());
+            let option = VerifyOption {
+                eps,
+                ..VerifyOption::new(TraceInput::Random(seed))
+            };
+            let check = engine.verify_backward(&inf_model, &result, node, &indexes, &option)?;
+            output.write(&format!("{:#?}\n", check))
+        }
+        Command::Jacobian {
+            model,
+            wrt,
+            of,
+            form,
+            output,
+        } => {
+            let result = model.parse(&mut engine)?;
+            let inf_model = model.inference_model(&engine)?;
+            let math_ops = LatexEngine::math_op_vecs(&inf_model);
+            let of = of.or(result.senario.last().cloned()).ok_or_else(|| {
+                Error::new(ErrorKind::NotFound, "model has no layer to differentiate")
+            })?;
+            let (s, v) = engine.gen_jacobian(&math_ops, &inf_model, &result, (of, wrt), &form)?;
+            output.write(&format!("{}={}\n", s, v))
+        }
+        Command::Export {
+            model,
+            format,
+            output,
+        } => {
+            if format != OutputFormat::Dot && format != OutputFormat::Mermaid {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "export only supports dot and mermaid",
+                )
+                .into());
+            }
+            let result = model.parse(&mut engine)?;
+            output.write(&result.render(&format, &output.graph_option()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn num_list_test() {
+        assert_eq!(
+            NumList::from_str("1, 3,224,224").unwrap().0,
+            vec![1, 3, 224, 224]
+        );
+        assert!(NumList::from_str("").unwrap().0.is_empty());
+        assert!(NumList::from_str("1,x").is_err());
+    }
+
+    #[test]
+    fn input_shape_test() {
+        let command = Command::from_iter_safe(&[
+            "onnx-latex",
+            "backward",
+            "model.onnx",
+            "--input-shape",
+            "1,2",
+            "--input-shape",
+            "3",
+            "--node",
+            "4",
+        ])
+        .unwrap();
+        match command {
+            Command::Backward {
+                model, node, form, ..
+            } => {
+                assert_eq!(model.shapes(), vec![vec![1, 2], vec![3]]);
+                assert_eq!(node, Some(4));
+                assert_eq!(form, BackwardForm::Element);
+            }
+            _ => panic!("expected backward command"),
+        }
+    }
+
+    #[test]
+    fn value_flags_test() {
+        let command = Command::from_iter_safe(&[
+            "onnx-latex",
+            "forward",
+            "model.onnx",
+            "--values",
+            "--max-rows",
+            "2",
+            "--max-elements",
+            "1000",
+        ])
+        .unwrap();
+        match command {
+            Command::Forward { model, .. } => {
+                let option = model.value_option().unwrap();
+                assert_eq!(option.max_rows, 2);
+                assert_eq!(option.max_cols, 4);
+                assert_eq!(option.max_elements, 1000);
+            }
+            _ => panic!("expected forward command"),
+        }
+    }
+
+    #[test]
+    fn optimizer_flags_test() {
+        let command = Command::from_iter_safe(&[
+            "onnx-latex",
+            "backward",
+            "model.onnx",
+            "--optimizer",
+            "adam",
+            "--beta1",
+            "0.9",
+            "--epsilon",
+            "1e-8",
+        ])
+        .unwrap();
+        match command {
+            Command::Backward {
+                optimizer,
+                beta1,
+                epsilon,
+                ..
+            } => {
+                assert_eq!(optimizer, Some(Optimizer::Adam));
+                assert_eq!(beta1.as_deref(), Some("0.9"));
+                assert_eq!(epsilon.as_deref(), Some("1e-8"));
+            }
+            _ => panic!("expected backward command"),
+        }
+    }
+
+    #[test]
+    fn verify_requires_node_test() {
+        assert!(Command::from_iter_safe(&["onnx-latex", "verify", "model.onnx"]).is_err());
+    }
+}
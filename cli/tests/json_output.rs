@@ -0,0 +1,34 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+// model of latex_gen written to temp file
+fn model_file(name: &str, bytes: Vec<u8>) -> PathBuf {
+    let path = env::temp_dir().join(format!("onnx_latex_{}_{}.onnx", name, std::process::id()));
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+// stdout of binary, which must be only json
+fn run_json(args: &[&str]) -> serde_json::Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_onnx-latex"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    serde_json::from_str(&stdout).unwrap_or_else(|e| panic!("{}: {}", e, stdout))
+}
+
+#[test]
+fn stdout_json_test() {
+    let path = model_file("two_layer", latex_gen::test_model::two_layer());
+    let model = path.to_str().unwrap();
+    let forward = run_json(&["forward", model, "--format", "compact"]);
+    assert!(forward["symbol_map"].is_array());
+    let backward = run_json(&["backward", model, "--weight-idxs", "0,0"]);
+    assert!(backward["symbol_map"].is_array());
+    fs::remove_file(&path).unwrap();
+}
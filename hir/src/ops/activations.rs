@@ -1,6 +1,6 @@
 use crate::{
     internal::*,
-    utils::{FormulKind, MathGen},
+    utils::{element_symbol, to_strings, FormulKind, MathGen},
 };
 use tract_core::ops::math::*;
 
@@ -63,6 +63,16 @@ impl MathGen for Clip {
         let i2 = self.1.map(|s| s.to_string()).unwrap_or("∞".to_string());
         format!(r#"min(max({},{}),{})"#, inputs[0], i1, i2)
     }
+    fn gen_element_value(
+        &self,
+        inputs: Vec<String>,
+        _input_shapes: Vec<Vec<usize>>,
+        coord: Vec<usize>,
+        _expand: Option<usize>,
+    ) -> Option<String> {
+        let x = element_symbol(&inputs[0], &to_strings(&coord));
+        Some(self.gen_forward_value(vec![x], None, None))
+    }
 }
 
 activation!(Clip, |op, name: &str, model: &mut TypedModel, inputs| {
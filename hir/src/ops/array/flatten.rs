@@ -1,7 +1,7 @@
 use crate::infer::*;
 use crate::internal::*;
 use crate::utils::FormulKind;
-use crate::utils::MathGen;
+use crate::utils::{element_symbol, to_strings, MathGen};
 
 #[derive(Debug, Clone, new, Default, Hash)]
 pub struct Flatten {
@@ -20,6 +20,33 @@ impl MathGen for Flatten {
     ) -> String {
         format!(r#"Flatten({})"#,inputs[0])
     }
+    fn gen_element_value(
+        &self,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        coord: Vec<usize>,
+        _expand: Option<usize>,
+    ) -> Option<String> {
+        // unravel flat index into input index
+        let shape = input_shapes.get(0)?;
+        let axis = if self.axis >= 0 {
+            self.axis
+        } else {
+            self.axis + shape.len() as i64
+        } as usize;
+        let mut outer = coord[0];
+        let mut inner = coord[1];
+        let mut index = vec![0; shape.len()];
+        for i in (axis..shape.len()).rev() {
+            index[i] = inner % shape[i];
+            inner /= shape[i];
+        }
+        for i in (0..axis).rev() {
+            index[i] = outer % shape[i];
+            outer /= shape[i];
+        }
+        Some(element_symbol(&inputs[0], &to_strings(&index)))
+    }
 }
 
 impl Flatten {
@@ -1,6 +1,8 @@
 use crate::infer::*;
 use crate::internal::*;
-use crate::utils::MathGen;
+use crate::utils::{
+    axis_vars, broadcast_grad, broadcast_out_index, element_symbol, input_index, unit_vars, MathGen,
+};
 
 use tract_core::ops as mir;
 pub use tract_core::ops::binary::wire_rank_broadcast;
@@ -9,7 +11,62 @@ use tract_core::ops::binary::BinMiniOp;
 #[derive(Debug, Clone, Hash)]
 pub struct InferenceBinOp(pub Box<dyn BinMiniOp>);
 impl_dyn_hash!(InferenceBinOp);
-impl MathGen for InferenceBinOp {}
+// output shape of numpy broadcast of two inputs
+fn broadcast_shape(a: &[usize], b: &[usize]) -> Option<Vec<usize>> {
+    let rank = a.len().max(b.len());
+    let dim = |s: &[usize], i: usize| {
+        if i + s.len() < rank {
+            1
+        } else {
+            s[i + s.len() - rank]
+        }
+    };
+    (0..rank)
+        .map(|i| match (dim(a, i), dim(b, i)) {
+            (x, y) if x == y || y == 1 => Some(x),
+            (1, y) => Some(y),
+            _ => None,
+        })
+        .collect()
+}
+
+impl MathGen for InferenceBinOp {
+    // delta summed over broadcasted axes of input, times other input for mul
+    fn gen_input_grad(
+        &self,
+        delta: String,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        slot: usize,
+        index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        if slot > 1 {
+            return None;
+        }
+        let (shape, other) = (input_shapes.get(slot)?, input_shapes.get(1 - slot)?);
+        let out_shape = broadcast_shape(input_shapes.get(0)?, input_shapes.get(1)?)?;
+        let out_vars = unit_vars(&axis_vars(out_shape.len())?, &out_shape);
+        let (vars, sums) = broadcast_grad(shape, &out_vars, &out_shape);
+        let index = input_index(index, vars.iter().map(|s| s.as_str()))?;
+        let out = broadcast_out_index(shape, &index, &out_vars);
+        let d = element_symbol(&delta, &out);
+        let formula = match (self.0.name(), slot) {
+            ("Add", 0) | ("Add", 1) | ("Sub", 0) => format!("{}{}", sums, d),
+            ("Sub", 1) => format!("-{}{}", sums, d),
+            ("Mul", 0) | ("Mul", 1) => {
+                let (other_index, _) = broadcast_grad(other, &out, &out_shape);
+                format!(
+                    r#"{}{}\cdot {}"#,
+                    sums,
+                    d,
+                    element_symbol(&inputs[1 - slot], &other_index)
+                )
+            }
+            _ => return None,
+        };
+        Some((index, formula))
+    }
+}
 
 impl Expansion for InferenceBinOp {
     fn name(&self) -> Cow<str> {
@@ -2,6 +2,10 @@ use crate::infer::*;
 use crate::internal::*;
 use crate::utils::FormulKind;
 use crate::utils::MathGen;
+use crate::utils::{
+    cartesian, element_symbol, input_index, offset_index, to_strings, valid_range, WindowGeometry,
+    INPUT_VARS, OUTPUT_VARS, WINDOW_VARS,
+};
 
 use tract_core::ops::cnn::conv::ConvUnary;
 use tract_core::ops::cnn::conv::KernelFormat;
@@ -58,6 +62,181 @@ impl MathGen for Conv {
             input = inputs[0]
         )
     }
+    fn gen_element_value(
+        &self,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        coord: Vec<usize>,
+        expand: Option<usize>,
+    ) -> Option<String> {
+        if self.data_format != DataFormat::NCHW
+            || self.kernel_fmt != KernelFormat::OIHW
+            || self.group.unwrap_or(1) != 1
+        {
+            return None;
+        }
+        let k_input = self.k_input.unwrap_or(1);
+        let x_shape = input_shapes.get(0)?;
+        let k_shape = input_shapes.get(k_input)?;
+        let spatial = &x_shape[2..];
+        let kernel = &k_shape[2..];
+        let rank = spatial.len();
+        if rank > WINDOW_VARS.len() {
+            return None;
+        }
+        let ones = tvec![1; rank];
+        let dilations = self.dilations.as_ref().unwrap_or(&ones);
+        let strides = self.strides.as_ref().unwrap_or(&ones);
+        let paddings = self.padding.compute(spatial, kernel, dilations, strides);
+        let bases: Vec<isize> = (0..rank)
+            .map(|i| (coord[2 + i] * strides[i]) as isize - paddings[i].pad_before as isize)
+            .collect();
+        // kernel offsets out of input are padding
+        let ranges: Option<Vec<(usize, usize)>> = (0..rank)
+            .map(|i| valid_range(bases[i], dilations[i], kernel[i], spatial[i]))
+            .collect();
+
+        let (n, co) = (coord[0].to_string(), coord[1].to_string());
+        let conv = match ranges {
+            None => "0".to_string(),
+            Some(r) => {
+                let mut all_ranges = vec![(0, k_shape[1] - 1)];
+                all_ranges.extend(r.iter().cloned());
+                let count: usize = all_ranges.iter().map(|(s, e)| e - s + 1).product();
+                if expand.map(|l| count <= l).unwrap_or(false) {
+                    let terms: Vec<String> = cartesian(&all_ranges)
+                        .iter()
+                        .map(|k| {
+                            let mut w_index = vec![co.clone()];
+                            let mut x_index = vec![n.clone(), k[0].to_string()];
+                            w_index.extend(to_strings(k));
+                            for i in 0..rank {
+                                let p = bases[i] + (k[i + 1] * dilations[i]) as isize;
+                                x_index.push(p.to_string());
+                            }
+                            format!(
+                                r#"{}\cdot {}"#,
+                                element_symbol(&inputs[k_input], &w_index),
+                                element_symbol(&inputs[0], &x_index)
+                            )
+                        })
+                        .collect();
+                    terms.join("+")
+                } else {
+                    let mut sums = format!(r#"\sum_{{c=0}}^{{{}}}"#, k_shape[1] - 1);
+                    let mut w_index = vec![co.clone(), "c".to_string()];
+                    let mut x_index = vec![n.clone(), "c".to_string()];
+                    for i in 0..rank {
+                        let (start, end) = r[i];
+                        sums += &format!(r#"\sum_{{{}={}}}^{{{}}}"#, WINDOW_VARS[i], start, end);
+                        w_index.push(WINDOW_VARS[i].to_string());
+                        x_index.push(offset_index(bases[i], WINDOW_VARS[i], dilations[i]));
+                    }
+                    format!(
+                        r#"{}{}\cdot {}"#,
+                        sums,
+                        element_symbol(&inputs[k_input], &w_index),
+                        element_symbol(&inputs[0], &x_index)
+                    )
+                }
+            }
+        };
+        match self.bias_input {
+            Some(b) => Some(format!("{}+{}", element_symbol(&inputs[b], &[co]), conv)),
+            None => Some(conv),
+        }
+    }
+    fn gen_input_grad(
+        &self,
+        delta: String,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        slot: usize,
+        index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        if self.data_format != DataFormat::NCHW
+            || self.kernel_fmt != KernelFormat::OIHW
+            || self.group.unwrap_or(1) != 1
+        {
+            return None;
+        }
+        let k_input = self.k_input.unwrap_or(1);
+        let x_shape = input_shapes.get(0)?;
+        let k_shape = input_shapes.get(k_input)?;
+        let spatial = &x_shape[2..];
+        let kernel = &k_shape[2..];
+        let rank = spatial.len();
+        if rank > WINDOW_VARS.len() {
+            return None;
+        }
+        let geometry = WindowGeometry::new(
+            &self.padding,
+            spatial,
+            kernel,
+            self.dilations.as_deref(),
+            self.strides.as_deref(),
+        );
+        let batch_sum = format!(r#"\sum_{{b=0}}^{{{}}}"#, x_shape[0] - 1);
+        if slot == 0 {
+            // correlation of delta with flipped kernel
+            let index = input_index(
+                index,
+                ["b", "c"].iter().chain(INPUT_VARS[..rank].iter()).cloned(),
+            )?;
+            let mut sums = format!(r#"\sum_{{k=0}}^{{{}}}"#, k_shape[0] - 1);
+            let mut d_index = vec![index[0].clone(), "k".to_string()];
+            let mut w_index = vec!["k".to_string(), index[1].clone()];
+            let mut conds = Vec::new();
+            for i in 0..rank {
+                sums += &format!(r#"\sum_{{{}=0}}^{{{}}}"#, WINDOW_VARS[i], kernel[i] - 1);
+                let (e, c) = geometry.backward_index(i, &index[2 + i], WINDOW_VARS[i]);
+                d_index.push(e);
+                conds.extend(c);
+                w_index.push(WINDOW_VARS[i].to_string());
+            }
+            let formula = format!(
+                r#"{}{}\cdot {}{}"#,
+                sums,
+                element_symbol(&delta, &d_index),
+                element_symbol(&inputs[k_input], &w_index),
+                conds.join("")
+            );
+            Some((index, formula))
+        } else if slot == k_input {
+            // correlation of input patch with delta
+            let index = input_index(
+                index,
+                ["k", "c"].iter().chain(WINDOW_VARS[..rank].iter()).cloned(),
+            )?;
+            let mut d_index = vec!["b".to_string(), index[0].clone()];
+            let mut x_index = vec!["b".to_string(), index[1].clone()];
+            for i in 0..rank {
+                d_index.push(OUTPUT_VARS[i].to_string());
+                x_index.push(geometry.forward_index(i, OUTPUT_VARS[i], &index[2 + i]));
+            }
+            let formula = format!(
+                r#"{}{}{}\cdot {}"#,
+                batch_sum,
+                geometry.output_sums(),
+                element_symbol(&delta, &d_index),
+                element_symbol(&inputs[0], &x_index)
+            );
+            Some((index, formula))
+        } else if Some(slot) == self.bias_input {
+            let index = input_index(index, ["k"].iter().cloned())?;
+            let mut d_index = vec!["b".to_string(), index[0].clone()];
+            d_index.extend(OUTPUT_VARS[..rank].iter().map(|s| s.to_string()));
+            let formula = format!(
+                "{}{}{}",
+                batch_sum,
+                geometry.output_sums(),
+                element_symbol(&delta, &d_index)
+            );
+            Some((index, formula))
+        } else {
+            None
+        }
+    }
 }
 
 impl Conv {
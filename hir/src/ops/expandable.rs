@@ -81,6 +81,29 @@ impl MathGen for Box<dyn Expansion> {
     fn gen_backward_value(&self, inputs: Vec<String>) -> Option<String> {
         self.as_ref().gen_backward_value(inputs)
     }
+
+    fn gen_element_value(
+        &self,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        coord: Vec<usize>,
+        expand: Option<usize>,
+    ) -> Option<String> {
+        self.as_ref()
+            .gen_element_value(inputs, input_shapes, coord, expand)
+    }
+
+    fn gen_input_grad(
+        &self,
+        delta: String,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        slot: usize,
+        index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        self.as_ref()
+            .gen_input_grad(delta, inputs, input_shapes, slot, index)
+    }
 }
 
 impl Op for Box<dyn Expansion> {
@@ -8,14 +8,14 @@ use tract_core::{
     model::Op,
     ops::{
         array::{Gather, Pad},
-        cnn::{MaxPool, PoolSpec, SumPool},
+        cnn::{MaxPool, PaddingSpec, PoolSpec, SumPool},
         dummy::Dummy,
         element_wise::ElementWiseOp,
         identity::Identity,
         konst::Const,
         logic::Iff,
         math::{Add, Max, Min},
-        nn::Sigmoid,
+        nn::{DataFormat, Sigmoid},
         unimpl::UnimplementedOp,
     },
 };
@@ -25,7 +25,7 @@ use crate::ops::{
     binary::Nary,
 };
 
-pub trait MathGen: DynClone {
+pub trait MathGen: DynClone + Send + Sync {
     fn get_original_type(&self) -> FormulKind {
         let result = FormulKind::Undefined;
         // println!("default called: {:?}",result);
@@ -57,6 +57,28 @@ pub trait MathGen: DynClone {
     fn gen_backward_value(&self, _inputs: Vec<String>) -> Option<String> {
         None
     }
+    // formula of one output element, expand sums when terms are not more than expand
+    fn gen_element_value(
+        &self,
+        _inputs: Vec<String>,
+        _input_shapes: Vec<Vec<usize>>,
+        _coord: Vec<usize>,
+        _expand: Option<usize>,
+    ) -> Option<String> {
+        None
+    }
+    // gradient of input slot element from upstream gradient delta, (index of input element, formula)
+    // index is symbolic when not given
+    fn gen_input_grad(
+        &self,
+        _delta: String,
+        _inputs: Vec<String>,
+        _input_shapes: Vec<Vec<usize>>,
+        _slot: usize,
+        _index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        None
+    }
     fn attributes(&self) -> HashMap<String, String> {
         HashMap::new()
     }
@@ -136,6 +158,421 @@ pub fn gen_symbol(symbol: Option<String>, n_type: FormulKind, idx: usize) -> Str
     }
 }
 
+// symbol with element index ex) {W_1}_{(0,1)}
+pub fn element_symbol(symbol: &str, index: &[String]) -> String {
+    format!("{{{}}}_{{({})}}", symbol, index.join(","))
+}
+// index expression base + scale * var
+pub fn offset_index(base: isize, var: &str, scale: usize) -> String {
+    let v = if scale == 1 {
+        var.to_string()
+    } else {
+        format!("{}{}", scale, var)
+    };
+    match base {
+        0 => v,
+        b if b > 0 => format!("{}+{}", b, v),
+        b => format!("{}{}", v, b),
+    }
+}
+// kernel offsets (first, last) which stay inside input, None if all are padding
+pub fn valid_range(
+    base: isize,
+    dilation: usize,
+    kernel: usize,
+    size: usize,
+) -> Option<(usize, usize)> {
+    let valid: Vec<usize> = (0..kernel)
+        .filter(|k| {
+            let p = base + (*k * dilation) as isize;
+            p >= 0 && p < size as isize
+        })
+        .collect();
+    Some((*valid.first()?, *valid.last()?))
+}
+// every index in inclusive ranges
+pub fn cartesian(ranges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut result = vec![Vec::new()];
+    for (start, end) in ranges.iter() {
+        result = result
+            .into_iter()
+            .flat_map(|prefix| {
+                (*start..=*end).map(move |i| {
+                    let mut next = prefix.clone();
+                    next.push(i);
+                    next
+                })
+            })
+            .collect();
+    }
+    result
+}
+// element index of broadcasted input
+pub fn broadcast_index(shape: &[usize], coord: &[usize]) -> Vec<usize> {
+    let offset = coord.len() - shape.len();
+    shape
+        .iter()
+        .enumerate()
+        .map(|(i, d)| if *d == 1 { 0 } else { coord[offset + i] })
+        .collect()
+}
+pub fn to_strings(index: &[usize]) -> Vec<String> {
+    index.iter().map(|s| s.to_string()).collect()
+}
+// sum variables of kernel window
+pub const WINDOW_VARS: [&str; 3] = ["m", "n", "o"];
+// given index of input element or symbolic variables
+pub fn input_index<'a>(
+    given: Option<Vec<String>>,
+    vars: impl Iterator<Item = &'a str>,
+) -> Option<Vec<String>> {
+    let vars: Vec<String> = vars.map(|s| s.to_string()).collect();
+    match given {
+        Some(g) if g.len() == vars.len() => Some(g),
+        Some(_) => None,
+        None => Some(vars),
+    }
+}
+// index of broadcasted input and sums over broadcasted axes of output
+pub fn broadcast_grad(
+    shape: &[usize],
+    out_vars: &[String],
+    out_shape: &[usize],
+) -> (Vec<String>, String) {
+    let offset = out_shape.len() - shape.len();
+    let mut index = Vec::new();
+    let mut sums = String::new();
+    for (i, v) in out_vars.iter().enumerate() {
+        let broadcasted = i < offset || shape[i - offset] == 1;
+        if i >= offset {
+            index.push(if broadcasted {
+                "0".to_string()
+            } else {
+                v.clone()
+            });
+        }
+        if broadcasted && out_shape[i] > 1 {
+            sums += &format!(r#"\sum_{{{}=0}}^{{{}}}"#, v, out_shape[i] - 1);
+        }
+    }
+    (index, sums)
+}
+// variables with 0 on axes of size 1, which have no sum to bind them
+pub fn unit_vars(vars: &[String], shape: &[usize]) -> Vec<String> {
+    vars.iter()
+        .zip(shape.iter())
+        .map(|(v, s)| if *s == 1 { "0".to_string() } else { v.clone() })
+        .collect()
+}
+// variables of each axis ex) (b,c,h,w)
+pub fn axis_vars(rank: usize) -> Option<Vec<String>> {
+    let vars: Vec<&str> = match rank {
+        0 => vec![],
+        1 => vec!["c"],
+        r if r <= 2 + INPUT_VARS.len() => ["b", "c"]
+            .iter()
+            .chain(INPUT_VARS[..r - 2].iter())
+            .cloned()
+            .collect(),
+        _ => return None,
+    };
+    Some(vars.iter().map(|s| s.to_string()).collect())
+}
+// output index of broadcasted input element, broadcasted axes keep output variables
+pub fn broadcast_out_index(shape: &[usize], index: &[String], out_vars: &[String]) -> Vec<String> {
+    let offset = out_vars.len() - shape.len();
+    let mut out = out_vars.to_vec();
+    for (i, v) in index.iter().enumerate() {
+        if shape[i] != 1 {
+            out[offset + i] = v.clone();
+        }
+    }
+    out
+}
+// output position variables of backward sums
+pub const OUTPUT_VARS: [&str; 3] = ["i", "j", "l"];
+// input position variables of backward
+pub const INPUT_VARS: [&str; 3] = ["h", "w", "d"];
+// strides, dilations, pad before and output size of window op
+pub struct WindowGeometry {
+    pub strides: Vec<usize>,
+    pub dilations: Vec<usize>,
+    pub pads: Vec<usize>,
+    pub outputs: Vec<usize>,
+}
+impl WindowGeometry {
+    pub fn new(
+        padding: &PaddingSpec,
+        spatial: &[usize],
+        kernel: &[usize],
+        dilations: Option<&[usize]>,
+        strides: Option<&[usize]>,
+    ) -> Self {
+        let rank = spatial.len();
+        let dilations = dilations.map(|d| d.to_vec()).unwrap_or(vec![1; rank]);
+        let strides = strides.map(|d| d.to_vec()).unwrap_or(vec![1; rank]);
+        let computed = padding.compute(spatial, kernel, &dilations, &strides);
+        WindowGeometry {
+            pads: computed.iter().map(|c| c.pad_before).collect(),
+            outputs: computed.iter().map(|c| c.convoluted).collect(),
+            strides,
+            dilations,
+        }
+    }
+    // input position read by output var through kernel var, ex) 2i+m-1
+    pub fn forward_index(&self, axis: usize, out_var: &str, k_var: &str) -> String {
+        let pad = self.pads[axis] as isize;
+        match k_var.parse::<usize>() {
+            Ok(k) => offset_index(
+                (k * self.dilations[axis]) as isize - pad,
+                out_var,
+                self.strides[axis],
+            ),
+            Err(_) => format!(
+                "{}+{}",
+                offset_index(0, out_var, self.strides[axis]),
+                offset_index(-pad, k_var, self.dilations[axis])
+            ),
+        }
+    }
+    // output position which reads input var through kernel var, with conditions that
+    // stride divides it and it is inside output
+    pub fn backward_index(&self, axis: usize, in_var: &str, k_var: &str) -> (String, Vec<String>) {
+        let pad = self.pads[axis];
+        let mut e = match in_var.parse::<usize>() {
+            Ok(v) => (v + pad).to_string(),
+            Err(_) if pad > 0 => format!("{}+{}", in_var, pad),
+            Err(_) => in_var.to_string(),
+        };
+        e += &format!("-{}", offset_index(0, k_var, self.dilations[axis]));
+        let (out, mut conds) = match self.strides[axis] {
+            1 => (e, Vec::new()),
+            s => (
+                format!(r#"\frac{{{}}}{{{}}}"#, e, s),
+                vec![format!(r#"[{} \mid {}]"#, s, e)],
+            ),
+        };
+        conds.push(format!(r#"[0\le {}<{}]"#, out, self.outputs[axis]));
+        (out, conds)
+    }
+    // count of kernel positions inside input for window which reads input var through kernel var
+    // ex) (\min(2,4-h+m)-\max(0,m-h)+1)
+    pub fn window_count(
+        &self,
+        axis: usize,
+        in_var: &str,
+        k_var: &str,
+        size: usize,
+        kernel: usize,
+    ) -> String {
+        let d = self.dilations[axis];
+        let (upper, lower) = match (in_var.parse::<usize>(), d) {
+            (Ok(v), _) => (
+                offset_index(((size - 1 - v) / d) as isize, k_var, 1),
+                offset_index(-((v / d) as isize), k_var, 1),
+            ),
+            (Err(_), 1) => (
+                format!("{}-{}+{}", size - 1, in_var, k_var),
+                format!("{}-{}", k_var, in_var),
+            ),
+            (Err(_), d) => (
+                format!(
+                    r#"{}+\lfloor\frac{{{}-{}}}{{{}}}\rfloor"#,
+                    k_var,
+                    size - 1,
+                    in_var,
+                    d
+                ),
+                format!(r#"{}-\lfloor\frac{{{}}}{{{}}}\rfloor"#, k_var, in_var, d),
+            ),
+        };
+        format!(r#"(\min({},{})-\max(0,{})+1)"#, kernel - 1, upper, lower)
+    }
+    // sums over every output position
+    pub fn output_sums(&self) -> String {
+        self.outputs
+            .iter()
+            .enumerate()
+            .map(|(i, o)| format!(r#"\sum_{{{}=0}}^{{{}}}"#, OUTPUT_VARS[i], o - 1))
+            .collect()
+    }
+}
+// how pooling spreads delta over window
+enum PoolGrad {
+    Max,
+    Sum,
+    // divided by fixed window size
+    Average(usize),
+    // divided by count of window positions inside input
+    AverageValid,
+}
+// gradient of pooling input, max routes delta to argmax of window, sum spreads delta over window
+fn gen_pool_input_grad(
+    pool_spec: &PoolSpec,
+    grad: PoolGrad,
+    delta: &str,
+    input: &str,
+    input_shape: &[usize],
+    index: Option<Vec<String>>,
+) -> Option<(Vec<String>, String)> {
+    let (prefix, geo) = match pool_spec.data_format {
+        DataFormat::NCHW => (vec!["b", "c"], 2),
+        DataFormat::CHW => (vec!["c"], 1),
+        _ => return None,
+    };
+    let spatial = &input_shape[geo..];
+    let rank = spatial.len();
+    if rank > WINDOW_VARS.len() {
+        return None;
+    }
+    let index = input_index(
+        index,
+        prefix.iter().chain(INPUT_VARS[..rank].iter()).cloned(),
+    )?;
+    let prefix = index[..geo].to_vec();
+    let kernel: Vec<usize> = pool_spec.kernel_shape.iter().cloned().collect();
+    let geometry = WindowGeometry::new(
+        &pool_spec.padding,
+        spatial,
+        &kernel,
+        pool_spec.dilations.as_deref(),
+        pool_spec.strides.as_deref(),
+    );
+    let formula = match grad {
+        PoolGrad::Max => {
+            // input position of window maximum, (m^*,n^*) is offset of maximum in window
+            let stars: Vec<String> = WINDOW_VARS[..rank]
+                .iter()
+                .map(|k| format!("{}^{{*}}", k))
+                .collect();
+            let mut d_index = prefix.clone();
+            let mut x_index = prefix.clone();
+            let mut max_index = Vec::new();
+            for i in 0..rank {
+                d_index.push(OUTPUT_VARS[i].to_string());
+                x_index.push(geometry.forward_index(i, OUTPUT_VARS[i], WINDOW_VARS[i]));
+                max_index.push(geometry.forward_index(i, OUTPUT_VARS[i], &stars[i]));
+            }
+            format!(
+                r#"{}{}[({})=({})],\ ({})=\mathrm{{argmax}}_{{({})}}{}"#,
+                geometry.output_sums(),
+                element_symbol(delta, &d_index),
+                index[geo..].join(","),
+                max_index.join(","),
+                stars.join(","),
+                WINDOW_VARS[..rank].join(","),
+                element_symbol(input, &x_index)
+            )
+        }
+        _ => {
+            let mut sums = String::new();
+            let mut d_index = prefix.clone();
+            let mut conds = Vec::new();
+            let mut counts = Vec::new();
+            for i in 0..rank {
+                sums += &format!(r#"\sum_{{{}=0}}^{{{}}}"#, WINDOW_VARS[i], kernel[i] - 1);
+                let (e, c) = geometry.backward_index(i, &index[geo + i], WINDOW_VARS[i]);
+                d_index.push(e);
+                conds.extend(c);
+                counts.push(geometry.window_count(
+                    i,
+                    &index[geo + i],
+                    WINDOW_VARS[i],
+                    spatial[i],
+                    kernel[i],
+                ));
+            }
+            let scale = match grad {
+                PoolGrad::Average(n) => format!(r#"\frac{{1}}{{{}}}"#, n),
+                PoolGrad::AverageValid => format!(r#"\frac{{1}}{{{}}}"#, counts.join(r#"\cdot "#)),
+                _ => String::new(),
+            };
+            format!(
+                "{}{}{}{}",
+                sums,
+                scale,
+                element_symbol(delta, &d_index),
+                conds.join("")
+            )
+        }
+    };
+    Some((index, formula))
+}
+// window reduction of pooling, reduce is \max or \sum
+fn gen_pool_element(
+    pool_spec: &PoolSpec,
+    reduce: &str,
+    input: &str,
+    input_shape: &[usize],
+    coord: &[usize],
+    expand: Option<usize>,
+) -> Option<(String, usize)> {
+    let (batch, channel, geo) = match pool_spec.data_format {
+        DataFormat::NCHW => (Some(0), 1, 2),
+        DataFormat::CHW => (None, 0, 1),
+        _ => return None,
+    };
+    let spatial = &input_shape[geo..];
+    let rank = spatial.len();
+    if rank > WINDOW_VARS.len() {
+        return None;
+    }
+    let kernel: Vec<usize> = pool_spec.kernel_shape.iter().cloned().collect();
+    let dilations: Vec<usize> = pool_spec
+        .dilations
+        .clone()
+        .map(|d| d.to_vec())
+        .unwrap_or(vec![1; rank]);
+    let strides: Vec<usize> = pool_spec
+        .strides
+        .clone()
+        .map(|d| d.to_vec())
+        .unwrap_or(vec![1; rank]);
+    let paddings = pool_spec
+        .padding
+        .compute(spatial, &kernel, &dilations, &strides);
+    let bases: Vec<isize> = (0..rank)
+        .map(|i| (coord[geo + i] * strides[i]) as isize - paddings[i].pad_before as isize)
+        .collect();
+    let ranges: Vec<(usize, usize)> = (0..rank)
+        .map(|i| valid_range(bases[i], dilations[i], kernel[i], spatial[i]))
+        .collect::<Option<Vec<_>>>()?;
+    let prefix: Vec<String> = batch
+        .iter()
+        .chain(Some(channel).iter())
+        .map(|i| coord[*i].to_string())
+        .collect();
+    let count = ranges.iter().map(|(s, e)| e - s + 1).product();
+    if expand.map(|l| count <= l).unwrap_or(false) {
+        let terms: Vec<String> = cartesian(&ranges)
+            .iter()
+            .map(|k| {
+                let mut index = prefix.clone();
+                for i in 0..rank {
+                    index.push((bases[i] + (k[i] * dilations[i]) as isize).to_string());
+                }
+                element_symbol(input, &index)
+            })
+            .collect();
+        let inner = if reduce == r#"\max"# {
+            format!(r#"\max({})"#, terms.join(","))
+        } else {
+            terms.join("+")
+        };
+        Some((inner, count))
+    } else {
+        let mut result = String::new();
+        let mut index = prefix.clone();
+        for i in 0..rank {
+            result += &format!(
+                "{}_{{{}={}}}^{{{}}}",
+                reduce, WINDOW_VARS[i], ranges[i].0, ranges[i].1
+            );
+            index.push(offset_index(bases[i], WINDOW_VARS[i], dilations[i]));
+        }
+        Some((result + &element_symbol(input, &index), count))
+    }
+}
 pub fn mathgen_op<T: Op + MathGen + Clone>(op: &dyn Op) -> Option<impl MathGen> {
     op.downcast_ref::<T>().cloned()
 }
@@ -169,6 +606,59 @@ impl MathGen for SumPool {
     ) -> String {
         format!(r#"\sum {}"#, inputs[0])
     }
+    fn gen_element_value(
+        &self,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        coord: Vec<usize>,
+        expand: Option<usize>,
+    ) -> Option<String> {
+        let (inner, count) = gen_pool_element(
+            &self.pool_spec,
+            r#"\sum"#,
+            &inputs[0],
+            input_shapes.get(0)?,
+            &coord,
+            expand,
+        )?;
+        if self.normalize {
+            let divisor = if self.count_include_pad {
+                self.pool_spec.kernel_shape.iter().product()
+            } else {
+                count
+            };
+            Some(format!(r#"\frac{{1}}{{{}}}({})"#, divisor, inner))
+        } else {
+            Some(inner)
+        }
+    }
+    fn gen_input_grad(
+        &self,
+        delta: String,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        slot: usize,
+        index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        if slot != 0 {
+            return None;
+        }
+        let pads = self.pool_spec.padding != PaddingSpec::Valid;
+        let grad = match (self.normalize, self.count_include_pad || !pads) {
+            (false, _) => PoolGrad::Sum,
+            (true, true) => PoolGrad::Average(self.pool_spec.kernel_shape.iter().product()),
+            // window size without padding, same count as forward
+            (true, false) => PoolGrad::AverageValid,
+        };
+        gen_pool_input_grad(
+            &self.pool_spec,
+            grad,
+            &delta,
+            &inputs[0],
+            input_shapes.get(0)?,
+            index,
+        )
+    }
 }
 impl MathGen for PoolSpec {}
 impl MathGen for MaxPool {
@@ -198,6 +688,43 @@ impl MathGen for MaxPool {
             kw = kernel[1] - 1
         )
     }
+    fn gen_element_value(
+        &self,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        coord: Vec<usize>,
+        expand: Option<usize>,
+    ) -> Option<String> {
+        gen_pool_element(
+            &self.pool_spec,
+            r#"\max"#,
+            &inputs[0],
+            input_shapes.get(0)?,
+            &coord,
+            expand,
+        )
+        .map(|(inner, _)| inner)
+    }
+    fn gen_input_grad(
+        &self,
+        delta: String,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        slot: usize,
+        index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        if slot != 0 {
+            return None;
+        }
+        gen_pool_input_grad(
+            &self.pool_spec,
+            PoolGrad::Max,
+            &delta,
+            &inputs[0],
+            input_shapes.get(0)?,
+            index,
+        )
+    }
 }
 impl MathGen for UnimplementedOp {}
 impl MathGen for Iff {}
@@ -217,4 +744,246 @@ impl MathGen for Sigmoid {
     ) -> String {
         format!(r#"\frac{{1}}{{1+e^{{-({})}}}}"#, inputs[0])
     }
+    fn gen_element_value(
+        &self,
+        inputs: Vec<String>,
+        _input_shapes: Vec<Vec<usize>>,
+        coord: Vec<usize>,
+        _expand: Option<usize>,
+    ) -> Option<String> {
+        let x = element_symbol(&inputs[0], &to_strings(&coord));
+        Some(self.gen_forward_value(vec![x], None, None))
+    }
+    fn gen_input_grad(
+        &self,
+        delta: String,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        slot: usize,
+        index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        if slot != 0 {
+            return None;
+        }
+        let vars = axis_vars(input_shapes.get(0)?.len())?;
+        let index = input_index(index, vars.iter().map(|s| s.as_str()))?;
+        let x = element_symbol(&inputs[0], &index);
+        let formula = format!(
+            r#"{}\frac{{e^{{-{x}}}}}{{(1+e^{{-{x}}})^{{2}}}}"#,
+            element_symbol(&delta, &index),
+            x = x
+        );
+        Some((index, formula))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tract_core::internal::tvec;
+
+    #[test]
+    fn window_range() {
+        // 3x3 kernel at top left corner with padding 1
+        assert_eq!(valid_range(-1, 1, 3, 5), Some((1, 2)));
+        assert_eq!(valid_range(3, 1, 3, 5), Some((0, 1)));
+        assert_eq!(valid_range(-4, 1, 3, 5), None);
+    }
+
+    #[test]
+    fn index_expression() {
+        assert_eq!(offset_index(0, "m", 1), "m");
+        assert_eq!(offset_index(9, "m", 1), "9+m");
+        assert_eq!(offset_index(-1, "n", 2), "2n-1");
+    }
+
+    #[test]
+    fn element_index() {
+        assert_eq!(cartesian(&[(0, 1), (2, 3)]).len(), 4);
+        assert_eq!(broadcast_index(&[1, 4], &[2, 3]), vec![0, 3]);
+        assert_eq!(element_symbol("W_1", &to_strings(&[0, 1])), "{W_1}_{(0,1)}");
+    }
+
+    #[test]
+    fn broadcast_gradient() {
+        let vars = vec!["i".to_string(), "j".to_string()];
+        assert_eq!(
+            broadcast_grad(&[4], &vars, &[3, 4]),
+            (vec!["j".to_string()], r#"\sum_{i=0}^{2}"#.to_string())
+        );
+        assert_eq!(
+            broadcast_grad(&[1, 4], &vars, &[3, 4]).0,
+            vec!["0".to_string(), "j".to_string()]
+        );
+        assert_eq!(
+            unit_vars(&vars, &[1, 4]),
+            vec!["0".to_string(), "j".to_string()]
+        );
+    }
+
+    #[test]
+    fn window_backward_index() {
+        let geometry = WindowGeometry {
+            strides: vec![2, 1],
+            dilations: vec![1, 1],
+            pads: vec![1, 0],
+            outputs: vec![3, 4],
+        };
+        assert_eq!(geometry.forward_index(0, "i", "m"), "2i+m-1");
+        assert_eq!(geometry.forward_index(1, "j", "n"), "j+n");
+        assert_eq!(geometry.forward_index(0, "i", "2"), "1+2i");
+        assert_eq!(
+            geometry.backward_index(0, "h", "m"),
+            (
+                r#"\frac{h+1-m}{2}"#.to_string(),
+                vec![
+                    r#"[2 \mid h+1-m]"#.to_string(),
+                    r#"[0\le \frac{h+1-m}{2}<3]"#.to_string()
+                ]
+            )
+        );
+        assert_eq!(
+            geometry.backward_index(1, "w", "n"),
+            ("w-n".to_string(), vec![r#"[0\le w-n<4]"#.to_string()])
+        );
+        assert_eq!(
+            geometry.backward_index(1, "3", "n"),
+            ("3-n".to_string(), vec![r#"[0\le 3-n<4]"#.to_string()])
+        );
+        assert_eq!(
+            geometry.window_count(1, "w", "n", 5, 3),
+            r#"(\min(2,4-w+n)-\max(0,n-w)+1)"#
+        );
+        assert_eq!(
+            geometry.window_count(1, "0", "n", 5, 3),
+            r#"(\min(2,4+n)-\max(0,n)+1)"#
+        );
+        assert_eq!(geometry.output_sums(), r#"\sum_{i=0}^{2}\sum_{j=0}^{3}"#);
+    }
+
+    fn pool_spec(pad: usize, stride: usize) -> PoolSpec {
+        PoolSpec {
+            data_format: DataFormat::NCHW,
+            kernel_shape: tvec![3, 3],
+            padding: PaddingSpec::Explicit(tvec![pad, pad], tvec![pad, pad], false),
+            dilations: None,
+            strides: Some(tvec![stride, stride]),
+            output_channel_override: None,
+        }
+    }
+
+    fn input_grad(
+        op: &dyn MathGen,
+        inputs: &[&str],
+        shapes: Vec<Vec<usize>>,
+        slot: usize,
+    ) -> String {
+        let inputs = inputs.iter().map(|s| s.to_string()).collect();
+        op.gen_input_grad(r#"\delta"#.to_string(), inputs, shapes, slot, None)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn binary_input_grad() {
+        use crate::ops::binary::InferenceBinOp;
+        use tract_core::ops::math::{Mul, Sub};
+        // bias of shape (3) is summed over batch axis
+        let add = InferenceBinOp(Box::new(Add));
+        let shapes = vec![vec![2, 3], vec![3]];
+        assert_eq!(
+            input_grad(&add, &["x", "y"], shapes.clone(), 1),
+            r#"\sum_{b=0}^{1}{\delta}_{(b,c)}"#
+        );
+        assert_eq!(
+            input_grad(
+                &InferenceBinOp(Box::new(Sub)),
+                &["x", "y"],
+                shapes.clone(),
+                1
+            ),
+            r#"-\sum_{b=0}^{1}{\delta}_{(b,c)}"#
+        );
+        assert_eq!(
+            input_grad(&InferenceBinOp(Box::new(Mul)), &["x", "y"], shapes, 0),
+            r#"{\delta}_{(b,c)}\cdot {y}_{(c)}"#
+        );
+    }
+
+    #[test]
+    fn sum_pool_input_grad() {
+        // average without padding count divides by valid window positions
+        let pool = SumPool::new(pool_spec(1, 1), false, true);
+        assert_eq!(
+            input_grad(&pool, &["x"], vec![vec![1, 1, 4, 4]], 0),
+            concat!(
+                r#"\sum_{m=0}^{2}\sum_{n=0}^{2}"#,
+                r#"\frac{1}{(\min(2,3-h+m)-\max(0,m-h)+1)\cdot (\min(2,3-w+n)-\max(0,n-w)+1)}"#,
+                r#"{\delta}_{(b,c,h+1-m,w+1-n)}[0\le h+1-m<4][0\le w+1-n<4]"#
+            )
+        );
+        let pool = SumPool::new(pool_spec(0, 2), true, true);
+        assert_eq!(
+            input_grad(&pool, &["x"], vec![vec![1, 1, 5, 5]], 0),
+            concat!(
+                r#"\sum_{m=0}^{2}\sum_{n=0}^{2}\frac{1}{9}{\delta}_{(b,c,\frac{h-m}{2},\frac{w-n}{2})}"#,
+                r#"[2 \mid h-m][0\le \frac{h-m}{2}<2][2 \mid w-n][0\le \frac{w-n}{2}<2]"#
+            )
+        );
+    }
+
+    #[test]
+    fn max_pool_input_grad() {
+        let pool = MaxPool::new(pool_spec(0, 1), None);
+        assert_eq!(
+            input_grad(&pool, &["x"], vec![vec![1, 1, 4, 4]], 0),
+            concat!(
+                r#"\sum_{i=0}^{1}\sum_{j=0}^{1}{\delta}_{(b,c,i,j)}"#,
+                r#"[(h,w)=(i+m^{*},j+n^{*})],\ "#,
+                r#"(m^{*},n^{*})=\mathrm{argmax}_{(m,n)}{x}_{(b,c,i+m,j+n)}"#
+            )
+        );
+        // input position is stride times output plus offset of maximum minus padding
+        let pool = MaxPool::new(pool_spec(1, 2), None);
+        assert_eq!(
+            input_grad(&pool, &["x"], vec![vec![1, 1, 4, 4]], 0),
+            concat!(
+                r#"\sum_{i=0}^{1}\sum_{j=0}^{1}{\delta}_{(b,c,i,j)}"#,
+                r#"[(h,w)=(2i+m^{*}-1,2j+n^{*}-1)],\ "#,
+                r#"(m^{*},n^{*})=\mathrm{argmax}_{(m,n)}{x}_{(b,c,2i+m-1,2j+n-1)}"#
+            )
+        );
+    }
+
+    #[test]
+    fn sigmoid_input_grad() {
+        assert_eq!(
+            input_grad(&Sigmoid {}, &["x"], vec![vec![1, 4]], 0),
+            r#"{\delta}_{(b,c)}\frac{e^{-{x}_{(b,c)}}}{(1+e^{-{x}_{(b,c)}})^{2}}"#
+        );
+    }
+
+    #[test]
+    fn conv_input_grad() {
+        let conv = crate::ops::cnn::Conv {
+            strides: Some(tvec![2, 2]),
+            ..Default::default()
+        };
+        let shapes = vec![vec![1, 2, 5, 5], vec![3, 2, 3, 3]];
+        assert_eq!(
+            input_grad(&conv, &["x", "W"], shapes.clone(), 0),
+            concat!(
+                r#"\sum_{k=0}^{2}\sum_{m=0}^{2}\sum_{n=0}^{2}"#,
+                r#"{\delta}_{(b,k,\frac{h-m}{2},\frac{w-n}{2})}\cdot {W}_{(k,c,m,n)}"#,
+                r#"[2 \mid h-m][0\le \frac{h-m}{2}<2][2 \mid w-n][0\le \frac{w-n}{2}<2]"#
+            )
+        );
+        assert_eq!(
+            input_grad(&conv, &["x", "W"], shapes, 1),
+            concat!(
+                r#"\sum_{b=0}^{0}\sum_{i=0}^{1}\sum_{j=0}^{1}"#,
+                r#"{\delta}_{(b,k,i,j)}\cdot {x}_{(b,c,2i+m,2j+n)}"#
+            )
+        );
+    }
 }
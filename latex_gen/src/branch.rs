@@ -0,0 +1,235 @@
+use tract_onnx::prelude::InferenceModel;
+
+use crate::{DiffChainNode, LatexEngine, LatexNode};
+
+// all nodes reachable from node including itself
+pub(crate) fn descendants(symbol_map: &Vec<Option<LatexNode>>, from: usize) -> Vec<usize> {
+    let mut result = Vec::new();
+    let mut stack = vec![from];
+    while let Some(n) = stack.pop() {
+        if result.contains(&n) {
+            continue;
+        }
+        result.push(n);
+        if let Some(node) = symbol_map[n].as_ref() {
+            stack.extend(node.outputs.iter().cloned());
+        }
+    }
+    result
+}
+
+fn outputs(symbol_map: &[Option<LatexNode>], n: usize) -> &[usize] {
+    symbol_map[n]
+        .as_ref()
+        .map(|s| s.outputs.as_slice())
+        .unwrap_or(&[])
+}
+
+// descendants of every node as bit sets, built in one pass of reverse topological order
+pub(crate) struct Reach {
+    sets: Vec<Vec<u64>>,
+}
+
+impl Reach {
+    pub fn new(symbol_map: &[Option<LatexNode>]) -> Self {
+        let len = symbol_map.len();
+        let words = len.div_ceil(64);
+        // post order over outputs, every node comes after its outputs
+        let mut order = Vec::with_capacity(len);
+        let mut visited = vec![false; len];
+        for start in 0..len {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut stack = vec![(start, 0)];
+            while let Some((n, k)) = stack.pop() {
+                match outputs(symbol_map, n).get(k) {
+                    Some(o) => {
+                        stack.push((n, k + 1));
+                        if !visited[*o] {
+                            visited[*o] = true;
+                            stack.push((*o, 0));
+                        }
+                    }
+                    None => order.push(n),
+                }
+            }
+        }
+        let mut sets = vec![Vec::new(); len];
+        for n in order.into_iter() {
+            let mut set = vec![0u64; words];
+            set[n / 64] |= 1 << (n % 64);
+            for o in outputs(symbol_map, n).iter() {
+                set.iter_mut()
+                    .zip(sets[*o].iter())
+                    .for_each(|(a, b)| *a |= *b);
+            }
+            sets[n] = set;
+        }
+        Reach { sets }
+    }
+    // to is reachable from node from
+    pub fn contains(&self, from: usize, to: usize) -> bool {
+        self.sets[from][to / 64] & (1 << (to % 64)) != 0
+    }
+}
+
+impl LatexEngine {
+    // outputs of node which lead to error node, first output if none
+    pub(crate) fn downstream(
+        symbol_map: &Vec<Option<LatexNode>>,
+        reach: &Reach,
+        i: usize,
+        error_node: usize,
+    ) -> Vec<usize> {
+        let node = symbol_map[i].as_ref().unwrap();
+        let mut outs = Vec::new();
+        for o in node.outputs.iter() {
+            if !outs.contains(o) && reach.contains(*o, error_node) {
+                outs.push(*o);
+            }
+        }
+        if outs.is_empty() {
+            outs.extend(node.outputs.first().cloned());
+        }
+        outs
+    }
+    // nearest node where all paths from outs meet
+    pub(crate) fn merge_point(reach: &Reach, outs: &[usize]) -> Option<usize> {
+        let len = reach.sets.len();
+        let common: Vec<usize> = (0..len)
+            .filter(|n| outs.iter().all(|o| reach.contains(*o, *n)))
+            .collect();
+        common
+            .iter()
+            .cloned()
+            .find(|c| common.iter().all(|n| reach.contains(*c, *n)))
+    }
+    // sum over output elements of each downstream path of node,
+    // paths which meet again end at named gradient of merge node, defined once in paths
+    pub(crate) fn downstream_sum(
+        &self,
+        symbol_map: &Vec<Option<LatexNode>>,
+        reach: &Reach,
+        stops: &[usize],
+        model: &InferenceModel,
+        i: usize,
+        error_node: usize,
+    ) -> DiffChainNode {
+        if stops.contains(&i) {
+            return DiffChainNode::Delta(i, symbol_map[i].as_ref().unwrap().symbol.clone());
+        }
+        let outs = Self::downstream(symbol_map, reach, i, error_node);
+        let merge = if outs.len() > 1 {
+            Self::merge_point(reach, &outs)
+        } else {
+            None
+        };
+        // error node ends every path already
+        let mut path_stops = stops.to_vec();
+        path_stops.extend(merge.filter(|m| *m != error_node));
+        let mut paths: Vec<DiffChainNode> = outs
+            .iter()
+            .map(|o| {
+                let in_node = symbol_map[*o].as_ref().unwrap();
+                let sum = self.expand_diff(
+                    symbol_map,
+                    reach,
+                    &path_stops,
+                    model,
+                    self.diff_node(model, symbol_map, *o),
+                    error_node,
+                );
+                let size_check = if in_node.output_shape.len() > 1 {
+                    in_node.output_shape.split_at(1).1.to_vec()
+                } else {
+                    vec![in_node.output_shape[0]]
+                };
+                DiffChainNode::Sum(Box::new(sum), size_check)
+            })
+            .collect();
+        if paths.len() == 1 {
+            return paths.remove(0);
+        }
+        let merge = merge.map(|m| {
+            let symbol = symbol_map[m].as_ref().unwrap().symbol.clone();
+            let tail = if m != error_node {
+                Some(Box::new(self.downstream_sum(
+                    symbol_map, reach, stops, model, m, error_node,
+                )))
+            } else {
+                None
+            };
+            (m, symbol, tail)
+        });
+        DiffChainNode::Paths(paths, merge)
+    }
+}
+
+#[test]
+fn branch_test() {
+    // 0 -> 1 -> 3 -> 4, 0 -> 2 -> 3, 0 -> 5
+    let links: Vec<Vec<usize>> = vec![vec![1, 2, 5], vec![3], vec![3], vec![4], vec![], vec![]];
+    let symbol_map: Vec<Option<LatexNode>> = links
+        .into_iter()
+        .enumerate()
+        .map(|(i, outputs)| {
+            Some(LatexNode {
+                index: i,
+                outputs,
+                ..LatexNode::default()
+            })
+        })
+        .collect();
+    let reach = Reach::new(&symbol_map);
+    assert!(reach.contains(0, 4) && reach.contains(2, 2) && !reach.contains(5, 4));
+    assert_eq!(
+        LatexEngine::downstream(&symbol_map, &reach, 0, 4),
+        vec![1, 2]
+    );
+    assert_eq!(LatexEngine::downstream(&symbol_map, &reach, 1, 4), vec![3]);
+    assert_eq!(LatexEngine::merge_point(&reach, &[1, 2]), Some(3));
+    assert_eq!(LatexEngine::merge_point(&reach, &[1, 5]), None);
+}
+
+#[test]
+fn residual_backward_test() {
+    let lengths: Vec<usize> = [2, 4]
+        .iter()
+        .map(|blocks| {
+            let bytes = crate::test_model::residual(*blocks);
+            let mut engine = LatexEngine::new();
+            let model = engine
+                .model_from_file(&mut std::io::Cursor::new(bytes.clone()))
+                .unwrap();
+            let result = engine
+                .parse_from_file(&mut std::io::Cursor::new(bytes), Some(4))
+                .unwrap();
+            let math_ops = LatexEngine::math_op_vecs(&model);
+            let (first, last) = (result.senario[0], *result.senario.last().unwrap());
+            let (_, value) = engine
+                .gen_each_back(
+                    &math_ops,
+                    &model,
+                    &result,
+                    (first, last),
+                    &crate::Indexes::new(vec![0, 0], vec![0, 0]),
+                    None,
+                )
+                .unwrap();
+            // gradient of each join node is defined once
+            assert_eq!(value.matches(r#"\quad"#).count(), *blocks, "{}", value);
+            assert!(!value.contains("Undefined"), "{}", value);
+            // definition is indexed like its references in sum of paths
+            assert!(
+                value.contains(r#"{\delta^{(Add_{7})}}_{(cn_{0},)}="#),
+                "{}",
+                value
+            );
+            value.len()
+        })
+        .collect();
+    // linear in blocks, not doubled by each block
+    assert!(lengths[1] < 3 * lengths[0], "{:?}", lengths);
+}
@@ -0,0 +1,115 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::{LatexEngine, LatexNode};
+
+// called with each finished node and (done nodes, all nodes), false stops work
+pub type NodeListener = Arc<dyn Fn(&LatexNode, (usize, usize)) -> bool + Send + Sync>;
+
+// shared with caller thread to read progress and stop parse or backward
+#[derive(Clone, Default)]
+pub struct ParseControl {
+    cancelled: Arc<AtomicBool>,
+    done: Arc<AtomicUsize>,
+    total: Arc<AtomicUsize>,
+    listener: Option<NodeListener>,
+}
+
+impl ParseControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_listener<F>(listener: F) -> Self
+    where
+        F: Fn(&LatexNode, (usize, usize)) -> bool + Send + Sync + 'static,
+    {
+        ParseControl {
+            listener: Some(Arc::new(listener)),
+            ..Self::default()
+        }
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+    // (parsed nodes, all nodes)
+    pub fn progress(&self) -> (usize, usize) {
+        (
+            self.done.load(Ordering::SeqCst),
+            self.total.load(Ordering::SeqCst),
+        )
+    }
+    pub(crate) fn start(&self, total: usize) {
+        self.done.store(0, Ordering::SeqCst);
+        self.total.store(total, Ordering::SeqCst);
+    }
+    pub(crate) fn step(&self, node: Option<&LatexNode>) {
+        let done = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        if let (Some(listener), Some(node)) = (self.listener.as_ref(), node) {
+            if !listener(node, (done, self.total.load(Ordering::SeqCst))) {
+                self.cancel();
+            }
+        }
+    }
+}
+
+impl LatexEngine {
+    pub(crate) fn cancelled(&self) -> bool {
+        self.control
+            .as_ref()
+            .map(|c| c.is_cancelled())
+            .unwrap_or(false)
+    }
+}
+
+#[test]
+fn control_test() {
+    let control = ParseControl::new();
+    let shared = control.clone();
+    control.start(3);
+    shared.step(None);
+    assert_eq!(control.progress(), (1, 3));
+    shared.cancel();
+    assert!(control.is_cancelled());
+
+    // listener stops parse at second node
+    let control = ParseControl::with_listener(|_, (done, _)| done < 2);
+    control.start(3);
+    let node = LatexNode::default();
+    control.step(Some(&node));
+    assert!(!control.is_cancelled());
+    control.step(Some(&node));
+    assert!(control.is_cancelled());
+}
+
+#[test]
+fn backward_cancel_test() {
+    let bytes = crate::test_model::two_layer();
+    let mut engine = LatexEngine::new();
+    let model = engine
+        .model_from_file(&mut std::io::Cursor::new(bytes.clone()))
+        .unwrap();
+    let result = engine
+        .parse_from_file(&mut std::io::Cursor::new(bytes), Some(4))
+        .unwrap();
+    let math_ops = LatexEngine::math_op_vecs(&model);
+    let control = ParseControl::new();
+    control.cancel();
+    engine.control = Some(control);
+    let (first, last) = (result.senario[0], *result.senario.last().unwrap());
+    let err = engine
+        .gen_each_back(
+            &math_ops,
+            &model,
+            &result,
+            (first, last),
+            &crate::Indexes::new(vec![0, 0], vec![0, 0]),
+            None,
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+}
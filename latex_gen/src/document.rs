@@ -0,0 +1,187 @@
+use std::{
+    fmt::Display,
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
+
+use crate::{GraphOption, LatexNode, LatexResult};
+
+// output format of parsing result
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    // json without pretty printing
+    CompactJson,
+    Tex,
+    Markdown,
+    // html page rendered by katex
+    Html,
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "compact" | "compact_json" => Ok(OutputFormat::CompactJson),
+            "tex" | "latex" => Ok(OutputFormat::Tex),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            "dot" => Ok(OutputFormat::Dot),
+            "mermaid" => Ok(OutputFormat::Mermaid),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown output format {}", s),
+            )),
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            OutputFormat::Json => "json",
+            OutputFormat::CompactJson => "compact",
+            OutputFormat::Tex => "tex",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Html => "html",
+            OutputFormat::Dot => "dot",
+            OutputFormat::Mermaid => "mermaid",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+fn tex_escape(s: &str) -> String {
+    s.replace('_', r#"\_"#)
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+const KATEX: &str = "https://cdn.jsdelivr.net/npm/katex@0.13.11/dist";
+
+impl LatexResult {
+    // nodes in senario order
+    fn senario_nodes(&self) -> impl Iterator<Item = &LatexNode> {
+        self.senario
+            .iter()
+            .filter_map(move |i| self.symbol_map.get(*i).and_then(|s| s.as_ref()))
+    }
+    // standalone latex document
+    pub fn gen_tex(&self) -> String {
+        let mut result = String::new();
+        result += "\\documentclass{article}\n";
+        result += "\\usepackage{amsmath}\n";
+        result += "\\usepackage{amssymb}\n";
+        result += "\\begin{document}\n";
+        for node in self.senario_nodes() {
+            result += &format!(
+                "\\subsection*{{{} ({})}}\n",
+                tex_escape(&node.name),
+                tex_escape(&node.op_name)
+            );
+            result += &format!("\\[\n{}={}\n\\]\n", node.symbol, node.forward_value);
+            if !node.backward_value.is_empty() {
+                result += &format!(
+                    "\\[\n{}={}\n\\]\n",
+                    node.backward_symbol, node.backward_value
+                );
+            }
+            for u in node.update.iter() {
+                result += &format!("\\[\n{}\n\\]\n", u);
+            }
+        }
+        result += "\\end{document}\n";
+        result
+    }
+    // markdown with math block
+    pub fn gen_markdown(&self) -> String {
+        let mut result = String::new();
+        for node in self.senario_nodes() {
+            result += &format!("### {} ({})\n\n", node.name, node.op_name);
+            result += &format!("output shape: `{:?}`\n\n", node.output_shape);
+            result += &format!("$$\n{}={}\n$$\n\n", node.symbol, node.forward_value);
+            if !node.backward_value.is_empty() {
+                result += &format!(
+                    "$$\n{}={}\n$$\n\n",
+                    node.backward_symbol, node.backward_value
+                );
+            }
+            for u in node.update.iter() {
+                result += &format!("$$\n{}\n$$\n\n", u);
+            }
+        }
+        result
+    }
+    // html page, formulas are rendered by katex auto render
+    pub fn gen_html(&self) -> String {
+        let mut result = String::new();
+        result += "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n";
+        result += &format!(
+            "<link rel=\"stylesheet\" href=\"{}/katex.min.css\">\n",
+            KATEX
+        );
+        result += &format!("<script defer src=\"{}/katex.min.js\"></script>\n", KATEX);
+        result += &format!(
+            "<script defer src=\"{}/contrib/auto-render.min.js\" \
+             onload=\"renderMathInElement(document.body)\"></script>\n",
+            KATEX
+        );
+        result += "</head>\n<body>\n";
+        let math = |s: String| format!("<p>$${}$$</p>\n", html_escape(&s));
+        for node in self.senario_nodes() {
+            result += &format!(
+                "<h3>{} ({})</h3>\n",
+                html_escape(&node.name),
+                html_escape(&node.op_name)
+            );
+            result += &format!(
+                "<p>output shape: <code>{:?}</code></p>\n",
+                node.output_shape
+            );
+            result += &math(format!("{}={}", node.symbol, node.forward_value));
+            if !node.backward_value.is_empty() {
+                result += &math(format!("{}={}", node.backward_symbol, node.backward_value));
+            }
+            for u in node.update.iter() {
+                result += &math(u.clone());
+            }
+        }
+        result += "</body>\n</html>\n";
+        result
+    }
+    // render with format
+    pub fn render(&self, format: &OutputFormat, graph_option: &GraphOption) -> String {
+        match format {
+            OutputFormat::Json => self.gen_json(),
+            OutputFormat::CompactJson => serde_json::to_string(self).unwrap(),
+            OutputFormat::Tex => self.gen_tex(),
+            OutputFormat::Markdown => self.gen_markdown(),
+            OutputFormat::Html => self.gen_html(),
+            OutputFormat::Dot => self.gen_dot(graph_option),
+            OutputFormat::Mermaid => self.gen_mermaid(graph_option),
+        }
+    }
+}
+
+#[test]
+fn format_test() {
+    assert_eq!(
+        "md".parse::<OutputFormat>().unwrap(),
+        OutputFormat::Markdown
+    );
+    assert_eq!("TEX".parse::<OutputFormat>().unwrap(), OutputFormat::Tex);
+    assert!("pdf".parse::<OutputFormat>().is_err());
+    assert_eq!(
+        "compact".parse::<OutputFormat>().unwrap(),
+        OutputFormat::CompactJson
+    );
+    assert_eq!(html_escape("a<b&c"), "a&lt;b&amp;c");
+}
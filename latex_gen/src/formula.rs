@@ -0,0 +1,889 @@
+// numeric evaluation of generated latex formulas, ex) backward formulas in gradient check
+use std::io::{Error, ErrorKind};
+
+use tract_onnx::tract_hir::utils::element_symbol;
+
+#[derive(Debug, Clone, Copy)]
+enum Func {
+    Exp,
+    Sqrt,
+    Log,
+    Sign,
+    Abs,
+    Floor,
+    Ceil,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Rel {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Num(f64),
+    Var(String),
+    // {name}_{(index)}
+    Element(String, Vec<Expr>),
+    Neg(Box<Expr>),
+    Add(Vec<Expr>),
+    Mul(Vec<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+    // iverson bracket, 1 when every relation holds ex) [0\le h-m<4]
+    Cond(Vec<Expr>, Vec<Rel>),
+    // [a \mid b]
+    Divides(Box<Expr>, Box<Expr>),
+    // \sum_{var=from}^{to} of rest of term, to is N-1 when not given
+    Sum(String, Box<Expr>, Option<Box<Expr>>, Box<Expr>),
+    // (a,b,) in index of weight element
+    Tuple(Vec<Expr>),
+    // E_{(total,element)}, numerator of loss gradient
+    Error(Option<Box<Expr>>),
+    // \frac{\partial a}{\partial b}
+    Partial(Box<Expr>, Box<Expr>),
+    // \left(\frac{\partial a}{\partial b}\right)^{\top}\delta^{(a)}, names of a, b and delta
+    Vjp(String, String, String),
+}
+
+impl Expr {
+    fn is_indicator(&self) -> bool {
+        matches!(self, Expr::Cond(..) | Expr::Divides(..))
+    }
+}
+
+// tensors and variables bound for evaluation
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Scope {
+    tensors: Vec<(String, Vec<usize>, Vec<f64>)>,
+    vars: Vec<(String, f64)>,
+    // flat position of vector formula, symbols without index are elements at it
+    pub at: Option<usize>,
+}
+
+impl Scope {
+    pub fn tensor(&mut self, name: &str, shape: &[usize], values: Vec<f64>) {
+        self.tensors.retain(|(n, _, _)| n != name);
+        self.tensors
+            .push((name.to_string(), shape.to_vec(), values));
+    }
+    pub fn set(&mut self, name: &str, value: f64) {
+        match self.vars.iter_mut().rev().find(|(n, _)| n == name) {
+            Some(v) => v.1 = value,
+            None => self.vars.push((name.to_string(), value)),
+        }
+    }
+    fn var(&self, name: &str) -> Option<f64> {
+        self.vars
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| *v)
+    }
+    pub fn has_tensor(&self, name: &str) -> bool {
+        self.tensors.iter().any(|(n, _, _)| n == name)
+    }
+    pub fn values(&self, name: &str) -> Result<&[f64], Error> {
+        self.tensors
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, _, v)| v.as_slice())
+            .ok_or_else(|| invalid(format!("unbound tensor {}", name)))
+    }
+    fn element(&self, name: &str, index: &[f64]) -> Result<f64, Error> {
+        let (_, shape, values) = self
+            .tensors
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .ok_or_else(|| invalid(format!("unbound tensor {}", name)))?;
+        let index = pad_index(shape, index)
+            .ok_or_else(|| invalid(format!("{} has rank {}", name, shape.len())))?;
+        let mut offset = 0;
+        for (i, s) in index.iter().zip(shape.iter()) {
+            match as_index(*i) {
+                Some(i) if i < *s => offset = offset * s + i,
+                _ => {
+                    return Err(invalid(format!(
+                        "index {:?} is out of shape {:?} of {}",
+                        index, shape, name
+                    )))
+                }
+            }
+        }
+        Ok(values[offset])
+    }
+    // output and target of loss as {y}_{(i)}, {t}_{(i)} and N
+    pub fn loss(output: &[f64], target: &[f64]) -> Self {
+        let mut scope = Scope::default();
+        scope.tensor("y", &[output.len()], output.to_vec());
+        scope.tensor("t", &[target.len()], target.to_vec());
+        scope.set("N", output.len() as f64);
+        scope
+    }
+}
+
+// element symbols of loss scope
+pub(crate) fn loss_symbols() -> (String, String) {
+    let i = ["i".to_string()];
+    (element_symbol("y", &i), element_symbol("t", &i))
+}
+
+fn invalid(m: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, m)
+}
+
+// index with leading batch dims of size 1 left out is padded with 0
+pub(crate) fn pad_index(shape: &[usize], index: &[f64]) -> Option<Vec<f64>> {
+    if index.len() > shape.len() {
+        return None;
+    }
+    let missing = shape.len() - index.len();
+    if shape[..missing].iter().any(|s| *s != 1) {
+        return None;
+    }
+    let mut result = vec![0.0; missing];
+    result.extend_from_slice(index);
+    Some(result)
+}
+
+// side of partial derivative with evaluated index
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Leaf {
+    // loss, with output element if given
+    Error(Option<(String, Vec<f64>)>),
+    Element(String, Vec<f64>),
+    // weight element of layer output element ex) {w}_{({f_{1}}_{(0,1,)},(2,1,))}
+    Weight(String, Vec<f64>, Vec<f64>),
+    // whole tensor ex) f_{2}
+    Vector(String),
+}
+
+// values of partial derivatives in formula
+pub(crate) trait Partials {
+    // derivative of of by by, at is position of vector formula
+    fn partial(&self, of: &Leaf, by: &Leaf, at: Option<usize>) -> Result<f64, Error>;
+    // element at of transposed jacobian of of by by times delta
+    fn vjp(&self, of: &str, by: &str, delta: &[f64], at: usize) -> Result<f64, Error>;
+}
+
+// formulas without partial derivatives
+struct NoPartials;
+
+impl Partials for NoPartials {
+    fn partial(&self, _of: &Leaf, _by: &Leaf, _at: Option<usize>) -> Result<f64, Error> {
+        Err(invalid("partial derivative is not bound".to_string()))
+    }
+    fn vjp(&self, _of: &str, _by: &str, _delta: &[f64], _at: usize) -> Result<f64, Error> {
+        Err(invalid("partial derivative is not bound".to_string()))
+    }
+}
+
+fn as_index(v: f64) -> Option<usize> {
+    if v >= 0.0 && (v - v.round()).abs() < 1e-9 {
+        Some(v.round() as usize)
+    } else {
+        None
+    }
+}
+
+fn as_integer(v: f64) -> Option<i64> {
+    if (v - v.round()).abs() < 1e-9 {
+        Some(v.round() as i64)
+    } else {
+        None
+    }
+}
+
+// parsed formula, evaluated many times with different bindings
+#[derive(Debug, Clone)]
+pub(crate) struct Formula(Expr);
+
+impl Formula {
+    pub fn parse(src: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            src,
+            pos: 0,
+            abs: 0,
+        };
+        let expr = parser.expr()?;
+        parser.skip_space();
+        if parser.pos != src.len() {
+            return Err(parser.error("unexpected token"));
+        }
+        Ok(Formula(expr))
+    }
+    pub fn eval(&self, scope: &mut Scope) -> Result<f64, Error> {
+        eval(&self.0, scope, &NoPartials)
+    }
+    pub fn eval_with(&self, scope: &mut Scope, partials: &dyn Partials) -> Result<f64, Error> {
+        eval(&self.0, scope, partials)
+    }
+}
+
+fn leaf(expr: &Expr, scope: &mut Scope, partials: &dyn Partials) -> Result<Leaf, Error> {
+    let element = |name: &str, index: &[Expr], scope: &mut Scope| {
+        index
+            .iter()
+            .map(|e| eval(e, scope, partials))
+            .collect::<Result<Vec<f64>, Error>>()
+            .map(|i| (name.to_string(), i))
+    };
+    Ok(match expr {
+        Expr::Error(None) => Leaf::Error(None),
+        Expr::Error(Some(e)) => match e.as_ref() {
+            Expr::Element(name, index) => Leaf::Error(Some(element(name, index, scope)?)),
+            _ => return Err(invalid(format!("{:?} is not element", e))),
+        },
+        Expr::Element(name, index) => match index.as_slice() {
+            [Expr::Element(layer, at), Expr::Tuple(w)] => {
+                let (layer, at) = element(layer, at, scope)?;
+                Leaf::Weight(layer, at, element(name, w, scope)?.1)
+            }
+            _ => {
+                let (name, index) = element(name, index, scope)?;
+                Leaf::Element(name, index)
+            }
+        },
+        Expr::Var(name) => Leaf::Vector(name.clone()),
+        _ => return Err(invalid(format!("{:?} is not derivable", expr))),
+    })
+}
+
+fn eval(expr: &Expr, scope: &mut Scope, partials: &dyn Partials) -> Result<f64, Error> {
+    Ok(match expr {
+        Expr::Num(v) => *v,
+        Expr::Var(name) => match (scope.var(name), scope.at) {
+            (Some(v), _) => v,
+            (None, Some(at)) if scope.has_tensor(name) => scope
+                .values(name)?
+                .get(at)
+                .cloned()
+                .ok_or_else(|| invalid(format!("{} has no element {}", name, at)))?,
+            _ => return Err(invalid(format!("unbound variable {}", name))),
+        },
+        Expr::Element(name, index) => {
+            let index = index
+                .iter()
+                .map(|e| eval(e, scope, partials))
+                .collect::<Result<Vec<f64>, Error>>()?;
+            scope.element(name, &index)?
+        }
+        Expr::Neg(e) => -eval(e, scope, partials)?,
+        Expr::Add(terms) => {
+            let mut sum = 0.0;
+            for t in terms.iter() {
+                sum += eval(t, scope, partials)?;
+            }
+            sum
+        }
+        Expr::Mul(factors) => {
+            // conditions first so that elements outside of them are not read
+            for f in factors.iter().filter(|f| f.is_indicator()) {
+                if eval(f, scope, partials)? == 0.0 {
+                    return Ok(0.0);
+                }
+            }
+            let mut product = 1.0;
+            for f in factors.iter().filter(|f| !f.is_indicator()) {
+                product *= eval(f, scope, partials)?;
+            }
+            product
+        }
+        Expr::Div(a, b) => eval(a, scope, partials)? / eval(b, scope, partials)?,
+        Expr::Pow(a, b) => eval(a, scope, partials)?.powf(eval(b, scope, partials)?),
+        Expr::Call(func, args) => {
+            let args = args
+                .iter()
+                .map(|e| eval(e, scope, partials))
+                .collect::<Result<Vec<f64>, Error>>()?;
+            match func {
+                Func::Exp => args[0].exp(),
+                Func::Sqrt => args[0].sqrt(),
+                Func::Log => args[0].ln(),
+                Func::Sign if args[0] == 0.0 => 0.0,
+                Func::Sign => args[0].signum(),
+                Func::Abs => args[0].abs(),
+                Func::Floor => args[0].floor(),
+                Func::Ceil => args[0].ceil(),
+                Func::Min => args.iter().cloned().fold(f64::INFINITY, f64::min),
+                Func::Max => args.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            }
+        }
+        Expr::Cond(exprs, rels) => {
+            let values = exprs
+                .iter()
+                .map(|e| eval(e, scope, partials))
+                .collect::<Result<Vec<f64>, Error>>()?;
+            let holds = rels.iter().enumerate().all(|(i, r)| {
+                let (a, b) = (values[i], values[i + 1]);
+                match r {
+                    Rel::Lt => a < b,
+                    Rel::Le => a <= b,
+                    Rel::Gt => a > b,
+                    Rel::Ge => a >= b,
+                    Rel::Eq => (a - b).abs() < 1e-9,
+                }
+            });
+            if holds {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        Expr::Divides(a, b) => match (
+            as_integer(eval(a, scope, partials)?),
+            as_integer(eval(b, scope, partials)?),
+        ) {
+            (Some(a), Some(b)) if a != 0 && b.rem_euclid(a) == 0 => 1.0,
+            _ => 0.0,
+        },
+        Expr::Sum(var, from, to, body) => {
+            let bound = |v: f64| as_integer(v).ok_or_else(|| invalid(format!("sum bound {}", v)));
+            let from = bound(eval(from, scope, partials)?)?;
+            let to = match to {
+                Some(to) => bound(eval(to, scope, partials)?)?,
+                None => {
+                    let n = scope
+                        .var("N")
+                        .ok_or_else(|| invalid(format!("sum over {} without N", var)))?;
+                    bound(n)? - 1
+                }
+            };
+            scope.vars.push((var.clone(), 0.0));
+            let mut sum = 0.0;
+            let mut result = Ok(());
+            for k in from..=to {
+                if let Some(v) = scope.vars.last_mut() {
+                    v.1 = k as f64;
+                }
+                match eval(body, scope, partials) {
+                    Ok(v) => sum += v,
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            }
+            scope.vars.pop();
+            result?;
+            sum
+        }
+        Expr::Tuple(_) | Expr::Error(_) => {
+            return Err(invalid(format!("{:?} is not a number", expr)))
+        }
+        Expr::Partial(of, by) => {
+            let (of, by) = (leaf(of, scope, partials)?, leaf(by, scope, partials)?);
+            partials.partial(&of, &by, scope.at)?
+        }
+        Expr::Vjp(of, by, delta) => {
+            let at = scope
+                .at
+                .ok_or_else(|| invalid(format!("{} is vector", delta)))?;
+            partials.vjp(of, by, scope.values(delta)?, at)?
+        }
+    })
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    // depth of |...|, closing bar ends term
+    abs: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+    fn error(&self, m: &str) -> Error {
+        invalid(format!("{} at {} of {}", m, self.pos, self.src))
+    }
+    // name of command at position ex) frac of \frac
+    fn command(&self) -> Option<&'a str> {
+        let rest = self.rest().strip_prefix('\\')?;
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        Some(&rest[..end])
+    }
+    fn skip_space(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            match self.command() {
+                Some(c) if c == "left" || c == "right" => self.pos += c.len() + 1,
+                Some("") if self.rest()[1..].starts_with([',', ';', '!']) => self.pos += 2,
+                _ => break,
+            }
+        }
+    }
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.rest().chars().next()
+    }
+    fn eat(&mut self, s: &str) -> bool {
+        self.skip_space();
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, s: &str) -> Result<(), Error> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", s)))
+        }
+    }
+    fn eat_command(&mut self, name: &str) -> bool {
+        self.skip_space();
+        if self.command() == Some(name) {
+            self.pos += name.len() + 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn expr(&mut self) -> Result<Expr, Error> {
+        let mut terms = Vec::new();
+        let mut neg = self.eat("-");
+        if !neg {
+            self.eat("+");
+        }
+        loop {
+            let term = self.product()?;
+            terms.push(if neg { Expr::Neg(Box::new(term)) } else { term });
+            if self.eat("+") {
+                neg = false;
+            } else if self.eat("-") {
+                neg = true;
+            } else {
+                break;
+            }
+        }
+        Ok(if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            Expr::Add(terms)
+        })
+    }
+    fn product(&mut self) -> Result<Expr, Error> {
+        let mut factors = Vec::new();
+        loop {
+            match self.peek() {
+                None => break,
+                Some(c) if ")}],+-<>=".contains(c) => break,
+                Some('|') if self.abs > 0 => break,
+                Some('\\') => match self.command().unwrap_or_default() {
+                    "cdot" | "times" => {
+                        self.pos += self.command().unwrap_or_default().len() + 1;
+                        continue;
+                    }
+                    "le" | "leq" | "ge" | "geq" | "mid" | "rfloor" | "rceil" => break,
+                    "sum" => {
+                        factors.push(self.sum()?);
+                        break;
+                    }
+                    _ => factors.push(self.power()?),
+                },
+                Some(_) => factors.push(self.power()?),
+            }
+        }
+        match factors.len() {
+            0 => Err(self.error("expected term")),
+            1 => Ok(factors.pop().unwrap()),
+            _ => Ok(Expr::Mul(factors)),
+        }
+    }
+    fn sum(&mut self) -> Result<Expr, Error> {
+        self.eat_command("sum");
+        self.expect("_")?;
+        self.expect("{")?;
+        let var = self.name()?;
+        let (from, to) = if self.eat("=") {
+            let from = self.expr()?;
+            self.expect("}")?;
+            self.expect("^")?;
+            (from, Some(Box::new(self.group()?)))
+        } else {
+            self.expect("}")?;
+            (Expr::Num(0.0), None)
+        };
+        let body = self.product()?;
+        Ok(Expr::Sum(var, Box::new(from), to, Box::new(body)))
+    }
+    // single letter, or letters with subscript ex) cn_{0}
+    fn name(&mut self) -> Result<String, Error> {
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() => {
+                let rest = self.rest();
+                let letters = rest
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(rest.len());
+                if rest[letters..].starts_with("_{") {
+                    let start = self.pos;
+                    self.pos += letters + 1;
+                    let close = self
+                        .closing_brace()
+                        .ok_or_else(|| self.error("unclosed brace"))?;
+                    self.pos = close + 1;
+                    return Ok(self.src[start..self.pos].to_string());
+                }
+                self.pos += 1;
+                Ok(c.to_string())
+            }
+            _ => Err(self.error("expected variable")),
+        }
+    }
+    // expressions until closing parenthesis, trailing comma is allowed
+    fn list(&mut self, first: Option<Expr>) -> Result<Vec<Expr>, Error> {
+        let mut items: Vec<Expr> = first.into_iter().collect();
+        if items.is_empty() && self.peek() != Some(')') {
+            items.push(self.expr()?);
+        }
+        while self.eat(",") {
+            if self.peek() == Some(')') {
+                break;
+            }
+            items.push(self.expr()?);
+        }
+        self.expect(")")?;
+        Ok(items)
+    }
+    // side of \frac{\partial a}{\partial b}, whole brace group
+    fn partial_leaf(&mut self) -> Result<Expr, Error> {
+        self.expect("{")?;
+        if !self.eat_command("partial") {
+            return Err(self.error("expected \\partial"));
+        }
+        self.skip_space();
+        let mut depth = 1;
+        let close = self
+            .rest()
+            .char_indices()
+            .find(|(_, c)| {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|(i, _)| self.pos + i)
+            .ok_or_else(|| self.error("unclosed brace"))?;
+        let raw = self.src[self.pos..close].trim_end();
+        let sub = |src: &str| -> Result<Expr, Error> {
+            let mut parser = Parser {
+                src,
+                pos: 0,
+                abs: 0,
+            };
+            let e = parser.expr()?;
+            parser.skip_space();
+            if parser.pos != src.len() {
+                return Err(parser.error("unexpected token"));
+            }
+            Ok(e)
+        };
+        let leaf = if let Some(inner) = raw.strip_prefix("E_{(total,") {
+            let inner = inner
+                .strip_suffix(")}")
+                .ok_or_else(|| self.error("unclosed error symbol"))?;
+            if inner.is_empty() {
+                Expr::Error(None)
+            } else {
+                Expr::Error(Some(Box::new(sub(inner)?)))
+            }
+        } else if raw.starts_with('{') {
+            sub(raw)?
+        } else {
+            Expr::Var(raw.to_string())
+        };
+        self.pos = close + 1;
+        Ok(leaf)
+    }
+    // {expr} or single character
+    fn group(&mut self) -> Result<Expr, Error> {
+        if self.eat("{") {
+            let e = self.expr()?;
+            self.expect("}")?;
+            Ok(e)
+        } else {
+            match self.peek() {
+                Some(c) if c.is_ascii_digit() => {
+                    self.pos += 1;
+                    Ok(Expr::Num(c.to_digit(10).unwrap_or_default() as f64))
+                }
+                _ => Ok(Expr::Var(self.name()?)),
+            }
+        }
+    }
+    fn power(&mut self) -> Result<Expr, Error> {
+        let base = self.primary()?;
+        if self.eat("^") {
+            Ok(Expr::Pow(Box::new(base), Box::new(self.group()?)))
+        } else {
+            Ok(base)
+        }
+    }
+    // end of brace group which starts at position
+    fn closing_brace(&self) -> Option<usize> {
+        let mut depth = 0;
+        for (i, c) in self.rest().char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(self.pos + i);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let c = self.peek().ok_or_else(|| self.error("unexpected end"))?;
+        match c {
+            '0'..='9' | '.' => {
+                let rest = self.rest();
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(rest.len());
+                let v = rest[..end]
+                    .parse::<f64>()
+                    .map_err(|_| self.error("invalid number"))?;
+                self.pos += end;
+                Ok(Expr::Num(v))
+            }
+            '(' => {
+                self.pos += 1;
+                let e = self.expr()?;
+                if self.peek() == Some(',') {
+                    return Ok(Expr::Tuple(self.list(Some(e))?));
+                }
+                self.expect(")")?;
+                if self.eat("^{\\top}") {
+                    return self.vjp(e);
+                }
+                Ok(e)
+            }
+            '{' => {
+                let close = self
+                    .closing_brace()
+                    .ok_or_else(|| self.error("unclosed brace"))?;
+                if self.src[close + 1..].starts_with("_{(") {
+                    let name = self.src[self.pos + 1..close].to_string();
+                    self.pos = close + 4;
+                    let index = self.list(None)?;
+                    self.expect("}")?;
+                    Ok(Expr::Element(name, index))
+                } else {
+                    self.group()
+                }
+            }
+            '[' => {
+                self.pos += 1;
+                let e = self.condition()?;
+                self.expect("]")?;
+                Ok(e)
+            }
+            '|' => {
+                self.pos += 1;
+                self.abs += 1;
+                let e = self.expr();
+                self.abs -= 1;
+                let e = e?;
+                self.expect("|")?;
+                Ok(Expr::Call(Func::Abs, vec![e]))
+            }
+            'e' if self.rest()[1..].trim_start().starts_with('^') => {
+                self.pos += 1;
+                self.expect("^")?;
+                Ok(Expr::Call(Func::Exp, vec![self.group()?]))
+            }
+            c if c.is_ascii_alphabetic() => Ok(Expr::Var(self.name()?)),
+            '\\' => self.command_expr(),
+            _ => Err(self.error("unexpected token")),
+        }
+    }
+    // transposed jacobian times delta of numerator, after ^{\top}
+    fn vjp(&mut self, jacobian: Expr) -> Result<Expr, Error> {
+        let (of, by) = match jacobian {
+            Expr::Partial(of, by) => match (*of, *by) {
+                (Expr::Var(of), Expr::Var(by)) => (of, by),
+                _ => return Err(self.error("transposed jacobian of elements")),
+            },
+            _ => return Err(self.error("transpose of non jacobian")),
+        };
+        self.skip_space();
+        let start = self.pos;
+        if !self.eat_command("delta") {
+            return Err(self.error("expected \\delta"));
+        }
+        self.expect("^")?;
+        let close = self
+            .closing_brace()
+            .ok_or_else(|| self.error("unclosed brace"))?;
+        self.pos = close + 1;
+        Ok(Expr::Vjp(of, by, self.src[start..self.pos].to_string()))
+    }
+    fn command_expr(&mut self) -> Result<Expr, Error> {
+        let name = self.command().unwrap_or_default();
+        self.pos += name.len() + 1;
+        let call = |f: Func, e: Expr| Ok(Expr::Call(f, vec![e]));
+        match name {
+            "frac" if self.rest().trim_start().starts_with("{\\partial") => {
+                let of = self.partial_leaf()?;
+                let by = self.partial_leaf()?;
+                Ok(Expr::Partial(Box::new(of), Box::new(by)))
+            }
+            // label under brace is not evaluated
+            "underbrace" => {
+                let e = self.group()?;
+                if self.eat("_") {
+                    let close = self
+                        .closing_brace()
+                        .ok_or_else(|| self.error("unclosed brace"))?;
+                    self.pos = close + 1;
+                }
+                Ok(e)
+            }
+            "frac" => {
+                let a = self.group()?;
+                let b = self.group()?;
+                Ok(Expr::Div(Box::new(a), Box::new(b)))
+            }
+            "sqrt" => call(Func::Sqrt, self.group()?),
+            "log" | "ln" => call(Func::Log, self.power()?),
+            "exp" => call(Func::Exp, self.power()?),
+            "mathrm" => {
+                self.expect("{")?;
+                let end = self
+                    .rest()
+                    .find('}')
+                    .ok_or_else(|| self.error("unclosed brace"))?;
+                let func = self.rest()[..end].to_string();
+                self.pos += end + 1;
+                match func.as_str() {
+                    "sign" => call(Func::Sign, self.power()?),
+                    _ => Err(self.error(&format!("{} is not supported", func))),
+                }
+            }
+            "min" | "max" => {
+                self.expect("(")?;
+                let mut args = vec![self.expr()?];
+                while self.eat(",") {
+                    args.push(self.expr()?);
+                }
+                self.expect(")")?;
+                let func = if name == "min" { Func::Min } else { Func::Max };
+                Ok(Expr::Call(func, args))
+            }
+            "lfloor" | "lceil" => {
+                let e = self.expr()?;
+                if name == "lfloor" {
+                    if !self.eat_command("rfloor") {
+                        return Err(self.error("expected \\rfloor"));
+                    }
+                    call(Func::Floor, e)
+                } else {
+                    if !self.eat_command("rceil") {
+                        return Err(self.error("expected \\rceil"));
+                    }
+                    call(Func::Ceil, e)
+                }
+            }
+            _ => Err(self.error(&format!("\\{} is not supported", name))),
+        }
+    }
+    fn relation(&mut self) -> Option<Rel> {
+        if self.eat_command("leq") || self.eat_command("le") || self.eat("<=") {
+            Some(Rel::Le)
+        } else if self.eat_command("geq") || self.eat_command("ge") || self.eat(">=") {
+            Some(Rel::Ge)
+        } else if self.eat("<") {
+            Some(Rel::Lt)
+        } else if self.eat(">") {
+            Some(Rel::Gt)
+        } else if self.eat("=") {
+            Some(Rel::Eq)
+        } else {
+            None
+        }
+    }
+    fn condition(&mut self) -> Result<Expr, Error> {
+        let first = self.expr()?;
+        if self.eat_command("mid") {
+            let b = self.expr()?;
+            return Ok(Expr::Divides(Box::new(first), Box::new(b)));
+        }
+        let mut exprs = vec![first];
+        let mut rels = Vec::new();
+        while let Some(r) = self.relation() {
+            rels.push(r);
+            exprs.push(self.expr()?);
+        }
+        if rels.is_empty() {
+            return Err(self.error("expected relation"));
+        }
+        Ok(Expr::Cond(exprs, rels))
+    }
+}
+
+#[test]
+fn formula_test() {
+    let eval_with =
+        |src: &str, scope: &mut Scope| Formula::parse(src).unwrap().eval(scope).unwrap();
+    let mut scope = Scope::default();
+    scope.tensor("W", &[2, 3], vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    scope.tensor(r#"\delta"#, &[1, 3], vec![1.0, 0.5, -1.0]);
+    scope.set("i", 0.0);
+    scope.set("k", 1.0);
+    // gemm input gradient
+    assert_eq!(
+        eval_with(
+            r#"\sum_{j=0}^{2}{\delta}_{(i,j)}\cdot {W}_{(k,j)}"#,
+            &mut scope
+        ),
+        4.0 + 2.5 - 6.0
+    );
+    assert_eq!(
+        eval_with(r#"2\frac{1}{4}+3^{2}-\left(1\right)"#, &mut scope),
+        8.5
+    );
+    assert_eq!(eval_with(r#"\min(2,k+3)-\max(0,k-4)+1"#, &mut scope), 3.0);
+    assert_eq!(
+        eval_with(
+            r#"\lfloor\frac{7}{2}\rfloor\cdot [2 \mid 4][0\le k<2]"#,
+            &mut scope
+        ),
+        3.0
+    );
+    // condition is checked before out of shape element
+    assert_eq!(eval_with(r#"{W}_{(k+5,0)}[0\le k+5<2]"#, &mut scope), 0.0);
+    assert!((eval_with(r#"\frac{1}{1+e^{-0}}"#, &mut scope) - 0.5).abs() < 1e-12);
+
+    let mut loss = Scope::loss(&[1.0, 3.0], &[0.0, 1.0]);
+    assert_eq!(
+        eval_with(r#"\frac{1}{N}\sum_{i}({y}_{(i)}-{t}_{(i)})^{2}"#, &mut loss),
+        2.5
+    );
+    assert_eq!(
+        eval_with(r#"\frac{1}{N}\sum_{i}|{y}_{(i)}-{t}_{(i)}|"#, &mut loss),
+        1.5
+    );
+
+    assert!(Formula::parse(r#"[(h,w)=\mathrm{argmax}_{(m,n)}{x}_{(m,n)}]"#).is_err());
+}
@@ -0,0 +1,170 @@
+use std::io::{Error, ErrorKind};
+
+use serde::{Deserialize, Serialize};
+use tract_onnx::prelude::*;
+
+use crate::{
+    trace::{eval_changed, eval_values},
+    value::{render_matrix, tensor_values},
+    verify::target_values,
+    InferencePlan, LatexEngine, LatexResult, Loss, ModelError, TraceInput, ValueStats,
+};
+
+// option for gradient of constants
+#[derive(Debug, Clone)]
+pub struct GradientOption {
+    pub input: TraceInput,
+    // zeros when not given
+    pub target: Option<Tensor>,
+    // step of finite difference check
+    pub eps: f32,
+    // bigger constants are skipped
+    pub max_elements: usize,
+    // keep every gradient value in result
+    pub full: bool,
+    // first elements of each constant checked with finite difference
+    pub check_elements: usize,
+}
+
+impl GradientOption {
+    pub fn new(input: TraceInput) -> Self {
+        GradientOption {
+            input,
+            target: None,
+            eps: 1e-3,
+            max_elements: 4096,
+            full: false,
+            check_elements: 4,
+        }
+    }
+}
+
+// gradient of error on constant
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GradientValue {
+    pub shape: Vec<usize>,
+    pub stats: ValueStats,
+    // l2 norm
+    pub norm: f64,
+    // matrix when gradient is small
+    pub matrix: Option<String>,
+    pub values: Option<Vec<f64>>,
+    // max difference from finite difference on checked elements
+    pub check_error: Option<f64>,
+}
+
+impl LatexEngine {
+    // gradient of every float constant from backward formulas evaluated with traced values,
+    // first elements are checked with central difference of loss
+    pub fn gradient_plan(
+        &self,
+        plan: &InferencePlan,
+        symbol_result: &mut LatexResult,
+        option: &GradientOption,
+    ) -> TractResult<()> {
+        let not_found = |m: &str| Error::new(ErrorKind::NotFound, m.to_string());
+        let model = plan.model();
+        let inputs = Self::trace_inputs(model, &option.input)?;
+        let values = eval_values(model, &plan.order, &inputs)?;
+        let last_point = *symbol_result
+            .senario
+            .last()
+            .ok_or(not_found("model has no layer"))?;
+        let output = values[last_point]
+            .as_ref()
+            .ok_or(not_found("error node is not evaluated"))?[0]
+            .clone();
+        let target = target_values(&option.target, output.len())?;
+        let loss = self.loss.clone().unwrap_or(Loss::Mse);
+        let deltas =
+            self.eval_deltas(model, &plan.order, &values, last_point, &target, option.eps)?;
+
+        let value_option = self.value_option.clone().unwrap_or_default();
+        let mut gradients = Vec::new();
+        for (n, v) in values.iter().enumerate() {
+            if self.cancelled() {
+                return Err(ModelError::Cancelled.into());
+            }
+            let is_const = symbol_result.symbol_map[n]
+                .as_ref()
+                .map(|s| s.op_name == "Const")
+                .unwrap_or(false);
+            let konst = match v {
+                Some(v) if is_const && v[0].datum_type().is_float() => v[0].clone(),
+                _ => continue,
+            };
+            if konst.len() > option.max_elements {
+                continue;
+            }
+            // constant outside of backward path has no gradient
+            let grad = deltas.values[n]
+                .clone()
+                .unwrap_or_else(|| vec![0.0; konst.len()]);
+            let mut check_error: Option<f64> = None;
+            for (at, g) in grad.iter().enumerate().take(option.check_elements) {
+                if self.cancelled() {
+                    return Err(ModelError::Cancelled.into());
+                }
+                let mut errors = Vec::new();
+                for delta in [option.eps, -option.eps].iter() {
+                    let mut t = konst.cast_to::<f32>()?.into_owned();
+                    t.as_slice_mut::<f32>()?[at] += *delta;
+                    let out = eval_changed(model, &plan.order, &values, n, t, last_point)?;
+                    errors.push(loss.eval(&tensor_values(&out[0]).unwrap_or_default(), &target)?);
+                }
+                let numeric = (errors[0] - errors[1]) / (2.0 * option.eps as f64);
+                let error = (numeric - g).abs();
+                check_error = Some(check_error.map_or(error, |e| e.max(error)));
+            }
+            let matrix = if grad.len() <= value_option.max_elements {
+                Some(render_matrix(konst.shape(), &grad, &value_option))
+            } else {
+                None
+            };
+            gradients.push((
+                n,
+                GradientValue {
+                    shape: konst.shape().to_vec(),
+                    stats: ValueStats::from_values(&grad),
+                    norm: grad.iter().map(|g| g * g).sum::<f64>().sqrt(),
+                    matrix,
+                    values: if option.full { Some(grad) } else { None },
+                    check_error,
+                },
+            ));
+        }
+        for (n, g) in gradients.into_iter() {
+            if let Some(f) = symbol_result.symbol_map[n].as_mut() {
+                f.gradient = Some(g);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn gradient_two_layer_test() {
+    let mut engine = LatexEngine::new();
+    engine.gradient_option = Some(GradientOption::new(TraceInput::Random(0)));
+    let result = engine
+        .parse_from_file(
+            &mut std::io::Cursor::new(crate::test_model::two_layer()),
+            Some(4),
+        )
+        .unwrap();
+    let weight = result.symbol_map[result.senario[0]]
+        .as_ref()
+        .unwrap()
+        .inputs[1];
+    let gradient = result.symbol_map[weight]
+        .as_ref()
+        .and_then(|n| n.gradient.clone())
+        .expect("weight has no gradient");
+    assert_eq!(gradient.shape, vec![3, 4]);
+    assert_eq!(
+        gradient.stats.count,
+        gradient.shape.iter().product::<usize>()
+    );
+    assert!(gradient.check_error.unwrap() < 1e-3);
+}
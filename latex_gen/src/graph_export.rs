@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use crate::{LatexNode, LatexResult};
+
+// option for graph export
+#[derive(Debug, Clone, Default)]
+pub struct GraphOption {
+    // group nodes by first n parts of node name (layer1.0.conv1 -> layer1.0)
+    pub cluster_depth: Option<usize>,
+}
+
+impl GraphOption {
+    pub fn new(cluster_depth: Option<usize>) -> Self {
+        GraphOption { cluster_depth }
+    }
+    // cluster name of node
+    fn cluster_key(&self, node: &LatexNode) -> Option<String> {
+        let depth = self.cluster_depth?;
+        let parts: Vec<&str> = node.name.split('.').collect();
+        if depth == 0 || parts.len() < 2 {
+            return None;
+        }
+        let end = depth.min(parts.len() - 1);
+        Some(parts[..end].join("."))
+    }
+}
+
+fn shape_label(shape: &[usize]) -> String {
+    let inner: Vec<String> = shape.iter().map(|s| s.to_string()).collect();
+    format!("[{}]", inner.join("x"))
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', r#"\\"#).replace('"', r#"\""#)
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+impl LatexResult {
+    fn graph_nodes(&self) -> impl Iterator<Item = &LatexNode> {
+        self.symbol_map.iter().filter_map(|s| s.as_ref())
+    }
+    // (from, to, label)
+    fn graph_edges(&self) -> Vec<(usize, usize, String)> {
+        let mut edges = Vec::new();
+        for node in self.graph_nodes() {
+            for i in node.inputs.iter() {
+                if let Some(Some(from)) = self.symbol_map.get(*i) {
+                    edges.push((*i, node.index, shape_label(&from.output_shape)));
+                }
+            }
+        }
+        edges
+    }
+    // cluster name -> node ids, nodes without cluster are not included
+    fn graph_clusters(&self, option: &GraphOption) -> BTreeMap<String, Vec<usize>> {
+        let mut clusters: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for node in self.graph_nodes() {
+            if let Some(key) = option.cluster_key(node) {
+                clusters.entry(key).or_default().push(node.index);
+            }
+        }
+        clusters
+    }
+    // graphviz dot format
+    pub fn gen_dot(&self, option: &GraphOption) -> String {
+        let mut result = String::new();
+        result += "digraph model {\n";
+        result += "    rankdir=TB;\n";
+        result += "    node [shape=box];\n";
+        for node in self.graph_nodes() {
+            let label = format!(
+                "{}\\n{}\\n{}",
+                dot_escape(&node.symbol),
+                dot_escape(&node.op_name),
+                shape_label(&node.output_shape)
+            );
+            result += &format!("    n{} [label=\"{}\"];\n", node.index, label);
+        }
+        for (i, (name, ids)) in self.graph_clusters(option).iter().enumerate() {
+            result += &format!("    subgraph cluster_{} {{\n", i);
+            result += &format!("        label=\"{}\";\n", dot_escape(name));
+            for id in ids.iter() {
+                result += &format!("        n{};\n", id);
+            }
+            result += "    }\n";
+        }
+        for (from, to, label) in self.graph_edges() {
+            result += &format!("    n{} -> n{} [label=\"{}\"];\n", from, to, label);
+        }
+        result += "}\n";
+        result
+    }
+    // mermaid flowchart format
+    pub fn gen_mermaid(&self, option: &GraphOption) -> String {
+        let mut result = String::new();
+        result += "flowchart TB\n";
+        for node in self.graph_nodes() {
+            let label = format!(
+                "{}<br/>{}<br/>{}",
+                mermaid_escape(&node.symbol),
+                mermaid_escape(&node.op_name),
+                shape_label(&node.output_shape)
+            );
+            result += &format!("    n{}[\"{}\"]\n", node.index, label);
+        }
+        for (i, (name, ids)) in self.graph_clusters(option).iter().enumerate() {
+            result += &format!("    subgraph c{}[\"{}\"]\n", i, mermaid_escape(name));
+            for id in ids.iter() {
+                result += &format!("        n{}\n", id);
+            }
+            result += "    end\n";
+        }
+        for (from, to, label) in self.graph_edges() {
+            result += &format!("    n{} -->|\"{}\"| n{}\n", from, label, to);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+fn sample_result() -> LatexResult {
+    let mut result = LatexResult::new(3);
+    let mut input = LatexNode::default();
+    input.index = 0;
+    input.name = "input".to_string();
+    input.symbol = r#"\overline{Input}"#.to_string();
+    input.op_name = "Source".to_string();
+    input.output_shape = vec![1, 4];
+    input.outputs = vec![2];
+    let mut weight = LatexNode::default();
+    weight.index = 1;
+    weight.name = "layer1.0.weight".to_string();
+    weight.symbol = r#"\overline{W_{1}}"#.to_string();
+    weight.op_name = "Const".to_string();
+    weight.output_shape = vec![4, 2];
+    weight.outputs = vec![2];
+    let mut gemm = LatexNode::default();
+    gemm.index = 2;
+    gemm.name = "layer1.0.fc".to_string();
+    gemm.symbol = "f_{1}".to_string();
+    gemm.op_name = "Gemm".to_string();
+    gemm.inputs = vec![0, 1];
+    gemm.output_shape = vec![1, 2];
+    result.symbol_map = vec![Some(input), Some(weight), Some(gemm)];
+    result
+}
+
+#[test]
+fn dot_test() {
+    let dot = sample_result().gen_dot(&GraphOption::new(Some(2)));
+    assert!(dot.contains(r#"n2 [label="f_{1}\nGemm\n[1x2]"];"#));
+    assert!(dot.contains(r#"n1 [label="\\overline{W_{1}}\nConst\n[4x2]"];"#));
+    assert!(dot.contains(r#"n0 -> n2 [label="[1x4]"];"#));
+    assert!(dot.contains(r#"label="layer1.0";"#));
+}
+
+#[test]
+fn mermaid_test() {
+    let mermaid = sample_result().gen_mermaid(&GraphOption::default());
+    assert!(mermaid.contains(r#"n2["f_{1}<br/>Gemm<br/>[1x2]"]"#));
+    assert!(mermaid.contains(r#"n1 -->|"[4x2]"| n2"#));
+    assert!(!mermaid.contains("subgraph"));
+}
@@ -0,0 +1,248 @@
+use std::{
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
+
+use tract_onnx::{
+    prelude::*,
+    tract_hir::utils::{element_symbol, to_strings, MathGen},
+};
+
+use crate::{
+    branch::descendants,
+    parse_struct::{only_inputs_symbol_parts, symbol_split},
+    verify::chain_paths,
+    LatexEngine, LatexResult,
+};
+
+// rows or columns shown before ellipsis
+const PREVIEW: usize = 4;
+
+// matrix of element partials or product of local jacobians
+#[derive(Debug, Clone, PartialEq)]
+pub enum JacobianForm {
+    Matrix,
+    Chain,
+}
+
+impl Default for JacobianForm {
+    fn default() -> Self {
+        JacobianForm::Chain
+    }
+}
+
+impl FromStr for JacobianForm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "matrix" => Ok(JacobianForm::Matrix),
+            "chain" => Ok(JacobianForm::Chain),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown jacobian form {}", s),
+            )),
+        }
+    }
+}
+
+// multi index of flat position
+fn unravel(shape: &[usize], mut flat: usize) -> Vec<usize> {
+    let mut result = vec![0; shape.len()];
+    for (i, s) in shape.iter().enumerate().rev() {
+        result[i] = flat % (*s).max(1);
+        flat /= (*s).max(1);
+    }
+    result
+}
+
+// shown element indexes, None for ellipsis
+fn preview(shape: &[usize]) -> Vec<Option<Vec<usize>>> {
+    let size: usize = shape.iter().product();
+    if size <= PREVIEW {
+        (0..size).map(|k| Some(unravel(shape, k))).collect()
+    } else {
+        vec![
+            Some(unravel(shape, 0)),
+            Some(unravel(shape, 1)),
+            None,
+            Some(unravel(shape, size - 1)),
+        ]
+    }
+}
+
+impl LatexEngine {
+    // jacobian of output node with respect to input or intermediate node, return(symbol,value)
+    pub fn gen_jacobian(
+        &self,
+        math_opvec: &Vec<Box<dyn MathGen>>,
+        model: &InferenceModel,
+        symbol_result: &LatexResult,
+        n_indxs: (usize, usize),
+        form: &JacobianForm,
+    ) -> Result<(String, String), Error> {
+        let (output, wrt) = n_indxs;
+        let not_found = || Error::new(ErrorKind::NotFound, "not found index");
+        let out_node = symbol_result
+            .symbol_map
+            .get(output)
+            .and_then(|s| s.as_ref())
+            .ok_or(not_found())?;
+        let wrt_node = symbol_result
+            .symbol_map
+            .get(wrt)
+            .and_then(|s| s.as_ref())
+            .ok_or(not_found())?;
+        if !descendants(&symbol_result.symbol_map, wrt).contains(&output) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("node {} does not depend on node {}", output, wrt),
+            ));
+        }
+        let math_op = &math_opvec[output];
+        let symbol = math_op.gen_backward(out_node.symbol.clone(), wrt_node.symbol.clone());
+        let value = match form {
+            JacobianForm::Matrix => {
+                let rows = preview(&out_node.output_shape);
+                let cols = preview(&wrt_node.output_shape);
+                let lines: Vec<String> = rows
+                    .iter()
+                    .map(|r| {
+                        let cells: Vec<String> = cols
+                            .iter()
+                            .map(|c| match (r, c) {
+                                (Some(r), Some(c)) => math_op.gen_backward(
+                                    element_symbol(&out_node.symbol, &to_strings(r)),
+                                    element_symbol(&wrt_node.symbol, &to_strings(c)),
+                                ),
+                                (Some(_), None) => r#"\cdots"#.to_string(),
+                                (None, Some(_)) => r#"\vdots"#.to_string(),
+                                (None, None) => r#"\ddots"#.to_string(),
+                            })
+                            .collect();
+                        cells.join(" & ")
+                    })
+                    .collect();
+                format!(
+                    r#"\begin{{bmatrix}}{}\end{{bmatrix}}"#,
+                    lines.join(r#" \\ "#)
+                )
+            }
+            JacobianForm::Chain if output == wrt => "I".to_string(),
+            JacobianForm::Chain => {
+                let start = self.diff_node(model, &symbol_result.symbol_map, wrt);
+                let chain =
+                    self.expand_diff_symbol(&symbol_result.symbol_map, model, start, output);
+                let order = model
+                    .eval_order()
+                    .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{:?}", e)))?;
+                let terms = chain_paths(&chain)
+                    .into_iter()
+                    .map(|mut path| {
+                        path.sort_by_key(|n| order.iter().position(|o| o == n));
+                        // local jacobians from output side
+                        let locals = path
+                            .windows(2)
+                            .rev()
+                            .map(|w| {
+                                let upper =
+                                    symbol_result.symbol_map[w[1]].as_ref().ok_or(not_found())?;
+                                let under =
+                                    symbol_result.symbol_map[w[0]].as_ref().ok_or(not_found())?;
+                                Ok(math_opvec[w[1]]
+                                    .gen_backward(upper.symbol.clone(), under.symbol.clone()))
+                            })
+                            .collect::<Result<Vec<String>, Error>>()?;
+                        Ok(locals.join(" "))
+                    })
+                    .collect::<Result<Vec<String>, Error>>()?;
+                if terms.len() > 1 {
+                    let (_, _, paths) = self.symbol_library.get_symbol("_Paths").unwrap();
+                    let splits = symbol_split(paths.formul.as_str()).unwrap();
+                    only_inputs_symbol_parts(splits, vec![terms.join("+"), "paths".to_string()])
+                } else {
+                    terms.join("")
+                }
+            }
+        };
+        Ok((symbol, value))
+    }
+}
+
+#[test]
+fn preview_test() {
+    assert_eq!(unravel(&[2, 3], 4), vec![1, 1]);
+    assert_eq!(preview(&[2, 2]).len(), 4);
+    let p = preview(&[1, 10]);
+    assert_eq!(p[2], None);
+    assert_eq!(p[3], Some(vec![0, 9]));
+    assert_eq!(
+        "matrix".parse::<JacobianForm>().unwrap(),
+        JacobianForm::Matrix
+    );
+}
+
+#[test]
+fn jacobian_two_layer_test() {
+    let session = LatexEngine::new()
+        .session_from_file(
+            &mut std::io::Cursor::new(crate::test_model::two_layer()),
+            crate::ParseMode::Full(Some(4)),
+        )
+        .unwrap();
+    let input = session
+        .result
+        .symbol_map
+        .iter()
+        .flatten()
+        .find(|n| n.op_name == "Source")
+        .map(|n| n.index)
+        .expect("model has no input");
+    let output = *session.result.senario.last().unwrap();
+    let (_, chain) = session
+        .jacobian(output, input, &JacobianForm::Chain)
+        .unwrap();
+    assert!(chain.contains(r#"\partial"#));
+    let (_, matrix) = session
+        .jacobian(output, input, &JacobianForm::Matrix)
+        .unwrap();
+    assert!(matrix.starts_with(r#"\begin{bmatrix}"#));
+    // output does not depend on itself through input
+    assert!(session
+        .jacobian(input, output, &JacobianForm::Chain)
+        .is_err());
+}
+
+#[test]
+fn jacobian_residual_test() {
+    let session = LatexEngine::new()
+        .session_from_file(
+            &mut std::io::Cursor::new(crate::test_model::residual(2)),
+            crate::ParseMode::Full(Some(4)),
+        )
+        .unwrap();
+    let input = session
+        .result
+        .symbol_map
+        .iter()
+        .flatten()
+        .find(|n| n.op_name == "Source")
+        .map(|n| n.index)
+        .expect("model has no input");
+    let output = *session.result.senario.last().unwrap();
+    let (_, chain) = session
+        .jacobian(output, input, &JacobianForm::Chain)
+        .unwrap();
+    assert!(!chain.contains("Undefined"), "{}", chain);
+    // every path through both residual blocks is its own term
+    let inner = chain
+        .trim_start_matches(r#"\underbrace{\left("#)
+        .split(r#"\right)}"#)
+        .next()
+        .unwrap();
+    let mut terms: Vec<&str> = inner.split('+').collect();
+    assert_eq!(terms.len(), 4, "{}", chain);
+    terms.sort();
+    terms.dedup();
+    assert_eq!(terms.len(), 4, "{}", chain);
+}
@@ -12,7 +12,7 @@ use tract_onnx::{
             konst::Const,
             source::Source,
         },
-        utils::{is_weightable, mathgen_ele_op, FormulKind, MathGen},
+        utils::{element_symbol, is_weightable, mathgen_ele_op, FormulKind},
     },
     Onnx,
 };
@@ -24,17 +24,52 @@ use tract_onnx::tract_hir::utils::mathgen_op;
 use tract_onnx::{prelude::*, tract_hir::infer::InferenceOp};
 
 use crate::parse_struct::{except_self_symbol_parts, only_inputs_symbol_parts};
-pub use tract_onnx::prelude::TractResult;
+pub use tract_onnx::prelude::{InferenceModel, TractError, TractResult};
+pub use tract_onnx::tract_hir::utils::MathGen;
 
 use self::{
+    branch::Reach,
     node_info::{Formul, FormulNode},
-    parse_struct::{insert_symbol_parts, symbol_split, DebugValue},
+    parse_struct::{insert_symbol_parts, symbol_split},
+    window_backward::delta_symbol,
 };
 
 use serde::{Deserialize, Serialize};
 
+mod branch;
+mod control;
+mod document;
+mod formula;
+mod gradient;
+mod graph_export;
+mod jacobian;
+mod loss;
+mod matrix_form;
 mod node_info;
+mod optimizer;
+mod param_backward;
 mod parse_struct;
+mod session;
+#[cfg(any(test, feature = "test_model"))]
+pub mod test_model;
+mod trace;
+mod value;
+mod verify;
+mod window_backward;
+
+pub use control::{NodeListener, ParseControl};
+pub use document::OutputFormat;
+pub use gradient::{GradientOption, GradientValue};
+pub use graph_export::GraphOption;
+pub use jacobian::JacobianForm;
+pub use loss::Loss;
+pub use matrix_form::BackwardForm;
+pub use optimizer::{Optimizer, OptimizerOption};
+pub use parse_struct::DebugValue;
+pub use session::ExpandSession;
+pub use trace::{TraceInput, TraceValue};
+pub use value::{ValueOption, ValueStats};
+pub use verify::{GradCheck, VerifyOption};
 
 type InferenceNode = Node<InferenceFact, Box<dyn InferenceOp>>;
 
@@ -61,8 +96,11 @@ where
 
 // onnx parsing engine 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct LatexNode {
     pub index: usize,
+    #[serde(default)]
+    pub name: String,
     pub inputs: Vec<usize>,
     pub outputs: Vec<usize>,
     pub symbol: String,
@@ -75,6 +113,17 @@ pub struct LatexNode {
     pub backward_symbol: String,
     pub description: String,
     pub op_attributes: DebugValue,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub value_stats: Option<ValueStats>,
+    #[serde(default)]
+    pub trace: Option<TraceValue>,
+    // parameter update equations of optimizer
+    #[serde(default)]
+    pub update: Vec<String>,
+    #[serde(default)]
+    pub gradient: Option<GradientValue>,
 }
 impl LatexNode {
     // erase prefix
@@ -84,6 +133,7 @@ impl LatexNode {
         self.forward_value = r(&self.forward_value);
         self.backward_symbol = r(&self.backward_symbol);
         self.backward_value = r(&self.backward_value);
+        self.update = self.update.iter().map(r).collect();
     }
 }
 
@@ -93,6 +143,7 @@ pub struct SymbolLibrary {
     pub func: Formul,
     pub etc: Formul,
     pub activation: Formul,
+    pub loss: Formul,
 }
 
 impl SymbolLibrary {
@@ -113,15 +164,38 @@ impl SymbolLibrary {
             "/formuls/activation.ron"
         )))
         .expect("activation error");
+        let loss_info = node_info::read_str(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/formuls/loss.ron"
+        )))
+        .expect("loss error");
         SymbolLibrary {
             func: func_info,
             etc: etc_info,
             activation: activation_info,
+            loss: loss_info,
+        }
+    }
+    // bundled library with ron files of directory in place, ex) formul.ron, loss.ron
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> std::io::Result<Self> {
+        let mut library = Self::new();
+        let mut files = [
+            ("formul.ron", &mut library.func),
+            ("etc.ron", &mut library.etc),
+            ("activation.ron", &mut library.activation),
+            ("loss.ron", &mut library.loss),
+        ];
+        for (name, formul) in files.iter_mut() {
+            let path = dir.as_ref().join(name);
+            if path.exists() {
+                **formul = node_info::read_ron(path)?;
+            }
         }
+        Ok(library)
     }
     // (symbol,form)
     pub fn get_symbol(&self, target: &str) -> Option<(String, FormulKind, FormulNode)> {
-        let form = [&self.func, &self.etc, &self.activation];
+        let form = [&self.func, &self.etc, &self.activation, &self.loss];
         form.iter().filter_map(|x| x.gen_symbol(target).ok()).next()
     }
     // generate weight symbol 
@@ -200,6 +274,14 @@ pub enum DiffChainNode {
     UnWeightable(usize, String),
     Sum(Box<DiffChainNode>, Vec<usize>),
     Chain(Vec<DiffChainNode>),
+    // sum over downstream paths of branch, with node where they join,
+    // paths end at gradient of join node and tail is its definition
+    Paths(
+        Vec<DiffChainNode>,
+        Option<(usize, String, Option<Box<DiffChainNode>>)>,
+    ),
+    // named gradient of error on node output, ex) \delta^{(f_{3})}
+    Delta(usize, String),
     Not,
 }
 
@@ -214,7 +296,55 @@ pub struct LatexEngine {
     pub activation_count: usize,
     pub symbol_library: SymbolLibrary,
     pub math_op_vec: Vec<Option<Box<dyn MathGen>>>,
+    // render const values when set
+    pub value_option: Option<ValueOption>,
+    // evaluate model and attach node values when set
+    pub trace_input: Option<TraceInput>,
+    // explicit loss at the head of backward chain when set
+    pub loss: Option<Loss>,
+    // element wise chain rule or matrix form
+    pub backward_form: BackwardForm,
+    // update equations after backward when set
+    pub optimizer: Option<OptimizerOption>,
+    // attach numeric gradient of constants when set
+    pub gradient_option: Option<GradientOption>,
+    // progress and cancellation of parse and backward when set
+    pub control: Option<ParseControl>,
+}
+// model failure which callers tell apart, found in error chain by downcast
+#[derive(Debug, Clone, Serialize)]
+pub enum ModelError {
+    // onnx file can not be read
+    Malformed,
+    // node without formula, ex) op unknown to tract
+    Unsupported {
+        node: usize,
+        name: String,
+        op: String,
+    },
+    // stopped by parse control
+    Cancelled,
+}
+
+impl Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::Malformed => write!(f, "malformed onnx model"),
+            ModelError::Unsupported { node, name, op } => {
+                write!(f, "unsupported op {} at node {} ({})", op, node, name)
+            }
+            ModelError::Cancelled => write!(f, "parse cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+fn malformed(e: TractError) -> TractError {
+    e.context(ModelError::Malformed)
 }
+
+#[derive(Clone, Copy)]
 pub enum ErrorResultTo {
     Total,
     Innner(usize),
@@ -290,35 +420,39 @@ pub fn into_proto(input: String) -> TractResult<()> {
 }
 // just print model info 
 pub fn model_info<P: AsRef<Path>>(path: P) -> TractResult<()> {
+    print!("{}", gen_model_info(path)?);
+    Ok(())
+}
+// model info as text
+pub fn gen_model_info<P: AsRef<Path>>(path: P) -> TractResult<String> {
     let model = tract_onnx::onnx()
         // load the model
         .model_for_path(path)?
         .into_runnable()?;
     let inf_model = model.model.clone();
 
+    let mut result = String::new();
     for n in inf_model.nodes() {
         let op_name = n.op().name();
         let node_name = n.name.clone();
-        println!("id: {}", n.id);
-        println!("op options {:?}", n.op());
-        println!("inputs: ");
+        result += &format!("id: {}\n", n.id);
+        result += &format!("op options {:?}\n", n.op());
+        result += "inputs: \n";
         for i in n.inputs.iter() {
-            print!(" {:?}", i);
-            let fact = model.model().outlet_fact(*i).unwrap();
-            println!("shape: {:?}", fact.shape.clone());
-            // println!("value: {:?}", fact.value.clone());
+            let fact = model.model().outlet_fact(*i)?;
+            result += &format!(" {:?} shape: {:?}\n", i, fact.shape.clone());
         }
         for i in n.outputs.iter() {
-            println!("out test:{:?}", i.fact.shape);
+            result += &format!("out shape: {:?}\n", i.fact.shape);
             for j in i.successors.iter() {
-                println!("output: {:?}", j);
+                result += &format!("output: {:?}\n", j);
             }
         }
-        println!("node name: {}", node_name);
-        println!("op name: {}", op_name);
-        println!();
+        result += &format!("node name: {}\n", node_name);
+        result += &format!("op name: {}\n", op_name);
+        result += "\n";
     }
-    Ok(())
+    Ok(result)
 }
 // only for store func index and weight index
 pub struct Indexes {
@@ -348,11 +482,38 @@ impl LatexEngine {
             activation_count: 0,
             symbol_library: symbol_lib,
             math_op_vec: Vec::new(),
+            value_option: None,
+            trace_input: None,
+            loss: None,
+            backward_form: BackwardForm::Element,
+            optimizer: None,
+            gradient_option: None,
+            control: None,
+        }
+    }
+    // engine with notation library other than bundled one
+    pub fn with_library(symbol_library: SymbolLibrary) -> Self {
+        LatexEngine {
+            symbol_library,
+            ..Self::new()
         }
     }
     // read from file
     pub fn model_from_file(&self, reader: &mut dyn Read) -> TractResult<InferenceModel> {
-        let s = self.engine.model_for_read(reader)?.into_runnable()?;
+        let s = self
+            .engine
+            .model_for_read(reader)
+            .map_err(malformed)?
+            .into_runnable()?;
+        Ok(s.model().clone())
+    }
+    // read from path
+    pub fn model_from_path<P: AsRef<Path>>(&self, path: P) -> TractResult<InferenceModel> {
+        let s = self
+            .engine
+            .model_for_path(path)
+            .map_err(malformed)?
+            .into_runnable()?;
         Ok(s.model().clone())
     }
     fn flush(&mut self) {
@@ -368,41 +529,84 @@ impl LatexEngine {
         path: P,
         many: Option<usize>,
     ) -> TractResult<LatexResult> {
-        let plan = self.engine.model_for_path(path)?.into_runnable()?;
-        self.start_parse(&plan, many)
+        let plan = self
+            .engine
+            .model_for_path(path)
+            .map_err(malformed)?
+            .into_runnable()?;
+        self.start_parse(&plan, ParseMode::Full(many))
     }
-    pub fn parse_from_file(
+    // read from path with input shape override and parse mode
+    pub fn parse_from_path_with<P: AsRef<Path>>(
         &mut self,
-        file: &mut dyn Read,
-        many: Option<usize>,
+        path: P,
+        input_shapes: &[Vec<usize>],
+        mode: ParseMode,
     ) -> TractResult<LatexResult> {
-        let plan = self.engine.model_for_read(file)?.into_runnable()?;
-        self.start_parse(&plan, many)
+        let plan = self.plan_from_path_with(path, input_shapes)?;
+        self.start_parse(&plan, mode)
     }
-
-    // generate input and start parse 
-    fn start_parse(
+    // read from path with the same input shape overrides as parse_from_path_with
+    pub fn model_from_path_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        input_shapes: &[Vec<usize>],
+    ) -> TractResult<InferenceModel> {
+        Ok(self
+            .plan_from_path_with(path, input_shapes)?
+            .model()
+            .clone())
+    }
+    fn plan_from_path_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        input_shapes: &[Vec<usize>],
+    ) -> TractResult<InferencePlan> {
+        let mut model = self.engine.model_for_path(path).map_err(malformed)?;
+        for (i, shape) in input_shapes.iter().enumerate() {
+            let fact = InferenceFact::dt_shape(f32::datum_type(), shape.clone());
+            model = model.with_input_fact(i, fact)?;
+        }
+        model.into_runnable()
+    }
+    pub fn parse_from_file(
         &mut self,
-        plan: &InferencePlan,
+        file: &mut dyn Read,
         many: Option<usize>,
     ) -> TractResult<LatexResult> {
-        let mm = plan.model();
-        let input_shape: Vec<usize> = mm.node(0).outputs[0]
-            .fact
-            .shape
-            .dims()
-            .map(|s| format!("{}", s).as_str().parse().unwrap())
-            .collect();
-        let _total_elements: usize = input_shape.iter().product();
-
-        // let mut rng = thread_rng();
-        // let vals: Vec<_> = (0..total_elements).map(|_| rng.gen::<f32>()).collect();
-        // let input = tract_ndarray::arr1(&vals)
-        //     .into_shape(input_shape.as_slice())
-        //     .unwrap();
-
-        let result = self.parse_plan(&plan, ParseMode::Full(many));
+        let plan = self
+            .engine
+            .model_for_read(file)
+            .map_err(malformed)?
+            .into_runnable()?;
+        self.start_parse(&plan, ParseMode::Full(many))
+    }
 
+    // start parse and trace values if trace input is set
+    fn start_parse(&mut self, plan: &InferencePlan, mode: ParseMode) -> TractResult<LatexResult> {
+        if let Some(node) = plan
+            .model()
+            .nodes()
+            .iter()
+            .find(|n| Self::try_mathgen(n).is_none())
+        {
+            return Err(ModelError::Unsupported {
+                node: node.id,
+                name: node.name.clone(),
+                op: node.op().name().to_string(),
+            }
+            .into());
+        }
+        let mut result = self.parse_plan(&plan, mode);
+        if self.cancelled() {
+            return Err(ModelError::Cancelled.into());
+        }
+        if let Some(ref input) = self.trace_input {
+            self.trace_plan(plan, &mut result, input)?;
+        }
+        if let Some(ref option) = self.gradient_option {
+            self.gradient_plan(plan, &mut result, option)?;
+        }
         Ok(result)
     }
     //  start parse
@@ -420,10 +624,15 @@ impl LatexEngine {
         let _print_time = |s: &Instant, _m: &str| {
             let _end = s.elapsed();
         };
+        if let Some(ref control) = self.control {
+            control.start(plan.order.len());
+        }
         // iterate node by order 
         for (_step, n) in plan.order.iter().enumerate() {
+            if self.cancelled() {
+                break;
+            }
             let node = inf_model.node(*n);
-            println!("node {}", *n);
             // println!("node_kind {:?}",node_kind);
             let mt = self.math_op_vec.get_mut(*n).unwrap();
             // print_time(&start,"mathgen");
@@ -516,7 +725,7 @@ impl LatexEngine {
                 ParseMode::Brief => {
                     let input_symbols = input_ids
                         .iter()
-                        .map(|s| self.symbol_map[*s].as_ref().unwrap().symbol.clone())
+                        .map(|s| self.leaf_symbol(self.symbol_map[*s].as_ref().unwrap()))
                         .collect();
                     node_op.gen_forward_value(
                         input_symbols,
@@ -532,6 +741,9 @@ impl LatexEngine {
                 form.inputs = input_ids.clone();
                 form.forward_value = forward_string;
             }
+            if let Some(ref control) = self.control {
+                control.step(self.symbol_map[*n].as_ref());
+            }
         }
 
         // backward
@@ -545,21 +757,24 @@ impl LatexEngine {
     }
     // generate boxed mathgen 
     fn boxed_mathgen(node: &InferenceNode) -> Box<dyn MathGen> {
+        Self::try_mathgen(node).unwrap()
+    }
+    // boxed mathgen, None if op has no formula
+    fn try_mathgen(node: &InferenceNode) -> Option<Box<dyn MathGen>> {
         if let Some(e) = node.op_as::<Box<dyn Expansion>>().cloned() {
-            Box::new(e)
+            Some(Box::new(e))
         } else {
             let op = node.op();
             // println!("op detail {:?}",op);
             let mut result = each_op!(op, [Const, Pad, Dummy, Source, MaxPool, SumPool]);
             let t = result.iter_mut().find_map(|s| std::mem::take(s));
             if let Some(x) = t {
-                x
+                Some(x)
             } else {
                 // elementwise
-                let ele = node.op_as::<ElementWiseOp>();
-                let inner_ref = ele.unwrap().0.as_ref();
+                let inner_ref = node.op_as::<ElementWiseOp>()?.0.as_ref();
                 let mut ele_map = each_ele_op!(inner_ref, [Sigmoid]);
-                ele_map.iter_mut().find_map(|s| std::mem::take(s)).unwrap()
+                ele_map.iter_mut().find_map(|s| std::mem::take(s))
             }
         }
     }
@@ -571,15 +786,43 @@ impl LatexEngine {
         input_indexs: Indexes,
         depth: Option<usize>,
     ) -> Result<(), std::io::Error> {
-        let senario = symbol_result.senario.clone();
-        let last_point = senario.last().unwrap();
         let model = tract_onnx::onnx()
             .model_for_proto_model(&model_proto)
             .unwrap();
 
         let math_ops = Self::math_op_vecs(&model);
+        self.gen_back_model(symbol_result, &model, &math_ops, &input_indexs, depth)
+    }
+    // back propagation of every layer and parameter of model, each node is passed to control
+    pub fn gen_back_model(
+        &self,
+        symbol_result: &mut LatexResult,
+        model: &InferenceModel,
+        math_ops: &Vec<Box<dyn MathGen>>,
+        input_indexs: &Indexes,
+        depth: Option<usize>,
+    ) -> Result<(), std::io::Error> {
+        let senario = symbol_result.senario.clone();
+        let last_point = senario.last().ok_or(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "model has no layer to backward",
+        ))?;
+        symbol_result.loss = self.gen_loss(symbol_result, *last_point)?;
+        symbol_result.skipped.clear();
+        // bias, normalization, slope and input parameters
+        let params: Vec<usize> = (0..symbol_result.symbol_map.len())
+            .filter(|p| !senario.contains(p) && symbol_result.symbol_map[*p].is_some())
+            .collect();
+        if let Some(ref control) = self.control {
+            control.start(senario.len() + params.len());
+        }
+        let cancelled =
+            || std::io::Error::new(std::io::ErrorKind::Interrupted, "backward cancelled");
         // iterate senario 
         for i in senario.iter() {
+            if self.cancelled() {
+                return Err(cancelled());
+            }
             let _node = model.node(*i);
             let math_op = math_ops[*i].as_ref();
             let sym_node = symbol_result.symbol_map[*i].as_ref().unwrap();
@@ -587,26 +830,97 @@ impl LatexEngine {
             let kind = math_op.get_symbol_type(sym_node.extra_symbol.clone());
             // if senario 
             if is_weightable(kind).is_none() {
-                println!("skip {}", *i);
+                self.back_step(symbol_result, None);
                 continue;
             }
             // get each backpropagation 
             let (s, v) = self.gen_each_back(
-                &math_ops,
-                &model,
+                math_ops,
+                model,
                 symbol_result,
                 (*i, *last_point),
-                &input_indexs,
+                input_indexs,
                 depth,
             )?;
+            // update is set before step, listener sends node with it
+            let update = self
+                .gen_node_update(symbol_result, *i, &s)
+                .unwrap_or_default();
             if let Some(f) = symbol_result.symbol_map[*i].as_mut() {
-                println!("called {},{}", v, s);
                 f.backward_value = v;
                 f.backward_symbol = s;
+                f.update = update;
+            }
+            self.back_step(symbol_result, Some(*i));
+        }
+        for p in params.iter() {
+            if self.cancelled() {
+                return Err(cancelled());
+            }
+            // weights are covered by backward of their layer
+            let is_weight = senario.iter().any(|n| {
+                let sym_node = symbol_result.symbol_map[*n].as_ref().unwrap();
+                let kind = math_ops[*n].get_symbol_type(sym_node.extra_symbol.clone());
+                is_weightable(kind).is_some()
+                    && sym_node.inputs.get(symbol_result.weight_slot(*n)) == Some(p)
+            });
+            if is_weight {
+                self.back_step(symbol_result, None);
+                continue;
+            }
+            match self.gen_param_back(math_ops, model, symbol_result, (*p, *last_point), &[]) {
+                Ok((s, v)) => {
+                    let update = self
+                        .gen_node_update(symbol_result, *p, &s)
+                        .unwrap_or_default();
+                    if let Some(f) = symbol_result.symbol_map[*p].as_mut() {
+                        f.backward_value = v;
+                        f.backward_symbol = s;
+                        f.update = update;
+                    }
+                    self.back_step(symbol_result, Some(*p));
+                }
+                Err(_) => {
+                    symbol_result.skipped.push(*p);
+                    self.back_step(symbol_result, None);
+                }
             }
         }
         Ok(())
     }
+    // count node done, computed node is passed to listener of control
+    fn back_step(&self, symbol_result: &LatexResult, n: Option<usize>) {
+        if let Some(ref control) = self.control {
+            control.step(n.and_then(|n| symbol_result.symbol_map[n].as_ref()));
+        }
+    }
+    // loss definition on output of last node, ex) E_{(total,)}=\frac{1}{N}\sum_{i}(...)^{2}
+    pub fn gen_loss(
+        &self,
+        symbol_result: &LatexResult,
+        last_point: usize,
+    ) -> Result<Option<String>, std::io::Error> {
+        let loss = match self.loss {
+            Some(ref l) => l,
+            None => return Ok(None),
+        };
+        let symbol = symbol_result.symbol_map[last_point]
+            .as_ref()
+            .map(|s| s.symbol.clone())
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "not found last node",
+            ))?;
+        let (_, _, underform) = self.symbol_library.get_symbol("_Under").unwrap();
+        let under_splits = symbol_split(underform.formul.as_str()).unwrap();
+        let output = only_inputs_symbol_parts(under_splits.clone(), vec![symbol, "i".to_string()]);
+        let target = only_inputs_symbol_parts(under_splits, vec!["t".to_string(), "i".to_string()]);
+        let e_symbol = self
+            .symbol_library
+            .gen_error_symbol(vec!["total".to_string(), "".to_string()]);
+        let formul = loss.gen_formul(&self.symbol_library, output, target)?;
+        Ok(Some(format!("{}={}", e_symbol, formul)))
+    }
     // generate boxed mathgen in model node 
     pub fn math_op_vecs(model: &InferenceModel) -> Vec<Box<dyn MathGen>> {
         model
@@ -652,8 +966,25 @@ impl LatexEngine {
         //         "which's size exceed shape range",
         //     ));
         // }
+        // check loss template before building chain
+        self.gen_loss(symbol_result, last_point)?;
+        if self.backward_form == BackwardForm::Matrix {
+            return self.gen_matrix_back(math_opvec, model, symbol_result, n_indxs);
+        }
         let sym_node = symbol_result.symbol_map[index].as_ref().unwrap();
         let kind = math_op.get_symbol_type(sym_node.extra_symbol.clone());
+        // conv and pooling use correlation with delta instead of element chain
+        if matches!(
+            kind,
+            FormulKind::Cnn | FormulKind::MaxPool | FormulKind::SumPool
+        ) {
+            return self.gen_window_back(math_opvec, model, symbol_result, n_indxs);
+        }
+        let cancelled =
+            || std::io::Error::new(std::io::ErrorKind::Interrupted, "backward cancelled");
+        if self.cancelled() {
+            return Err(cancelled());
+        }
         let start_node = if is_weightable(kind).is_some() {
             DiffChainNode::Weightable(index, symbol.clone())
         } else {
@@ -667,7 +998,10 @@ impl LatexEngine {
             start_node,
             last_point,
         );
-        println!("expand {:?}", expand_value);
+        // expanding chain of large model takes long, stop before rendering
+        if self.cancelled() {
+            return Err(cancelled());
+        }
         let e_option = depth
             .map(|x| ErrorResultTo::Innner(x))
             .unwrap_or(ErrorResultTo::Total);
@@ -682,6 +1016,44 @@ impl LatexEngine {
 
         Ok((math_op.gen_backward(e_symbol, down_symbol), backward))
     }
+    // formula of one output element, ex) channel 3, row 10, col 4 of conv output
+    pub fn gen_element(
+        &self,
+        math_opvec: &Vec<Box<dyn MathGen>>,
+        symbol_result: &LatexResult,
+        node_idx: usize,
+        coord: &[usize],
+        expand: Option<usize>,
+    ) -> Result<String, std::io::Error> {
+        let sym_node = symbol_result
+            .symbol_map
+            .get(node_idx)
+            .and_then(|s| s.as_ref())
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "not found index",
+            ))?;
+        let shape = &sym_node.output_shape;
+        if coord.len() != shape.len() || coord.iter().zip(shape.iter()).any(|(c, s)| c >= s) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("coordinate {:?} is out of shape {:?}", coord, shape),
+            ));
+        }
+        let (inputs, input_shapes) = symbol_result.input_info(node_idx)?;
+        let value = math_opvec[node_idx]
+            .gen_element_value(inputs, input_shapes, coord.to_vec(), expand)
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("element formula is not supported for {}", sym_node.op_name),
+            ))?;
+        let index: Vec<String> = coord.iter().map(|s| s.to_string()).collect();
+        Ok(format!(
+            "{}={}",
+            element_symbol(&sym_node.symbol, &index),
+            value
+        ))
+    }
     // recursive forward propgation 
     fn rec_node(
         &self,
@@ -690,13 +1062,13 @@ impl LatexEngine {
         many: Option<usize>,
         timer: &Instant,
     ) -> String {
-        let _print_time = |s: &Instant, m: &str| {
-            let end = s.elapsed();
-            println!("{}: {:?}", m, end);
-        };
+        // deep formula stops early, result is dropped anyway
+        if self.cancelled() {
+            return String::new();
+        }
         let sym_node = self.symbol_map[node.id].as_ref().unwrap();
         if node.inputs.len() == 0 {
-            return sym_node.symbol.clone();
+            return self.leaf_symbol(sym_node);
         }
         match many {
             Some(x) if x == 0 => sym_node.symbol.clone(),
@@ -724,6 +1096,32 @@ impl LatexEngine {
             }
         }
     }
+    // symbol of input node, matrix if inline value is set
+    fn leaf_symbol(&self, sym_node: &LatexNode) -> String {
+        match (&self.value_option, &sym_node.value) {
+            (Some(option), Some(v)) if option.inline => v.clone(),
+            _ => sym_node.symbol.clone(),
+        }
+    }
+    // const value as matrix or statistics
+    fn const_value(&self, node: &InferenceNode) -> (Option<String>, Option<ValueStats>) {
+        let option = match self.value_option {
+            Some(ref o) => o,
+            None => return (None, None),
+        };
+        let tensor = match node.op_as::<Const>() {
+            Some(c) => c.0.clone(),
+            None => return (None, None),
+        };
+        match value::tensor_values(&tensor) {
+            Some(values) if values.len() <= option.max_elements => (
+                Some(value::render_matrix(tensor.shape(), &values, option)),
+                None,
+            ),
+            Some(values) => (None, Some(ValueStats::from_values(&values))),
+            None => (None, None),
+        }
+    }
     // count up symbol 
     fn countup(&mut self, kind: &FormulKind) -> Option<usize> {
         match kind {
@@ -779,12 +1177,20 @@ impl LatexEngine {
         let kind = node_op.get_symbol_type(extra_symbol.clone());
 
         let i = self.countup(&kind).unwrap_or(0);
-        let symbol = node_op.gen_forward(extra_symbol.clone(), i);
+        // ops without symbol kind are named by op and node index ex) Add_{7}
+        let symbol = match kind {
+            FormulKind::Undefined => format!("{}_{{{}}}", op_name, index),
+            _ => node_op.gen_forward(extra_symbol.clone(), i),
+        };
+        let (value, value_stats) = self.const_value(node);
         if let Some(nn) = self.symbol_map[index].as_mut() {
             nn.op_name = op_name;
+            nn.name = n_name.clone();
             nn.index = index;
             nn.symbol = symbol;
             nn.extra_symbol = extra_symbol.clone();
+            nn.value = value;
+            nn.value_stats = value_stats;
             if node.outputs.len() != 0 && node.outputs[0].successors.len() != 0 {
                 for i in node.outputs[0].successors.iter() {
                     nn.outputs.push(i.node);
@@ -800,6 +1206,19 @@ impl LatexEngine {
         model: &InferenceModel,
         target: DiffChainNode,
         error_node: usize,
+    ) -> DiffChainNode {
+        let reach = Reach::new(symbol_map);
+        self.expand_diff(symbol_map, &reach, &[], model, target, error_node)
+    }
+    // expansion stops at gradient of stops
+    pub(crate) fn expand_diff(
+        &self,
+        symbol_map: &Vec<Option<LatexNode>>,
+        reach: &Reach,
+        stops: &[usize],
+        model: &InferenceModel,
+        target: DiffChainNode,
+        error_node: usize,
     ) -> DiffChainNode {
         match target {
             // chain start
@@ -810,24 +1229,10 @@ impl LatexEngine {
 
                 match v[0].clone() {
                     DiffChainNode::Weightable(i, _s) => {
-                        println!("weightable in chain: {}", i);
                         if i != error_node {
-                            let node = symbol_map[i].as_ref().unwrap();
-                            let into_node_id = node.outputs[0];
-
-                            let in_node = symbol_map[into_node_id].as_ref().unwrap();
-                            let sum = self.expand_diff_symbol(
-                                symbol_map,
-                                model,
-                                self.diff_node(model, symbol_map, into_node_id),
-                                error_node,
+                            sum_it.push(
+                                self.downstream_sum(symbol_map, reach, stops, model, i, error_node),
                             );
-                            let size_check = if in_node.output_shape.len() > 1 {
-                                in_node.output_shape.split_at(1).1.to_vec()
-                            } else {
-                                vec![in_node.output_shape[0]]
-                            };
-                            sum_it.push(DiffChainNode::Sum(Box::new(sum), size_check));
                             sum_it.append(&mut v_clone);
                             DiffChainNode::Chain(sum_it)
                         } else {
@@ -835,24 +1240,10 @@ impl LatexEngine {
                         }
                     }
                     DiffChainNode::UnWeightable(i, _s) => {
-                        println!("unwieghtable in chain: {}", i);
                         if i != error_node {
-                            let node = symbol_map[i].as_ref().unwrap();
-                            let into_node_id = node.outputs[0];
-
-                            let in_node = symbol_map[into_node_id].as_ref().unwrap();
-                            let sum = self.expand_diff_symbol(
-                                symbol_map,
-                                model,
-                                self.diff_node(model, symbol_map, into_node_id),
-                                error_node,
+                            sum_it.push(
+                                self.downstream_sum(symbol_map, reach, stops, model, i, error_node),
                             );
-                            let size_check = if in_node.output_shape.len() > 1 {
-                                in_node.output_shape.split_at(1).1.to_vec()
-                            } else {
-                                vec![in_node.output_shape[0]]
-                            };
-                            sum_it.push(DiffChainNode::Sum(Box::new(sum), size_check));
                             sum_it.append(&mut v_clone);
                             DiffChainNode::Chain(sum_it)
                         } else {
@@ -864,13 +1255,20 @@ impl LatexEngine {
             }
             // first
             DiffChainNode::Weightable(i, s) => {
-                //
+                // branch or merge node, sum over every path in chain
+                if i != error_node
+                    && (stops.contains(&i)
+                        || Self::downstream(symbol_map, reach, i, error_node).len() > 1)
+                {
+                    let chain = DiffChainNode::Chain(vec![DiffChainNode::Weightable(i, s)]);
+                    return self.expand_diff(symbol_map, reach, stops, model, chain, error_node);
+                }
                 let mut result = Vec::new();
                 // println!("out length {}", node.outputs.len());
                 let sym_node = symbol_map[i].as_ref().unwrap();
                 let mut already_rec = false;
                 if sym_node.outputs.len() != 0 {
-                    let into_node_idx = sym_node.outputs[0];
+                    let into_node_idx = Self::downstream(symbol_map, reach, i, error_node)[0];
                     let t_d = self.diff_node(model, symbol_map, into_node_idx);
                     match t_d.clone() {
                         x @ DiffChainNode::Weightable(_, _) => {
@@ -880,7 +1278,8 @@ impl LatexEngine {
                             } else {
                                 vec![in_node.output_shape[0]]
                             };
-                            let d = self.expand_diff_symbol(symbol_map, model, x, error_node);
+                            let d =
+                                self.expand_diff(symbol_map, reach, stops, model, x, error_node);
                             already_rec = true;
                             result.push(DiffChainNode::Sum(Box::new(d), size_check));
                         }
@@ -893,8 +1292,10 @@ impl LatexEngine {
                 if already_rec {
                     DiffChainNode::Chain(result)
                 } else {
-                    self.expand_diff_symbol(
+                    self.expand_diff(
                         symbol_map,
+                        reach,
+                        stops,
                         model,
                         DiffChainNode::Chain(result),
                         error_node,
@@ -902,18 +1303,31 @@ impl LatexEngine {
                 }
             }
             DiffChainNode::UnWeightable(i, s) => {
-                //
+                if i != error_node
+                    && (stops.contains(&i)
+                        || Self::downstream(symbol_map, reach, i, error_node).len() > 1)
+                {
+                    let chain = DiffChainNode::Chain(vec![DiffChainNode::UnWeightable(i, s)]);
+                    return self.expand_diff(symbol_map, reach, stops, model, chain, error_node);
+                }
                 let mut result = Vec::new();
                 // println!("out length {}", node.outputs.len());
                 let sym_node = symbol_map[i].as_ref().unwrap();
 
                 if sym_node.outputs.len() != 0 {
-                    let into_node_idx = sym_node.outputs[0];
+                    let into_node_idx = Self::downstream(symbol_map, reach, i, error_node)[0];
                     result.push(self.diff_node(model, symbol_map, into_node_idx));
                 }
                 result.push(DiffChainNode::UnWeightable(i, s));
 
-                self.expand_diff_symbol(symbol_map, model, DiffChainNode::Chain(result), error_node)
+                self.expand_diff(
+                    symbol_map,
+                    reach,
+                    stops,
+                    model,
+                    DiffChainNode::Chain(result),
+                    error_node,
+                )
             }
             x @ _ => x,
         }
@@ -946,6 +1360,7 @@ impl LatexEngine {
         input_indexs: &Indexes,
         prev_proper_symbols: &Vec<String>,
         prev_size: usize,
+        defs: &mut Vec<(usize, String)>,
     ) -> String {
         let result = match *target {
             DiffChainNode::Sum(ref d, ref many) => match final_model_end {
@@ -983,6 +1398,7 @@ impl LatexEngine {
                         input_indexs,
                         prev_proper_symbols,
                         many.len(),
+                        defs,
                     );
                     let mut result = String::new();
                     // fit to shape
@@ -1012,6 +1428,71 @@ impl LatexEngine {
                     result
                 }
             },
+            DiffChainNode::Paths(ref paths, ref merge) => {
+                let terms: Vec<String> = paths
+                    .iter()
+                    .map(|p| {
+                        self.rec_backward(
+                            p,
+                            back_package,
+                            model,
+                            level,
+                            final_model_end,
+                            pre_chain.clone(),
+                            input_indexs,
+                            prev_proper_symbols,
+                            prev_size,
+                            defs,
+                        )
+                    })
+                    .collect();
+                let label = match merge {
+                    Some((_, s, _)) => format!("paths\\to {}", s),
+                    None => "paths".to_string(),
+                };
+                // gradient of join node is defined once and paths end at its symbol,
+                // definition replaces reference inside sum of path, free index is cn_{level}
+                if let Some((m, s, Some(tail))) = merge {
+                    if defs.iter().all(|(n, _)| n != m) {
+                        let many = Self::chain_dims(tail).unwrap_or(prev_size);
+                        let (_, p1_str) = self.symbol_library.get_p0p1(
+                            level + 2,
+                            input_indexs,
+                            s.clone(),
+                            prev_proper_symbols,
+                            many,
+                        );
+                        let value = self.rec_backward(
+                            tail,
+                            back_package,
+                            model,
+                            level + 1,
+                            final_model_end,
+                            Some(s.clone()),
+                            input_indexs,
+                            prev_proper_symbols,
+                            prev_size,
+                            defs,
+                        );
+                        let delta = only_inputs_symbol_parts(
+                            back_package[4].clone(),
+                            vec![delta_symbol(s), p1_str],
+                        );
+                        defs.push((*m, format!("{}={}", delta, value)));
+                    }
+                }
+                only_inputs_symbol_parts(back_package[6].clone(), vec![terms.join("+"), label])
+            }
+            DiffChainNode::Delta(_, ref s) => {
+                let (p0_str, _) = self.symbol_library.get_p0p1(
+                    level,
+                    input_indexs,
+                    s.clone(),
+                    prev_proper_symbols,
+                    prev_size,
+                );
+                only_inputs_symbol_parts(back_package[4].clone(), vec![delta_symbol(s), p0_str])
+            }
             DiffChainNode::Chain(ref d) => {
                 let d1_symbol = if let Some(x) = d.get(1) {
                     Self::get_symbol_if_func(x)
@@ -1051,9 +1532,15 @@ impl LatexEngine {
                             vec![to_insert, p1_str.clone()],
                         );
 
-                        let e_a = only_inputs_symbol_parts(
-                            back_package[0].clone(),
-                            vec![e_symbol, a_sym.clone()],
+                        let e_a = self.loss_head(
+                            only_inputs_symbol_parts(
+                                back_package[0].clone(),
+                                vec![e_symbol, a_sym.clone()],
+                            ),
+                            &final_model_end,
+                            back_package,
+                            &a_sym,
+                            &p0_str,
                         );
                         let a_p = only_inputs_symbol_parts(
                             back_package[0].clone(),
@@ -1091,9 +1578,15 @@ impl LatexEngine {
                             vec![to_insert, p1_str.clone()],
                         );
 
-                        let e_a = only_inputs_symbol_parts(
-                            back_package[0].clone(),
-                            vec![e_symbol, a_sym.clone()],
+                        let e_a = self.loss_head(
+                            only_inputs_symbol_parts(
+                                back_package[0].clone(),
+                                vec![e_symbol, a_sym.clone()],
+                            ),
+                            &final_model_end,
+                            back_package,
+                            &a_sym,
+                            &p0_str,
                         );
                         let a_b = only_inputs_symbol_parts(
                             back_package[0].clone(),
@@ -1118,6 +1611,7 @@ impl LatexEngine {
                             input_indexs,
                             prev_proper_symbols,
                             prev_size,
+                            defs,
                         );
 
                         if let Some(ref d2) = d2_symbol {
@@ -1181,6 +1675,36 @@ impl LatexEngine {
         result
     }
 
+    // replace error diff with derivative of loss
+    fn loss_head(
+        &self,
+        e_a: String,
+        final_model_end: &ErrorResultTo,
+        back_package: &Vec<(&str, Vec<(&str, &str)>)>,
+        a_sym: &str,
+        p0_str: &str,
+    ) -> String {
+        match (&self.loss, final_model_end) {
+            (Some(loss), ErrorResultTo::Total) => {
+                let t_sym = only_inputs_symbol_parts(
+                    back_package[4].clone(),
+                    vec!["t".to_string(), p0_str.to_string()],
+                );
+                loss.gen_diff(&self.symbol_library, a_sym.to_string(), t_sym)
+                    .map(|d| format!("({})", d))
+                    .unwrap_or(e_a)
+            }
+            _ => e_a,
+        }
+    }
+    // number of output dims summed by chain
+    fn chain_dims(target: &DiffChainNode) -> Option<usize> {
+        match target {
+            DiffChainNode::Sum(_, many) => Some(many.len()),
+            DiffChainNode::Paths(paths, _) => paths.first().and_then(Self::chain_dims),
+            _ => None,
+        }
+    }
     fn get_symbol_if_func(target: &DiffChainNode) -> Option<String> {
         match target {
             DiffChainNode::Weightable(_, s) => Some(s.clone()),
@@ -1208,6 +1732,8 @@ impl LatexEngine {
         let under_splits = symbol_split(underform.formul.as_str()).unwrap();
         let (_, _, sum_w) = self.symbol_library.get_symbol("_Sum_w").unwrap();
         let sum_w_splits = symbol_split(sum_w.formul.as_str()).unwrap();
+        let (_, _, paths) = self.symbol_library.get_symbol("_Paths").unwrap();
+        let paths_splits = symbol_split(paths.formul.as_str()).unwrap();
 
         let vv = vec![
             d_splits,
@@ -1216,9 +1742,11 @@ impl LatexEngine {
             c3_splits,
             under_splits,
             sum_w_splits,
+            paths_splits,
         ];
         let propers: Vec<String> = ["c", "h", "w", "b"].iter().map(|s| s.to_string()).collect();
-        self.rec_backward(
+        let mut defs = Vec::new();
+        let value = self.rec_backward(
             target,
             &vv,
             model,
@@ -1228,14 +1756,25 @@ impl LatexEngine {
             input_indexs,
             &propers,
             0,
-        )
+            &mut defs,
+        );
+        // gradients of join nodes after the formula, ex) ,\quad \delta^{(f_{3})}_{...}=...
+        defs.iter()
+            .fold(value, |acc, (_, d)| format!(r#"{},\quad {}"#, acc, d))
     }
 }
 // parsing result struct 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct LatexResult {
     pub symbol_map: Vec<Option<LatexNode>>,
     pub senario: Vec<usize>,
+    // loss definition ex) E_{(total,)}=...
+    #[serde(default)]
+    pub loss: Option<String>,
+    // parameters without backward formula, ex) used by unsupported op
+    #[serde(default)]
+    pub skipped: Vec<usize>,
 }
 
 impl LatexResult {
@@ -1244,7 +1783,57 @@ impl LatexResult {
         LatexResult {
             symbol_map: input.clone(),
             senario: Vec::new(),
+            loss: None,
+            skipped: Vec::new(),
+        }
+    }
+    // weight input of layer, second input if not named
+    pub(crate) fn weight_slot(&self, i: usize) -> usize {
+        self.symbol_map[i]
+            .as_ref()
+            .and_then(|node| {
+                node.inputs.iter().position(|i| {
+                    self.symbol_map[*i]
+                        .as_ref()
+                        .and_then(|s| s.extra_symbol.clone())
+                        .map(|s| s == "weight")
+                        .unwrap_or(false)
+                })
+            })
+            .unwrap_or(1)
+    }
+    // outputs of node which are on path
+    pub(crate) fn next_on_path(&self, i: usize, path: &[usize]) -> Vec<usize> {
+        let mut result = Vec::new();
+        if let Some(node) = self.symbol_map[i].as_ref() {
+            for o in node.outputs.iter() {
+                if path.contains(o) && !result.contains(o) {
+                    result.push(*o);
+                }
+            }
+        }
+        result
+    }
+    // (symbol, output shape) of each input of node
+    pub(crate) fn input_info(
+        &self,
+        i: usize,
+    ) -> Result<(Vec<String>, Vec<Vec<usize>>), std::io::Error> {
+        let mut inputs = Vec::new();
+        let mut input_shapes = Vec::new();
+        let node = self.symbol_map[i].as_ref().ok_or(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "not found index",
+        ))?;
+        for i in node.inputs.iter() {
+            let in_node = self.symbol_map[*i].as_ref().ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "not found input index",
+            ))?;
+            inputs.push(in_node.symbol.clone());
+            input_shapes.push(in_node.output_shape.clone());
         }
+        Ok((inputs, input_shapes))
     }
     pub fn get_node_formul(&self, i: usize) -> String {
         if let Some(ref x) = self.symbol_map[i] {
@@ -1275,7 +1864,8 @@ impl LatexResult {
         }
     }
     pub fn from_reader(reader: Vec<u8>) -> Result<Self, std::io::Error> {
-        let input_str = std::str::from_utf8(reader.as_slice()).unwrap();
+        let input_str = std::str::from_utf8(reader.as_slice())
+            .map_err(|e| std::io::Error::new(ErrorKind::InvalidInput, format!("{:?}", e)))?;
         serde_json::from_str(input_str)
             .map_err(|e| std::io::Error::new(ErrorKind::InvalidInput, format!("{:?}", e)))
     }
@@ -0,0 +1,130 @@
+use std::{
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    formula::{loss_symbols, Formula, Scope},
+    node_info::FormulNode,
+    parse_struct::{only_inputs_symbol_parts, symbol_split},
+    SymbolLibrary,
+};
+
+// loss function at the head of backward chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Loss {
+    Mse,
+    CrossEntropy,
+    BinaryCrossEntropy,
+    L1,
+    // ron template like (formul: "...", diff: Some("...")), #_0 is output and #_1 is target
+    Custom(String),
+}
+
+impl FromStr for Loss {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mse" => Ok(Loss::Mse),
+            "cross_entropy" | "ce" => Ok(Loss::CrossEntropy),
+            "binary_cross_entropy" | "bce" => Ok(Loss::BinaryCrossEntropy),
+            "l1" => Ok(Loss::L1),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown loss {}", s),
+            )),
+        }
+    }
+}
+
+impl Loss {
+    fn custom(template: &str) -> Result<FormulNode, Error> {
+        ron::from_str::<FormulNode>(template)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{:?}", e)))
+    }
+    fn template(&self, library: &SymbolLibrary) -> Result<FormulNode, Error> {
+        let name = match self {
+            Loss::Custom(s) => return Self::custom(s),
+            Loss::Mse => "Loss.Mse",
+            Loss::CrossEntropy => "Loss.CrossEntropy",
+            Loss::BinaryCrossEntropy => "Loss.BinaryCrossEntropy",
+            Loss::L1 => "Loss.L1",
+        };
+        library
+            .get_symbol(name)
+            .map(|(_, _, f)| f)
+            .ok_or(Error::new(ErrorKind::NotFound, "not found loss"))
+    }
+    fn insert(form: &str, output: String, target: String) -> Result<String, Error> {
+        let splits = symbol_split(form)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{:?}", e)))?;
+        Ok(only_inputs_symbol_parts(splits, vec![output, target]))
+    }
+    // loss formula with output and target symbol
+    pub fn gen_formul(
+        &self,
+        library: &SymbolLibrary,
+        output: String,
+        target: String,
+    ) -> Result<String, Error> {
+        let form = self.template(library)?;
+        Self::insert(form.formul.as_str(), output, target)
+    }
+    // derivative of loss with respect to output
+    pub fn gen_diff(
+        &self,
+        library: &SymbolLibrary,
+        output: String,
+        target: String,
+    ) -> Result<String, Error> {
+        let form = self.template(library)?;
+        let diff = form
+            .diff
+            .ok_or(Error::new(ErrorKind::NotFound, "loss has no diff"))?;
+        Self::insert(diff.as_str(), output, target)
+    }
+    // loss value of output and target
+    pub fn eval(&self, output: &[f64], target: &[f64]) -> Result<f64, Error> {
+        let n = output.len().max(1) as f64;
+        let pairs = output.iter().zip(target.iter());
+        match self {
+            Loss::Mse => Ok(pairs.map(|(y, t)| (y - t) * (y - t)).sum::<f64>() / n),
+            Loss::CrossEntropy => Ok(-pairs.map(|(y, t)| t * y.ln()).sum::<f64>()),
+            Loss::BinaryCrossEntropy => Ok(-pairs
+                .map(|(y, t)| t * y.ln() + (1.0 - t) * (1.0 - y).ln())
+                .sum::<f64>()
+                / n),
+            Loss::L1 => Ok(pairs.map(|(y, t)| (y - t).abs()).sum::<f64>() / n),
+            // sum over i in template runs over every output element
+            Loss::Custom(s) => {
+                let (y, t) = loss_symbols();
+                let formul = Self::insert(Self::custom(s)?.formul.as_str(), y, t)?;
+                Formula::parse(&formul)?.eval(&mut Scope::loss(output, target))
+            }
+        }
+    }
+}
+
+#[test]
+fn loss_test() {
+    let library = SymbolLibrary::new();
+    let diff = Loss::Mse
+        .gen_diff(&library, "y_{i}".to_string(), "t_{i}".to_string())
+        .unwrap();
+    assert_eq!(diff, r#"\frac{2}{N}(y_{i}-t_{i})"#);
+    let custom =
+        Loss::Custom(r#"(formul: "(#_0-#_1)^{4}", diff: Some("4(#_0-#_1)^{3}"))"#.to_string());
+    assert_eq!(
+        custom
+            .gen_formul(&library, "y".to_string(), "t".to_string())
+            .unwrap(),
+        "(y-t)^{4}"
+    );
+    assert!("hinge".parse::<Loss>().is_err());
+    assert_eq!(Loss::Mse.eval(&[1.0, 3.0], &[0.0, 1.0]).unwrap(), 2.5);
+    let custom = Loss::Custom(r#"(formul: "\\sum_{i}(#_0-#_1)^{4}", diff: None)"#.to_string());
+    assert_eq!(custom.eval(&[1.0, 3.0], &[0.0, 1.0]).unwrap(), 17.0);
+}
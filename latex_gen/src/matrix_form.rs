@@ -0,0 +1,194 @@
+use std::{
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
+
+use tract_onnx::{
+    prelude::*,
+    tract_hir::utils::{FormulKind, MathGen},
+};
+
+use crate::{verify::chain_nodes, DiffChainNode, LatexEngine, LatexResult};
+
+// form of backward formula
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackwardForm {
+    // chain rule for each element with index
+    Element,
+    // delta form for Gemm, MatMul and activation chains
+    Matrix,
+}
+
+impl Default for BackwardForm {
+    fn default() -> Self {
+        BackwardForm::Element
+    }
+}
+
+impl FromStr for BackwardForm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "element" => Ok(BackwardForm::Element),
+            "matrix" => Ok(BackwardForm::Matrix),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown backward form {}", s),
+            )),
+        }
+    }
+}
+
+enum Step {
+    // layer number
+    Linear(usize),
+    // activation symbol
+    Activation(String),
+}
+
+// ex) \odot {h_{1}}'(z^{(2)})
+fn activation_diff(acts: &[String], layer: usize) -> String {
+    let mut input = format!("z^{{({})}}", layer);
+    acts.iter()
+        .map(|a| {
+            let d = format!(r#"\odot {{{}}}'({})"#, a, input);
+            input = a.clone();
+            d
+        })
+        .collect()
+}
+
+impl LatexEngine {
+    // nodes from index to last point on every path of diff chain by senario order
+    pub(crate) fn chain_path(
+        &self,
+        symbol_result: &LatexResult,
+        model: &InferenceModel,
+        index: usize,
+        last_point: usize,
+    ) -> Result<Vec<usize>, Error> {
+        let sym_node = symbol_result.symbol_map[index]
+            .as_ref()
+            .ok_or(Error::new(ErrorKind::NotFound, "not found index"))?;
+        let start = DiffChainNode::Weightable(index, sym_node.symbol.clone());
+        let chain = self.expand_diff_symbol(&symbol_result.symbol_map, model, start, last_point);
+        let mut path = vec![index];
+        chain_nodes(&chain, &mut path);
+        path.sort_by_key(|n| symbol_result.senario.iter().position(|s| s == n));
+        Ok(path)
+    }
+    // \nabla_{W^{(l)}}E = \delta^{(l)}(a^{(l-1)})^{\top} with delta of each layer after l
+    pub fn gen_matrix_back(
+        &self,
+        math_opvec: &Vec<Box<dyn MathGen>>,
+        model: &InferenceModel,
+        symbol_result: &LatexResult,
+        n_indxs: (usize, usize),
+    ) -> Result<(String, String), Error> {
+        let (index, last_point) = n_indxs;
+        let unsupported = |n: usize| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "matrix form only supports Gemm, MatMul and activation chains, node {}",
+                    n
+                ),
+            )
+        };
+        let kind_of = |n: usize| {
+            symbol_result.symbol_map[n]
+                .as_ref()
+                .map(|s| math_opvec[n].get_symbol_type(s.extra_symbol.clone()))
+        };
+        // number linear layers by senario
+        let layers: Vec<usize> = symbol_result
+            .senario
+            .iter()
+            .cloned()
+            .filter(|n| kind_of(*n) == Some(FormulKind::Function))
+            .collect();
+        let layer_no = |n: usize| layers.iter().position(|x| *x == n).map(|p| p + 1);
+        let l = layer_no(index).ok_or(unsupported(index))?;
+
+        let path = self.chain_path(symbol_result, model, index, last_point)?;
+        // residual branch has several deltas on one node
+        if let Some(n) = path
+            .iter()
+            .find(|n| symbol_result.next_on_path(**n, &path).len() > 1)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("matrix form does not support branch at node {}", n),
+            ));
+        }
+
+        let mut steps = Vec::new();
+        for n in path.iter().filter(|n| **n != index) {
+            match kind_of(*n) {
+                Some(FormulKind::Function) => steps.push(Step::Linear(layer_no(*n).unwrap())),
+                Some(FormulKind::Activation) => steps.push(Step::Activation(
+                    symbol_result.symbol_map[*n]
+                        .as_ref()
+                        .unwrap()
+                        .symbol
+                        .clone(),
+                )),
+                // bias add and reshape keep delta
+                Some(FormulKind::Base)
+                | Some(FormulKind::Undefined)
+                | Some(FormulKind::Flatten) => {}
+                _ => return Err(unsupported(*n)),
+            }
+        }
+        // (layer, activations after layer)
+        let mut groups: Vec<(usize, Vec<String>)> = vec![(l, Vec::new())];
+        for s in steps.into_iter() {
+            match s {
+                Step::Linear(k) => groups.push((k, Vec::new())),
+                Step::Activation(a) => groups.last_mut().unwrap().1.push(a),
+            }
+        }
+
+        // gradient of error on last output
+        let (last_layer, _) = groups.last().unwrap().clone();
+        let last_output = format!("a^{{({})}}", last_layer);
+        let head = match self.loss {
+            Some(ref loss) => format!(
+                "({})",
+                loss.gen_diff(&self.symbol_library, last_output, "t".to_string())?
+            ),
+            None => format!(r#"\nabla_{{{}}}E"#, last_output),
+        };
+        let mut deltas = Vec::new();
+        for (i, (k, acts)) in groups.iter().enumerate() {
+            let upstream = match groups.get(i + 1) {
+                Some((next, _)) => format!(r#"(W^{{({0})}})^{{\top}}\delta^{{({0})}}"#, next),
+                None => head.clone(),
+            };
+            deltas.push(format!(
+                r#"\delta^{{({})}}={}{}"#,
+                k,
+                upstream,
+                activation_diff(acts, *k)
+            ));
+        }
+
+        let symbol = format!(r#"\nabla_{{W^{{({})}}}}E"#, l);
+        let mut value = format!(r#"\delta^{{({})}}(a^{{({})}})^{{\top}}"#, l, l - 1);
+        for d in deltas.iter() {
+            value += &format!(r#",\quad {}"#, d);
+        }
+        Ok((symbol, value))
+    }
+}
+
+#[test]
+fn activation_diff_test() {
+    let acts = vec!["h_{1}".to_string(), "h_{2}".to_string()];
+    assert_eq!(
+        activation_diff(&acts, 2),
+        r#"\odot {h_{1}}'(z^{(2)})\odot {h_{2}}'(h_{1})"#
+    );
+    assert_eq!(activation_diff(&[], 1), "");
+}
@@ -0,0 +1,298 @@
+use std::{
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
+
+use crate::{LatexEngine, LatexResult};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Optimizer {
+    Sgd,
+    Momentum,
+    RmsProp,
+    Adam,
+}
+
+impl FromStr for Optimizer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sgd" => Ok(Optimizer::Sgd),
+            "momentum" => Ok(Optimizer::Momentum),
+            "rmsprop" => Ok(Optimizer::RmsProp),
+            "adam" => Ok(Optimizer::Adam),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown optimizer {}", s),
+            )),
+        }
+    }
+}
+
+// optimizer with symbol or value of each hyper parameter, default symbol when not given
+#[derive(Debug, Clone)]
+pub struct OptimizerOption {
+    pub optimizer: Optimizer,
+    pub learning_rate: Option<String>,
+    pub momentum: Option<String>,
+    pub rho: Option<String>,
+    pub beta1: Option<String>,
+    pub beta2: Option<String>,
+    pub epsilon: Option<String>,
+}
+
+impl OptimizerOption {
+    pub fn new(optimizer: Optimizer) -> Self {
+        OptimizerOption {
+            optimizer,
+            learning_rate: None,
+            momentum: None,
+            rho: None,
+            beta1: None,
+            beta2: None,
+            epsilon: None,
+        }
+    }
+    // numeric hyper parameters in range, 0<=momentum,rho,beta<1 and epsilon>0, symbols are not checked
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |name: &str, v: &str, range: &str| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} {} is out of range, {}", name, v, range),
+            )
+        };
+        let decays = [
+            ("momentum", &self.momentum),
+            ("rho", &self.rho),
+            ("beta1", &self.beta1),
+            ("beta2", &self.beta2),
+        ];
+        for (name, value) in decays.iter() {
+            if let Some(v) = value {
+                match v.parse::<f64>() {
+                    Ok(x) if !(0.0..1.0).contains(&x) => return Err(invalid(name, v, "0<=x<1")),
+                    _ => {}
+                }
+            }
+        }
+        if let Some(ref v) = self.epsilon {
+            match v.parse::<f64>() {
+                Ok(x) if x.is_nan() || x <= 0.0 => return Err(invalid("epsilon", v, "x>0")),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    // update equations of parameter from its gradient
+    pub fn gen_update(&self, param: &str, grad: &str) -> Vec<String> {
+        let h = |v: &Option<String>, d: &str| v.clone().unwrap_or(d.to_string());
+        let lr = h(&self.learning_rate, r#"\eta"#);
+        let eps = h(&self.epsilon, r#"\epsilon"#);
+        let at = |s: &str, t: &str| format!("{{{}}}^{{({})}}", s, t);
+        let (now, next) = (at(param, "t"), at(param, "t+1"));
+        // state of parameter ex) m_{W_1}
+        let state = |s: &str, t: &str| at(&format!("{}_{{{}}}", s, param), t);
+        match self.optimizer {
+            Optimizer::Sgd => vec![format!("{}={}-{}{}", next, now, lr, grad)],
+            Optimizer::Momentum => {
+                let mu = h(&self.momentum, r#"\mu"#);
+                vec![
+                    format!(
+                        "{}={}{}-{}{}",
+                        state("v", "t+1"),
+                        mu,
+                        state("v", "t"),
+                        lr,
+                        grad
+                    ),
+                    format!("{}={}+{}", next, now, state("v", "t+1")),
+                ]
+            }
+            Optimizer::RmsProp => {
+                let rho = h(&self.rho, r#"\rho"#);
+                vec![
+                    format!(
+                        "{}={}{}+(1-{})({})^{{2}}",
+                        state("s", "t+1"),
+                        rho,
+                        state("s", "t"),
+                        rho,
+                        grad
+                    ),
+                    format!(
+                        r#"{}={}-\frac{{{}}}{{\sqrt{{{}}}+{}}}{}"#,
+                        next,
+                        now,
+                        lr,
+                        state("s", "t+1"),
+                        eps,
+                        grad
+                    ),
+                ]
+            }
+            Optimizer::Adam => {
+                let b1 = h(&self.beta1, r#"\beta_1"#);
+                let b2 = h(&self.beta2, r#"\beta_2"#);
+                let hat = |s: &str| at(&format!(r#"\hat{{{}}}_{{{}}}"#, s, param), "t+1");
+                vec![
+                    format!(
+                        "{}={}{}+(1-{}){}",
+                        state("m", "t+1"),
+                        b1,
+                        state("m", "t"),
+                        b1,
+                        grad
+                    ),
+                    format!(
+                        "{}={}{}+(1-{})({})^{{2}}",
+                        state("v", "t+1"),
+                        b2,
+                        state("v", "t"),
+                        b2,
+                        grad
+                    ),
+                    // bias correction
+                    format!(
+                        r#"{}=\frac{{{}}}{{1-({})^{{t+1}}}}"#,
+                        hat("m"),
+                        state("m", "t+1"),
+                        b1
+                    ),
+                    format!(
+                        r#"{}=\frac{{{}}}{{1-({})^{{t+1}}}}"#,
+                        hat("v"),
+                        state("v", "t+1"),
+                        b2
+                    ),
+                    format!(
+                        r#"{}={}-\frac{{{}}}{{\sqrt{{{}}}+{}}}{}"#,
+                        next,
+                        now,
+                        lr,
+                        hat("v"),
+                        eps,
+                        hat("m")
+                    ),
+                ]
+            }
+        }
+    }
+}
+
+impl LatexEngine {
+    // update equations of node from backward symbol, parameter of layer is its weight
+    pub fn gen_node_update(
+        &self,
+        symbol_result: &LatexResult,
+        i: usize,
+        backward_symbol: &str,
+    ) -> Option<Vec<String>> {
+        let option = self.optimizer.as_ref()?;
+        let node = symbol_result.symbol_map[i].as_ref()?;
+        if backward_symbol.is_empty() || node.op_name == "Source" {
+            return None;
+        }
+        let param = if symbol_result.senario.contains(&i) {
+            let w = node.inputs.get(symbol_result.weight_slot(i))?;
+            symbol_result.symbol_map[*w].as_ref()?.symbol.clone()
+        } else {
+            node.symbol.clone()
+        };
+        Some(option.gen_update(&param, backward_symbol))
+    }
+    // update equations of every node which has backward
+    pub fn gen_updates(&self, symbol_result: &mut LatexResult) {
+        let updates: Vec<(usize, Vec<String>)> = symbol_result
+            .symbol_map
+            .iter()
+            .enumerate()
+            .filter_map(|(i, n)| {
+                let n = n.as_ref()?;
+                self.gen_node_update(symbol_result, i, &n.backward_symbol)
+                    .map(|u| (i, u))
+            })
+            .collect();
+        for (i, u) in updates.into_iter() {
+            if let Some(f) = symbol_result.symbol_map[i].as_mut() {
+                f.update = u;
+            }
+        }
+    }
+}
+
+#[test]
+fn update_test() {
+    let mut option = OptimizerOption::new(Optimizer::Sgd);
+    option.learning_rate = Some("0.01".to_string());
+    assert_eq!(
+        option.gen_update("W_1", "g"),
+        vec!["{W_1}^{(t+1)}={W_1}^{(t)}-0.01g".to_string()]
+    );
+    option.optimizer = Optimizer::Adam;
+    let adam = option.gen_update("W_1", "g");
+    assert_eq!(adam.len(), 5);
+    assert_eq!(
+        adam[2],
+        r#"{\hat{m}_{W_1}}^{(t+1)}=\frac{{m_{W_1}}^{(t+1)}}{1-(\beta_1)^{t+1}}"#
+    );
+}
+
+#[test]
+fn validate_test() {
+    let mut option = OptimizerOption::new(Optimizer::Adam);
+    option.beta1 = Some("0.9".to_string());
+    option.beta2 = Some(r#"\beta_2"#.to_string());
+    option.epsilon = Some("1e-8".to_string());
+    assert!(option.validate().is_ok());
+    option.beta2 = Some("1".to_string());
+    assert!(option.validate().is_err());
+    option.beta2 = None;
+    option.epsilon = Some("0".to_string());
+    assert!(option.validate().is_err());
+    option.epsilon = None;
+    option.momentum = Some("-0.1".to_string());
+    assert!(option.validate().is_err());
+}
+
+#[test]
+fn listener_update_test() {
+    use std::sync::{Arc, Mutex};
+    let bytes = crate::test_model::two_layer();
+    let mut engine = LatexEngine::new();
+    let model = engine
+        .model_from_file(&mut std::io::Cursor::new(bytes.clone()))
+        .unwrap();
+    let mut result = engine
+        .parse_from_file(&mut std::io::Cursor::new(bytes), Some(4))
+        .unwrap();
+    let math_ops = LatexEngine::math_op_vecs(&model);
+    engine.optimizer = Some(OptimizerOption::new(Optimizer::Sgd));
+    // nodes as listener sees them
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let shared = seen.clone();
+    engine.control = Some(crate::ParseControl::with_listener(move |node, _| {
+        shared
+            .lock()
+            .unwrap()
+            .push((node.op_name.clone(), node.update.clone()));
+        true
+    }));
+    engine
+        .gen_back_model(
+            &mut result,
+            &model,
+            &math_ops,
+            &crate::Indexes::new(vec![0, 0], vec![0, 0]),
+            Some(4),
+        )
+        .unwrap();
+    let seen = seen.lock().unwrap();
+    assert!(!seen.is_empty());
+    // model input has gradient but no update
+    assert!(
+        seen.iter().all(|(op, u)| op == "Source" || !u.is_empty()),
+        "{:?}",
+        seen
+    );
+}
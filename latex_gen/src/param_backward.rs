@@ -0,0 +1,155 @@
+use std::io::{Error, ErrorKind};
+
+use tract_onnx::{
+    prelude::*,
+    tract_hir::utils::{element_symbol, to_strings, MathGen},
+};
+
+use crate::{window_backward::delta_symbol, LatexEngine, LatexResult};
+
+impl LatexEngine {
+    // gradient of bias, normalization scale and shift, prelu slope or model input
+    // element index is symbolic when param_idx is empty
+    pub fn gen_param_back(
+        &self,
+        math_opvec: &Vec<Box<dyn MathGen>>,
+        model: &InferenceModel,
+        symbol_result: &LatexResult,
+        n_indxs: (usize, usize),
+        param_idx: &[usize],
+    ) -> Result<(String, String), Error> {
+        let (param, last_point) = n_indxs;
+        let param_node = symbol_result.symbol_map[param]
+            .as_ref()
+            .ok_or(Error::new(ErrorKind::NotFound, "not found index"))?;
+        // every layer which uses parameter, gradients of them are summed
+        let consumers: Vec<usize> = symbol_result
+            .senario
+            .iter()
+            .cloned()
+            .filter(|n| {
+                symbol_result.symbol_map[*n]
+                    .as_ref()
+                    .map(|s| s.inputs.contains(&param))
+                    .unwrap_or(false)
+            })
+            .collect();
+        let first = *consumers.first().ok_or(Error::new(
+            ErrorKind::NotFound,
+            "parameter is not used by any layer",
+        ))?;
+        let mut index = if param_idx.is_empty() {
+            None
+        } else {
+            Some(to_strings(param_idx))
+        };
+        let mut terms = Vec::new();
+        for consumer in consumers.iter() {
+            let c_node = symbol_result.symbol_map[*consumer].as_ref().unwrap();
+            let slot = c_node.inputs.iter().position(|i| *i == param).unwrap();
+            let (inputs, input_shapes) = symbol_result.input_info(*consumer)?;
+            let (vars, formula) = math_opvec[*consumer]
+                .gen_input_grad(
+                    delta_symbol(&c_node.symbol),
+                    inputs,
+                    input_shapes,
+                    slot,
+                    index.clone(),
+                )
+                .ok_or(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "gradient of input {} is not supported for {}",
+                        slot, c_node.op_name
+                    ),
+                ))?;
+            // later consumers use index of first one
+            if index.is_none() {
+                index = Some(vars);
+            }
+            terms.push(
+                formula
+                    + &self.delta_chain(math_opvec, model, symbol_result, *consumer, last_point)?,
+            );
+        }
+        let e_symbol = self
+            .symbol_library
+            .gen_error_symbol(vec!["total".to_string(), "".to_string()]);
+        let index_vars = index.unwrap_or_default();
+        let symbol = math_opvec[first]
+            .gen_backward(e_symbol, element_symbol(&param_node.symbol, &index_vars));
+        let value = terms.join("+");
+        Ok((symbol, value))
+    }
+}
+
+#[test]
+fn shared_bias_test() {
+    let bytes = crate::test_model::shared_bias();
+    let mut engine = LatexEngine::new();
+    let model = engine
+        .model_from_file(&mut std::io::Cursor::new(bytes.clone()))
+        .unwrap();
+    let mut result = engine
+        .parse_from_file(&mut std::io::Cursor::new(bytes), Some(4))
+        .unwrap();
+    let math_ops = LatexEngine::math_op_vecs(&model);
+    let bias = result
+        .symbol_map
+        .iter()
+        .flatten()
+        .find(|n| n.name == "b")
+        .map(|n| n.index)
+        .unwrap();
+    let last = *result.senario.last().unwrap();
+    let (_, value) = engine
+        .gen_param_back(&math_ops, &model, &result, (bias, last), &[])
+        .unwrap();
+    // gradient through both layers
+    for node in result.symbol_map.iter().flatten() {
+        if node.op_name == "Gemm" {
+            assert!(value.contains(&delta_symbol(&node.symbol)), "{}", value);
+        }
+    }
+    engine
+        .gen_back_model(
+            &mut result,
+            &model,
+            &math_ops,
+            &crate::Indexes::new(vec![0, 0], vec![]),
+            Some(4),
+        )
+        .unwrap();
+    assert!(!result.skipped.contains(&bias));
+}
+
+#[test]
+fn residual_param_test() {
+    let bytes = crate::test_model::residual(2);
+    let mut engine = LatexEngine::new();
+    let model = engine
+        .model_from_file(&mut std::io::Cursor::new(bytes.clone()))
+        .unwrap();
+    let result = engine
+        .parse_from_file(&mut std::io::Cursor::new(bytes), Some(4))
+        .unwrap();
+    let math_ops = LatexEngine::math_op_vecs(&model);
+    let bias = result
+        .symbol_map
+        .iter()
+        .flatten()
+        .find(|n| n.name == "b")
+        .map(|n| n.index)
+        .unwrap();
+    let last = *result.senario.last().unwrap();
+    let (_, value) = engine
+        .gen_param_back(&math_ops, &model, &result, (bias, last), &[])
+        .unwrap();
+    // gradient of each add node is named by its own symbol
+    assert!(!value.contains("Undefined"), "{}", value);
+    for node in result.symbol_map.iter().flatten() {
+        if node.op_name == "Add" {
+            assert!(value.contains(&delta_symbol(&node.symbol)), "{}", value);
+        }
+    }
+}
@@ -84,6 +84,7 @@ pub fn insert_symbol_parts(
 // parse debug section
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum DebugValue {
     Str(String),
     Boolean(bool),
@@ -0,0 +1,207 @@
+use std::{
+    io::{Error, ErrorKind, Read},
+    path::Path,
+    time::Instant,
+};
+
+use tract_onnx::{prelude::*, tract_hir::utils::MathGen};
+
+use crate::{malformed, InferencePlan, JacobianForm, LatexEngine, LatexResult, ParseMode};
+
+// parsed model kept to expand or collapse inputs of one node at a time
+pub struct ExpandSession {
+    engine: LatexEngine,
+    model: InferenceModel,
+    math_ops: Vec<Box<dyn MathGen>>,
+    pub result: LatexResult,
+    // expansion level of each input of each node, 0 is input symbol
+    levels: Vec<Vec<usize>>,
+    // levels needed to reach model inputs and constants from node
+    max_levels: Vec<usize>,
+}
+
+impl LatexEngine {
+    // start expansion session from file, levels of inputs start from parse mode
+    pub fn session_from_file(
+        self,
+        file: &mut dyn Read,
+        mode: ParseMode,
+    ) -> TractResult<ExpandSession> {
+        let plan = self
+            .engine
+            .model_for_read(file)
+            .map_err(malformed)?
+            .into_runnable()?;
+        self.start_session(&plan, mode)
+    }
+    // start expansion session from path
+    pub fn session_from_path<P: AsRef<Path>>(
+        self,
+        path: P,
+        mode: ParseMode,
+    ) -> TractResult<ExpandSession> {
+        let plan = self
+            .engine
+            .model_for_path(path)
+            .map_err(malformed)?
+            .into_runnable()?;
+        self.start_session(&plan, mode)
+    }
+    fn start_session(
+        mut self,
+        plan: &InferencePlan,
+        mode: ParseMode,
+    ) -> TractResult<ExpandSession> {
+        let depth = match mode {
+            ParseMode::Brief => Some(1),
+            ParseMode::Full(many) => many,
+        };
+        let result = self.start_parse(plan, mode)?;
+        // rec_node reads symbols of engine
+        self.symbol_map = result.symbol_map.clone();
+        let model = plan.model().clone();
+        let mut max_levels = vec![0; model.nodes().len()];
+        for n in plan.order.iter() {
+            max_levels[*n] = model
+                .node(*n)
+                .inputs
+                .iter()
+                .map(|i| max_levels[i.node] + 1)
+                .max()
+                .unwrap_or(0);
+        }
+        let levels = model
+            .nodes()
+            .iter()
+            .map(|node| {
+                node.inputs
+                    .iter()
+                    .map(|i| match depth {
+                        Some(d) => d.saturating_sub(1).min(max_levels[i.node]),
+                        None => max_levels[i.node],
+                    })
+                    .collect()
+            })
+            .collect();
+        let math_ops = Self::math_op_vecs(&model);
+        Ok(ExpandSession {
+            engine: self,
+            model,
+            math_ops,
+            result,
+            levels,
+            max_levels,
+        })
+    }
+}
+
+impl ExpandSession {
+    pub fn model(&self) -> &InferenceModel {
+        &self.model
+    }
+    pub fn math_ops(&self) -> &Vec<Box<dyn MathGen>> {
+        &self.math_ops
+    }
+    // expansion level of each input of node
+    pub fn levels(&self, node: usize) -> Option<&[usize]> {
+        self.levels.get(node).map(|l| l.as_slice())
+    }
+    // jacobian of output node with respect to wrt node of kept model, return(symbol,value)
+    pub fn jacobian(
+        &self,
+        output: usize,
+        wrt: usize,
+        form: &JacobianForm,
+    ) -> Result<(String, String), Error> {
+        self.engine.gen_jacobian(
+            &self.math_ops,
+            &self.model,
+            &self.result,
+            (output, wrt),
+            form,
+        )
+    }
+    fn check(&self, node: usize, slot: usize) -> Result<usize, Error> {
+        let level = self
+            .levels
+            .get(node)
+            .and_then(|l| l.get(slot))
+            .ok_or(Error::new(
+                ErrorKind::NotFound,
+                format!("node {} has no input {}", node, slot),
+            ))?;
+        Ok(*level)
+    }
+    // expand input of node by one level, return new forward formula of node
+    pub fn expand(&mut self, node: usize, slot: usize) -> Result<String, Error> {
+        let level = self.check(node, slot)?;
+        let input = self.model.node(node).inputs[slot].node;
+        if level >= self.max_levels[input] {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("input {} of node {} is fully expanded", slot, node),
+            ));
+        }
+        self.levels[node][slot] = level + 1;
+        self.update(node)
+    }
+    // collapse input of node by one level, return new forward formula of node
+    pub fn collapse(&mut self, node: usize, slot: usize) -> Result<String, Error> {
+        let level = self.check(node, slot)?;
+        if level == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("input {} of node {} is already a symbol", slot, node),
+            ));
+        }
+        self.levels[node][slot] = level - 1;
+        self.update(node)
+    }
+    // regenerate forward formula of node with current levels
+    fn update(&mut self, node: usize) -> Result<String, Error> {
+        let timer = Instant::now();
+        let inf_node = self.model.node(node);
+        let ins = inf_node
+            .inputs
+            .iter()
+            .zip(self.levels[node].iter())
+            .map(|(i, l)| {
+                self.engine
+                    .rec_node(self.model.node(i.node), &self.model, Some(*l), &timer)
+            })
+            .collect();
+        let math_op = self.engine.math_op_vec[node]
+            .as_ref()
+            .ok_or(Error::new(ErrorKind::NotFound, "not found math op"))?;
+        let sym_node = self.result.symbol_map[node]
+            .as_mut()
+            .ok_or(Error::new(ErrorKind::NotFound, "not found index"))?;
+        sym_node.forward_value = math_op.gen_forward_value(
+            ins,
+            sym_node.input_shape_ref.clone(),
+            Some(sym_node.output_shape.clone()),
+        );
+        Ok(sym_node.forward_value.clone())
+    }
+}
+
+#[test]
+fn session_two_layer_test() {
+    let mut session = LatexEngine::new()
+        .session_from_file(
+            &mut std::io::Cursor::new(crate::test_model::two_layer()),
+            ParseMode::Brief,
+        )
+        .unwrap();
+    let last = *session.result.senario.last().unwrap();
+    let brief = session.result.symbol_map[last]
+        .as_ref()
+        .unwrap()
+        .forward_value
+        .clone();
+    let expanded = session.expand(last, 0).unwrap();
+    assert_ne!(expanded, brief);
+    assert_eq!(session.levels(last).unwrap()[0], 1);
+    assert_eq!(session.collapse(last, 0).unwrap(), brief);
+    assert!(session.collapse(last, 0).is_err());
+}
@@ -0,0 +1,137 @@
+// small onnx models built in code for tests
+use prost::Message;
+use tract_onnx::pb::{
+    tensor_proto::DataType,
+    tensor_shape_proto::{dimension, Dimension},
+    type_proto, GraphProto, ModelProto, NodeProto, OperatorSetIdProto, TensorProto,
+    TensorShapeProto, TypeProto, ValueInfoProto,
+};
+
+fn float_tensor(name: &str, dims: &[i64], values: Vec<f32>) -> TensorProto {
+    TensorProto {
+        name: name.to_string(),
+        dims: dims.to_vec(),
+        data_type: DataType::Float as i32,
+        float_data: values,
+        ..TensorProto::default()
+    }
+}
+
+fn value_info(name: &str, dims: &[i64]) -> ValueInfoProto {
+    let dim = dims
+        .iter()
+        .map(|d| Dimension {
+            value: Some(dimension::Value::DimValue(*d)),
+            ..Dimension::default()
+        })
+        .collect();
+    ValueInfoProto {
+        name: name.to_string(),
+        r#type: Some(TypeProto {
+            value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                elem_type: DataType::Float as i32,
+                shape: Some(TensorShapeProto { dim }),
+            })),
+            ..TypeProto::default()
+        }),
+        ..ValueInfoProto::default()
+    }
+}
+
+fn node(op: &str, name: &str, inputs: &[&str], output: &str) -> NodeProto {
+    NodeProto {
+        op_type: op.to_string(),
+        name: name.to_string(),
+        input: inputs.iter().map(|s| s.to_string()).collect(),
+        output: vec![output.to_string()],
+        ..NodeProto::default()
+    }
+}
+
+// deterministic weights in [-1, 1)
+fn weights(len: usize, seed: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| ((i * 7 + seed * 13) % 17) as f32 / 8.5 - 1.0)
+        .collect()
+}
+
+fn encode(graph: GraphProto) -> Vec<u8> {
+    let model = ModelProto {
+        ir_version: 7,
+        opset_import: vec![OperatorSetIdProto {
+            domain: String::new(),
+            version: 13,
+        }],
+        graph: Some(graph),
+        ..ModelProto::default()
+    };
+    let mut buf = Vec::new();
+    model.encode(&mut buf).unwrap();
+    buf
+}
+
+// x[1,3] -> Gemm -> Sigmoid -> Gemm -> y[1,2]
+pub fn two_layer() -> Vec<u8> {
+    encode(GraphProto {
+        name: "two_layer".to_string(),
+        node: vec![
+            node("Gemm", "fc1", &["x", "w1", "b1"], "h"),
+            node("Sigmoid", "act", &["h"], "a"),
+            node("Gemm", "fc2", &["a", "w2", "b2"], "y"),
+        ],
+        initializer: vec![
+            float_tensor("w1", &[3, 4], weights(12, 1)),
+            float_tensor("b1", &[4], weights(4, 2)),
+            float_tensor("w2", &[4, 2], weights(8, 3)),
+            float_tensor("b2", &[2], weights(2, 4)),
+        ],
+        input: vec![value_info("x", &[1, 3])],
+        output: vec![value_info("y", &[1, 2])],
+        ..GraphProto::default()
+    })
+}
+
+// x[1,3] -> Gemm -> Sigmoid -> Gemm -> y[1,3], both Gemm use bias b[3]
+pub fn shared_bias() -> Vec<u8> {
+    encode(GraphProto {
+        name: "shared_bias".to_string(),
+        node: vec![
+            node("Gemm", "fc1", &["x", "w1", "b"], "h"),
+            node("Sigmoid", "act", &["h"], "a"),
+            node("Gemm", "fc2", &["a", "w2", "b"], "y"),
+        ],
+        initializer: vec![
+            float_tensor("w1", &[3, 3], weights(9, 1)),
+            float_tensor("b", &[3], weights(3, 2)),
+            float_tensor("w2", &[3, 3], weights(9, 3)),
+        ],
+        input: vec![value_info("x", &[1, 3])],
+        output: vec![value_info("y", &[1, 3])],
+        ..GraphProto::default()
+    })
+}
+
+// x[1,3] -> Gemm, blocks of r_{k+1} = r_k + Sigmoid(r_k), Gemm -> y[1,2]
+pub fn residual(blocks: usize) -> Vec<u8> {
+    let mut nodes = vec![node("Gemm", "fc", &["x", "w", "b"], "r0")];
+    for k in 0..blocks {
+        let (r, a, next) = (format!("r{}", k), format!("a{}", k), format!("r{}", k + 1));
+        nodes.push(node("Sigmoid", &format!("act{}", k), &[&r], &a));
+        nodes.push(node("Add", &format!("add{}", k), &[&r, &a], &next));
+    }
+    let last = format!("r{}", blocks);
+    nodes.push(node("Gemm", "out", &[&last, "w2", "b2"], "y"));
+    encode(GraphProto {
+        name: "residual".to_string(),
+        node: nodes,
+        initializer: vec![
+            float_tensor("w", &[3, 3], weights(9, 1)),
+            float_tensor("b", &[3], weights(3, 2)),
+            float_tensor("w2", &[3, 2], weights(6, 3)),
+            float_tensor("b2", &[2], weights(2, 4)),
+        ],
+        input: vec![value_info("x", &[1, 3])],
+        output: vec![value_info("y", &[1, 2])],
+        ..GraphProto::default()
+    })
+}
@@ -0,0 +1,324 @@
+use std::io::{Error, ErrorKind};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tract_onnx::{
+    prelude::*,
+    tract_hir::{
+        infer::GenericFactoid,
+        internal::SessionState,
+        utils::{element_symbol, to_strings},
+    },
+};
+
+use crate::{
+    eval,
+    value::{render_matrix, tensor_values},
+    InferenceNode, InferencePlan, LatexEngine, LatexResult, ValueStats,
+};
+
+// input tensor of trace mode
+#[derive(Debug, Clone)]
+pub enum TraceInput {
+    Given(Vec<Tensor>),
+    // random values in [0, 1) from seed
+    Random(u64),
+}
+
+// output value of node from trace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TraceValue {
+    pub stats: ValueStats,
+    // matrix when output is small
+    pub value: Option<String>,
+    // first element with input values substituted
+    // ex) {f_{1}}_{(0,0)}={w_{0}}_{(0,0)}\cdot {x}_{(0,0)}=0.5\cdot 1.462=0.731
+    pub example: String,
+}
+
+// sums with more terms stay symbolic in example
+const EXAMPLE_EXPAND: usize = 8;
+
+// replace element symbols with numeric index by the traced value
+// ex) {w_{0}}_{(0,1)} -> 0.500
+pub(crate) fn substitute_values(
+    formula: &str,
+    symbols: &[(String, Arc<Tensor>)],
+    precision: usize,
+) -> String {
+    let mut result = formula.to_string();
+    for (symbol, tensor) in symbols.iter() {
+        let head = format!("{{{}}}_{{(", symbol);
+        let values = match tensor_values(tensor) {
+            Some(v) => v,
+            None => continue,
+        };
+        let mut out = String::new();
+        let mut rest = result.as_str();
+        while let Some(pos) = rest.find(&head) {
+            out.push_str(&rest[..pos]);
+            let after = &rest[pos + head.len()..];
+            let value = after.find(")}").and_then(|end| {
+                let coord = after[..end]
+                    .split(',')
+                    .map(|x| x.trim().parse::<usize>().ok())
+                    .collect::<Option<Vec<usize>>>()?;
+                let index = flat_offset(tensor.shape(), &coord)?;
+                values.get(index).map(|v| (end, *v))
+            });
+            match value {
+                Some((end, v)) if v < 0.0 => {
+                    out.push_str(&format!("({:.*})", precision, v));
+                    rest = &after[end + 2..];
+                }
+                Some((end, v)) => {
+                    out.push_str(&format!("{:.*}", precision, v));
+                    rest = &after[end + 2..];
+                }
+                None => {
+                    out.push_str(&head);
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        result = out;
+    }
+    result
+}
+
+// row major offset, None when coordinate is out of shape
+fn flat_offset(shape: &[usize], coord: &[usize]) -> Option<usize> {
+    if shape.len() != coord.len() || coord.iter().zip(shape.iter()).any(|(c, s)| c >= s) {
+        return None;
+    }
+    Some(
+        coord
+            .iter()
+            .zip(shape.iter())
+            .fold(0, |acc, (c, s)| acc * s + c),
+    )
+}
+
+// evaluate one node
+pub(crate) fn eval_node(
+    session_state: &mut SessionState,
+    node: &InferenceNode,
+    inputs: TVec<Arc<Tensor>>,
+) -> TractResult<TVec<Arc<Tensor>>> {
+    let mut state = node.op().state(session_state, node.id)?;
+    eval(
+        session_state,
+        state.as_mut().map(|s| s.as_mut()),
+        node,
+        inputs,
+    )
+}
+
+// evaluate nodes by order, return outputs of each node
+pub(crate) fn eval_values(
+    model: &InferenceModel,
+    order: &[usize],
+    inputs: &[Tensor],
+) -> TractResult<Vec<Option<TVec<Arc<Tensor>>>>> {
+    let input_outlets = model.input_outlets()?.to_vec();
+    let mut session_state = SessionState::default();
+    let mut values: Vec<Option<TVec<Arc<Tensor>>>> = vec![None; model.nodes().len()];
+    for n in order.iter() {
+        let node = model.node(*n);
+        let outputs = if let Some(pos) = input_outlets.iter().position(|o| o.node == *n) {
+            let t = inputs.get(pos).cloned().ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                format!("input {} is not given", pos),
+            ))?;
+            tvec!(Arc::new(t))
+        } else {
+            let node_inputs = node
+                .inputs
+                .iter()
+                .map(|o| {
+                    values[o.node]
+                        .as_ref()
+                        .map(|v| v[o.slot].clone())
+                        .ok_or(Error::new(
+                            ErrorKind::NotFound,
+                            format!("node {} is not evaluated", o.node),
+                        ))
+                })
+                .collect::<Result<TVec<_>, Error>>()?;
+            eval_node(&mut session_state, node, node_inputs)?
+        };
+        values[*n] = Some(outputs);
+    }
+    Ok(values)
+}
+
+// outputs of until node with output of changed node replaced,
+// only nodes depending on changed node are evaluated again and others are taken from cached
+pub(crate) fn eval_changed(
+    model: &InferenceModel,
+    order: &[usize],
+    cached: &[Option<TVec<Arc<Tensor>>>],
+    changed: usize,
+    tensor: Tensor,
+    until: usize,
+) -> TractResult<TVec<Arc<Tensor>>> {
+    let not_found =
+        |n: usize| Error::new(ErrorKind::NotFound, format!("node {} is not evaluated", n));
+    let mut session_state = SessionState::default();
+    let mut values = cached.to_vec();
+    let mut dirty = vec![false; model.nodes().len()];
+    values[changed] = Some(tvec!(Arc::new(tensor)));
+    dirty[changed] = true;
+    let start = order.iter().position(|n| *n == changed).unwrap_or(0);
+    for n in order[start..].iter() {
+        let node = model.node(*n);
+        if !dirty[*n] && node.inputs.iter().any(|o| dirty[o.node]) {
+            let node_inputs = node
+                .inputs
+                .iter()
+                .map(|o| {
+                    values[o.node]
+                        .as_ref()
+                        .map(|v| v[o.slot].clone())
+                        .ok_or_else(|| not_found(o.node))
+                })
+                .collect::<Result<TVec<_>, Error>>()?;
+            values[*n] = Some(eval_node(&mut session_state, node, node_inputs)?);
+            dirty[*n] = true;
+        }
+        if *n == until {
+            break;
+        }
+    }
+    values[until].clone().ok_or_else(|| not_found(until).into())
+}
+
+impl LatexEngine {
+    // random input for each model input
+    fn random_inputs(model: &InferenceModel, seed: u64) -> TractResult<Vec<Tensor>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut result = Vec::new();
+        for outlet in model.input_outlets()?.iter() {
+            let fact = model.outlet_fact(*outlet)?;
+            let shape: Vec<usize> = fact
+                .shape
+                .dims()
+                .map(|s| match s {
+                    GenericFactoid::Only(x) => x.to_i64().ok().map(|v| v as usize),
+                    GenericFactoid::Any => None,
+                })
+                .collect::<Option<Vec<usize>>>()
+                .ok_or(Error::new(
+                    ErrorKind::InvalidInput,
+                    "input shape is not fixed, give input shape",
+                ))?;
+            let total_elements: usize = shape.iter().product();
+            let vals: Vec<f32> = (0..total_elements).map(|_| rng.gen::<f32>()).collect();
+            result.push(tract_ndarray::ArrayD::from_shape_vec(shape, vals)?.into_tensor());
+        }
+        Ok(result)
+    }
+    pub(crate) fn trace_inputs(
+        model: &InferenceModel,
+        input: &TraceInput,
+    ) -> TractResult<Vec<Tensor>> {
+        match input {
+            TraceInput::Given(v) => Ok(v.clone()),
+            TraceInput::Random(seed) => Self::random_inputs(model, *seed),
+        }
+    }
+    // run plan with input and attach output value of each node
+    pub fn trace_plan(
+        &self,
+        plan: &InferencePlan,
+        symbol_result: &mut LatexResult,
+        input: &TraceInput,
+    ) -> TractResult<()> {
+        let model = plan.model();
+        let inputs = Self::trace_inputs(model, input)?;
+        let values = eval_values(model, &plan.order, &inputs)?;
+
+        let option = self.value_option.clone().unwrap_or_default();
+        let math_ops = Self::math_op_vecs(model);
+        let symbols: Vec<(String, Arc<Tensor>)> = symbol_result
+            .symbol_map
+            .iter()
+            .zip(values.iter())
+            .filter_map(|(s, v)| match (s, v) {
+                (Some(s), Some(v)) if v.len() == 1 => Some((s.symbol.clone(), v[0].clone())),
+                _ => None,
+            })
+            .collect();
+        let mut traces = Vec::new();
+        for (i, v) in values.iter().enumerate() {
+            let (sym_node, output) = match (&symbol_result.symbol_map[i], v) {
+                (Some(s), Some(o)) => (s, o[0].clone()),
+                _ => continue,
+            };
+            let vals = match tensor_values(&output) {
+                Some(x) => x,
+                None => continue,
+            };
+            let value = if vals.len() <= option.max_elements {
+                Some(render_matrix(output.shape(), &vals, &option))
+            } else {
+                None
+            };
+            let coord = vec![0; output.shape().len()];
+            let first = vals.get(0).map(|x| format!("{:.*}", option.precision, x));
+            let example =
+                match self.gen_element(&math_ops, symbol_result, i, &coord, Some(EXAMPLE_EXPAND)) {
+                    Ok(f) => {
+                        // substitute right side only, left side is the element itself
+                        let rhs = f.splitn(2, '=').nth(1).unwrap_or_default();
+                        let values = substitute_values(rhs, &symbols, option.precision);
+                        if values == rhs {
+                            format!("{}={}", f, first.unwrap_or_default())
+                        } else {
+                            format!("{}={}={}", f, values, first.unwrap_or_default())
+                        }
+                    }
+                    Err(_) => format!(
+                        "{}={}",
+                        element_symbol(&sym_node.symbol, &to_strings(&coord)),
+                        first.unwrap_or_default()
+                    ),
+                };
+            traces.push((
+                i,
+                TraceValue {
+                    stats: ValueStats::from_values(&vals),
+                    value,
+                    example,
+                },
+            ));
+        }
+        for (i, t) in traces.into_iter() {
+            if let Some(f) = symbol_result.symbol_map[i].as_mut() {
+                f.trace = Some(t);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn substitute_values_test() {
+    let w = Arc::new(tensor2(&[[0.5f32, -1.0], [2.0, 3.0]]));
+    let symbols = vec![("w_{0}".to_string(), w)];
+    assert_eq!(
+        substitute_values(
+            r#"{w_{0}}_{(0,0)}\cdot {w_{0}}_{(0,1)}+{w_{0}}_{(i,1)}+{x}_{(1,1)}"#,
+            &symbols,
+            2
+        ),
+        r#"0.50\cdot (-1.00)+{w_{0}}_{(i,1)}+{x}_{(1,1)}"#
+    );
+    // out of shape stays symbol
+    assert_eq!(
+        substitute_values("{w_{0}}_{(2,0)}", &symbols, 2),
+        "{w_{0}}_{(2,0)}"
+    );
+}
@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use tract_onnx::prelude::*;
+
+// option for rendering const values
+#[derive(Debug, Clone)]
+pub struct ValueOption {
+    // digits after decimal point
+    pub precision: usize,
+    // rows and cols shown before \cdots
+    pub max_rows: usize,
+    pub max_cols: usize,
+    // bigger tensors only get statistics
+    pub max_elements: usize,
+    // use matrix instead of symbol in forward formula
+    pub inline: bool,
+}
+
+impl Default for ValueOption {
+    fn default() -> Self {
+        ValueOption {
+            precision: 3,
+            max_rows: 4,
+            max_cols: 4,
+            max_elements: 64,
+            inline: false,
+        }
+    }
+}
+
+// statistics of tensor values
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ValueStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std: f64,
+}
+
+impl ValueStats {
+    pub fn from_values(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return ValueStats::default();
+        }
+        let count = values.len();
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let var = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / count as f64;
+        ValueStats {
+            count,
+            min,
+            max,
+            mean,
+            std: var.sqrt(),
+        }
+    }
+}
+
+// tensor values as f64
+pub fn tensor_values(tensor: &Tensor) -> Option<Vec<f64>> {
+    let t = tensor.cast_to::<f64>().ok()?;
+    t.as_slice::<f64>().ok().map(|s| s.to_vec())
+}
+
+// render tensor as bmatrix, more than 2 dims are flattened into cols
+pub fn render_matrix(shape: &[usize], values: &[f64], option: &ValueOption) -> String {
+    let num = |v: f64| format!("{:.*}", option.precision, v);
+    if shape.is_empty() {
+        return values.get(0).map(|v| num(*v)).unwrap_or_default();
+    }
+    let rows = if shape.len() >= 2 { shape[0] } else { 1 };
+    let cols = if rows == 0 { 0 } else { values.len() / rows };
+    let show_rows = rows.min(option.max_rows);
+    let show_cols = cols.min(option.max_cols);
+
+    let mut lines = Vec::new();
+    for r in 0..show_rows {
+        let mut line: Vec<String> = (0..show_cols).map(|c| num(values[r * cols + c])).collect();
+        if cols > show_cols {
+            line.push(r#"\cdots"#.to_string());
+        }
+        lines.push(line.join(" & "));
+    }
+    if rows > show_rows {
+        let mut line = vec![r#"\vdots"#.to_string(); show_cols];
+        if cols > show_cols {
+            line.push(r#"\ddots"#.to_string());
+        }
+        lines.push(line.join(" & "));
+    }
+    format!(
+        r#"\begin{{bmatrix}}{}\end{{bmatrix}}"#,
+        lines.join(r#" \\ "#)
+    )
+}
+
+#[test]
+fn matrix_test() {
+    let option = ValueOption {
+        precision: 1,
+        max_rows: 2,
+        max_cols: 2,
+        ..ValueOption::default()
+    };
+    let values: Vec<f64> = (0..9).map(|v| v as f64).collect();
+    assert_eq!(
+        render_matrix(&[3, 3], &values, &option),
+        r#"\begin{bmatrix}0.0 & 1.0 & \cdots \\ 3.0 & 4.0 & \cdots \\ \vdots & \vdots & \ddots\end{bmatrix}"#
+    );
+    assert_eq!(
+        render_matrix(&[2], &[1.0, 2.0], &option),
+        r#"\begin{bmatrix}1.0 & 2.0\end{bmatrix}"#
+    );
+}
+
+#[test]
+fn stats_test() {
+    let stats = ValueStats::from_values(&[1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(stats.count, 4);
+    assert_eq!(stats.min, 1.0);
+    assert_eq!(stats.max, 4.0);
+    assert_eq!(stats.mean, 2.5);
+    assert!((stats.std - 1.25f64.sqrt()).abs() < 1e-9);
+}
@@ -0,0 +1,890 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
+
+use serde::{Deserialize, Serialize};
+use tract_onnx::{
+    prelude::*,
+    tract_hir::{internal::SessionState, ops::konst::Const},
+};
+
+use crate::{
+    formula::{loss_symbols, pad_index, Formula, Leaf, Partials, Scope},
+    trace::{eval_node, eval_values},
+    value::tensor_values,
+    DiffChainNode, FormulKind, Indexes, InferenceNode, LatexEngine, LatexResult, Loss, MathGen,
+    ModelError, TraceInput,
+};
+
+// option for checking backward formulas with finite difference
+#[derive(Debug, Clone)]
+pub struct VerifyOption {
+    pub input: TraceInput,
+    // zeros when not given
+    pub target: Option<Tensor>,
+    // step of finite difference
+    pub eps: f32,
+}
+
+impl VerifyOption {
+    pub fn new(input: TraceInput) -> Self {
+        VerifyOption {
+            input,
+            target: None,
+            eps: 1e-3,
+        }
+    }
+}
+
+// result of gradient check for one weight element
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradCheck {
+    pub node: usize,
+    pub weight_node: usize,
+    pub weight_idx: Vec<usize>,
+    // finite difference of loss
+    pub numeric: f64,
+    // generated backward output evaluated with traced values
+    pub symbolic: f64,
+    pub abs_error: f64,
+    pub rel_error: f64,
+}
+
+// loss gradient of every node output evaluated from backward formulas
+pub(crate) struct Deltas {
+    pub values: Vec<Option<Vec<f64>>>,
+}
+
+// row major coordinate of offset
+fn coord(shape: &[usize], at: usize) -> Vec<usize> {
+    let mut rest = at;
+    let mut coord = vec![0; shape.len()];
+    for (c, s) in coord.iter_mut().zip(shape.iter()).rev() {
+        *c = rest % s;
+        rest /= s;
+    }
+    coord
+}
+
+// row major offset of index
+fn flat_index(shape: &[usize], index: &[usize]) -> Option<usize> {
+    if shape.len() != index.len() || index.iter().zip(shape.iter()).any(|(i, s)| i >= s) {
+        return None;
+    }
+    Some(
+        index
+            .iter()
+            .zip(shape.iter())
+            .fold(0, |acc, (i, s)| acc * s + i),
+    )
+}
+
+fn perturbed(t: &Tensor, at: usize, delta: f32) -> TractResult<Tensor> {
+    let mut t = t.cast_to::<f32>()?.into_owned();
+    t.as_slice_mut::<f32>()?[at] += delta;
+    Ok(t)
+}
+
+// target values of loss, zeros when not given
+pub(crate) fn target_values(target: &Option<Tensor>, len: usize) -> Result<Vec<f64>, Error> {
+    match target {
+        Some(t) => {
+            let values = tensor_values(t)
+                .ok_or(Error::new(ErrorKind::InvalidInput, "target is not number"))?;
+            if values.len() != len {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("target has {} elements, output has {}", values.len(), len),
+                ));
+            }
+            Ok(values)
+        }
+        None => Ok(vec![0.0; len]),
+    }
+}
+
+// placeholder symbols of input gradient formula
+const DELTA: &str = r#"\delta"#;
+
+fn input_symbol(slot: usize) -> String {
+    format!("x_{{{}}}", slot)
+}
+
+// gradient of input slot evaluated from input gradient formula of op
+fn formula_vjp(
+    op: &dyn MathGen,
+    inputs: &TVec<Arc<Tensor>>,
+    output_shape: &[usize],
+    delta: &[f64],
+    slot: usize,
+) -> Result<Vec<f64>, Error> {
+    let unsupported = || Error::new(ErrorKind::InvalidInput, "no input gradient formula");
+    let names: Vec<String> = (0..inputs.len()).map(input_symbol).collect();
+    let shapes: Vec<Vec<usize>> = inputs.iter().map(|t| t.shape().to_vec()).collect();
+    let (index, formula) = op
+        .gen_input_grad(DELTA.to_string(), names.clone(), shapes.clone(), slot, None)
+        .ok_or_else(unsupported)?;
+    let formula = Formula::parse(&formula)?;
+    let shape = &shapes[slot];
+    if index.len() != shape.len() {
+        return Err(unsupported());
+    }
+    let mut scope = Scope::default();
+    for (name, t) in names.iter().zip(inputs.iter()) {
+        scope.tensor(name, t.shape(), tensor_values(t).ok_or_else(unsupported)?);
+    }
+    scope.tensor(DELTA, output_shape, delta.to_vec());
+    let len: usize = shape.iter().product();
+    let mut result = Vec::with_capacity(len);
+    for at in 0..len {
+        for (v, c) in index.iter().zip(coord(shape, at).iter()) {
+            match v.parse::<usize>() {
+                Ok(n) if n == *c => {}
+                Ok(_) => return Err(unsupported()),
+                Err(_) => scope.set(v, *c as f64),
+            }
+        }
+        result.push(formula.eval(&mut scope)?);
+    }
+    Ok(result)
+}
+
+// node ids which appear in diff chain
+pub(crate) fn chain_nodes(target: &DiffChainNode, result: &mut Vec<usize>) {
+    match target {
+        DiffChainNode::Weightable(i, _)
+        | DiffChainNode::UnWeightable(i, _)
+        | DiffChainNode::Delta(i, _) => {
+            if !result.contains(i) {
+                result.push(*i);
+            }
+        }
+        DiffChainNode::Sum(d, _) => chain_nodes(d, result),
+        DiffChainNode::Chain(v) => v.iter().for_each(|d| chain_nodes(d, result)),
+        DiffChainNode::Paths(v, merge) => {
+            v.iter().for_each(|d| chain_nodes(d, result));
+            if let Some((_, _, Some(tail))) = merge {
+                chain_nodes(tail, result);
+            }
+        }
+        DiffChainNode::Not => {}
+    }
+}
+
+// node ids of each path in diff chain
+pub(crate) fn chain_paths(target: &DiffChainNode) -> Vec<Vec<usize>> {
+    match target {
+        DiffChainNode::Weightable(i, _)
+        | DiffChainNode::UnWeightable(i, _)
+        | DiffChainNode::Delta(i, _) => vec![vec![*i]],
+        DiffChainNode::Sum(d, _) => chain_paths(d),
+        DiffChainNode::Chain(v) => v.iter().fold(vec![Vec::new()], |acc, d| {
+            let tails = chain_paths(d);
+            acc.iter()
+                .flat_map(|a| {
+                    tails.iter().map(move |t| {
+                        let mut p = a.clone();
+                        p.extend(t.iter().filter(|n| !a.contains(n)));
+                        p
+                    })
+                })
+                .collect()
+        }),
+        // each path continues with every path of join node gradient
+        DiffChainNode::Paths(v, merge) => {
+            let heads: Vec<Vec<usize>> = v.iter().flat_map(chain_paths).collect();
+            match merge {
+                Some((_, _, Some(tail))) => {
+                    let tails = chain_paths(tail);
+                    heads
+                        .iter()
+                        .flat_map(|h| {
+                            tails.iter().map(move |t| {
+                                let mut p = h.clone();
+                                p.extend(t.iter().filter(|n| !h.contains(n)));
+                                p
+                            })
+                        })
+                        .collect()
+                }
+                _ => heads,
+            }
+        }
+        DiffChainNode::Not => vec![Vec::new()],
+    }
+}
+
+// separator of formula and delta definitions in backward output
+const DEFINITION: &str = r#",\quad "#;
+
+// parts of s split at sep outside of braces
+fn split_top<'a>(s: &'a str, sep: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ if depth == 0 && i >= start && s[i..].starts_with(sep) => {
+                parts.push(&s[start..i]);
+                start = i + sep.len();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+// end of brace group which starts at open
+fn group_end(s: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// delta symbols used in formula ex) \delta^{(f_{1})}
+fn delta_refs(s: &str) -> Vec<&str> {
+    let head = r#"\delta^"#;
+    let mut result = Vec::new();
+    let mut from = 0;
+    while let Some(i) = s[from..].find(head).map(|i| from + i) {
+        match group_end(s, i + head.len()) {
+            Some(end) => {
+                result.push(&s[i..=end]);
+                from = end;
+            }
+            None => break,
+        }
+    }
+    result
+}
+
+fn not_evaluable(m: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, m)
+}
+
+// traced values of model, partial derivatives in backward output are evaluated from
+// input gradient formulas of ops
+struct Traced<'a> {
+    model: &'a InferenceModel,
+    symbol_result: &'a LatexResult,
+    math_ops: &'a [Box<dyn MathGen>],
+    values: &'a [Option<TVec<Arc<Tensor>>>],
+    last_point: usize,
+    // loss gradient of last point output
+    head: Vec<f64>,
+    // gradient of input slot by one output element, key is node, slot and element
+    rows: RefCell<HashMap<(usize, usize, usize), Vec<f64>>>,
+    // transposed jacobian times delta, key is node and input node
+    vjps: RefCell<HashMap<(usize, usize), Vec<f64>>>,
+}
+
+impl<'a> Traced<'a> {
+    fn node(&self, symbol: &str) -> Result<usize, Error> {
+        self.symbol_result
+            .symbol_map
+            .iter()
+            .flatten()
+            .find(|n| n.symbol == symbol)
+            .map(|n| n.index)
+            .ok_or_else(|| not_evaluable(format!("no node has symbol {}", symbol)))
+    }
+    fn output(&self, n: usize) -> Result<&Arc<Tensor>, Error> {
+        self.values[n]
+            .as_ref()
+            .map(|v| &v[0])
+            .ok_or_else(|| not_evaluable(format!("node {} is not evaluated", n)))
+    }
+    fn inputs(&self, n: usize) -> Result<TVec<Arc<Tensor>>, Error> {
+        self.model
+            .node(n)
+            .inputs
+            .iter()
+            .map(|o| {
+                self.values[o.node]
+                    .as_ref()
+                    .map(|v| v[o.slot].clone())
+                    .ok_or_else(|| not_evaluable(format!("node {} is not evaluated", o.node)))
+            })
+            .collect()
+    }
+    // input slots of node n which are fed by node input
+    fn slots(&self, n: usize, input: usize) -> Result<Vec<usize>, Error> {
+        let slots: Vec<usize> = self
+            .model
+            .node(n)
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.node == input && o.slot == 0)
+            .map(|(slot, _)| slot)
+            .collect();
+        if slots.is_empty() {
+            return Err(not_evaluable(format!(
+                "node {} is not input of node {}",
+                input, n
+            )));
+        }
+        Ok(slots)
+    }
+    fn flat(shape: &[usize], index: &[f64]) -> Result<usize, Error> {
+        let out_of_shape =
+            || not_evaluable(format!("index {:?} is out of shape {:?}", index, shape));
+        let index = pad_index(shape, index)
+            .ok_or_else(out_of_shape)?
+            .iter()
+            .map(|i| {
+                if *i >= 0.0 && i.fract() == 0.0 {
+                    Ok(*i as usize)
+                } else {
+                    Err(out_of_shape())
+                }
+            })
+            .collect::<Result<Vec<usize>, Error>>()?;
+        flat_index(shape, &index).ok_or_else(out_of_shape)
+    }
+    // derivative of one output element of node by every element of input slot
+    fn row(&self, n: usize, slot: usize, at: usize) -> Result<Vec<f64>, Error> {
+        if let Some(row) = self.rows.borrow().get(&(n, slot, at)) {
+            return Ok(row.clone());
+        }
+        let shape = self.output(n)?.shape().to_vec();
+        let mut delta = vec![0.0; shape.iter().product()];
+        *delta
+            .get_mut(at)
+            .ok_or_else(|| not_evaluable(format!("node {} has no element {}", n, at)))? = 1.0;
+        let row = formula_vjp(
+            self.math_ops[n].as_ref(),
+            &self.inputs(n)?,
+            &shape,
+            &delta,
+            slot,
+        )
+        .map_err(|e| not_evaluable(format!("gradient of node {}: {}", n, e)))?;
+        self.rows.borrow_mut().insert((n, slot, at), row.clone());
+        Ok(row)
+    }
+    // traced node outputs by symbol, target as t and number of outputs as N
+    fn scope(&self, target: &[f64]) -> Result<Scope, Error> {
+        let mut scope = Scope::default();
+        for node in self.symbol_result.symbol_map.iter().flatten() {
+            if let Some(values) = self.values[node.index]
+                .as_ref()
+                .and_then(|v| tensor_values(&v[0]))
+            {
+                scope.tensor(&node.symbol, self.output(node.index)?.shape(), values);
+            }
+        }
+        let output = self.output(self.last_point)?;
+        scope.tensor("t", output.shape(), target.to_vec());
+        scope.set("N", output.len() as f64);
+        Ok(scope)
+    }
+    // delta of node by definition ex) {\delta^{(f_{1})}}_{(i,j)}=... or \delta^{(f_{1})}=...
+    fn define(&self, scope: &mut Scope, lhs: &str, formula: &Formula) -> Result<(), Error> {
+        let malformed = || not_evaluable(format!("malformed delta definition {}", lhs));
+        let (name, vars) = if lhs.starts_with('{') {
+            let close = group_end(lhs, 0).ok_or_else(malformed)?;
+            let vars = lhs[close + 1..]
+                .strip_prefix("_{(")
+                .and_then(|v| v.strip_suffix(")}"))
+                .ok_or_else(malformed)?;
+            let vars: Vec<&str> = vars.split(',').filter(|v| !v.is_empty()).collect();
+            (&lhs[1..close], Some(vars))
+        } else {
+            (lhs, None)
+        };
+        let symbol = name
+            .strip_prefix(r#"\delta^{("#)
+            .and_then(|s| s.strip_suffix(")}"))
+            .ok_or_else(malformed)?;
+        let full = self.output(self.node(symbol)?)?.shape().to_vec();
+        let len: usize = full.iter().product();
+        let mut values = Vec::with_capacity(len);
+        match vars {
+            Some(vars) => {
+                // references leave out leading batch dims
+                if pad_index(&full, &vec![0.0; vars.len()]).is_none() {
+                    return Err(malformed());
+                }
+                let shape = full[full.len() - vars.len()..].to_vec();
+                for at in 0..len {
+                    for (v, c) in vars.iter().zip(coord(&shape, at).iter()) {
+                        match v.parse::<usize>() {
+                            Ok(n) if n == *c => {}
+                            Ok(_) => {
+                                return Err(not_evaluable(format!(
+                                    "{} defines one element only",
+                                    lhs
+                                )))
+                            }
+                            Err(_) => scope.set(v, *c as f64),
+                        }
+                    }
+                    values.push(formula.eval_with(scope, self)?);
+                }
+                scope.tensor(name, &shape, values);
+            }
+            None => {
+                for at in 0..len {
+                    scope.at = Some(at);
+                    let value = formula.eval_with(scope, self);
+                    scope.at = None;
+                    values.push(value?);
+                }
+                scope.tensor(name, &full, values);
+            }
+        }
+        Ok(())
+    }
+    // value of backward output, symbolic index of weight in symbol is bound to weight_idx
+    fn eval_backward(
+        &self,
+        symbol: &str,
+        value: &str,
+        weight_idx: &[usize],
+        target: &[f64],
+    ) -> Result<f64, Error> {
+        let mut scope = self.scope(target)?;
+        self.vjps.borrow_mut().clear();
+        let parts = split_top(value, DEFINITION);
+        let mut definitions = parts[1..]
+            .iter()
+            .map(|d| {
+                let (lhs, rhs) = split_top(d, "=")
+                    .split_first()
+                    .map(|(lhs, _)| (lhs.trim(), &d[lhs.len() + 1..]))
+                    .filter(|(_, rhs)| d.len() > rhs.len())
+                    .ok_or_else(|| not_evaluable(format!("{} is not definition", d)))?;
+                Ok((lhs, rhs, Formula::parse(rhs)?))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        // deltas are defined after deltas they use
+        while !definitions.is_empty() {
+            let ready = definitions
+                .iter()
+                .position(|(_, rhs, _)| delta_refs(rhs).iter().all(|d| scope.has_tensor(d)))
+                .ok_or_else(|| not_evaluable("delta is used but not defined".to_string()))?;
+            let (lhs, _, formula) = definitions.remove(ready);
+            self.define(&mut scope, lhs, &formula)?;
+        }
+        // denominator of symbol ex) {W}_{(o,c,m,n)}
+        let by = symbol
+            .rfind(r#"\partial "#)
+            .map(|i| symbol[i + r#"\partial "#.len()..].trim_end_matches('}'))
+            .unwrap_or_default();
+        if let Some(vars) = by
+            .find("}_{(")
+            .and_then(|i| by[i + 4..].strip_suffix(')'))
+            .filter(|v| !v.contains(['(', '{']))
+        {
+            let vars: Vec<&str> = vars.split(',').filter(|v| !v.is_empty()).collect();
+            if vars.len() != weight_idx.len() {
+                return Err(not_evaluable(format!(
+                    "weight index {:?} does not fit {}",
+                    weight_idx, symbol
+                )));
+            }
+            for (v, i) in vars.iter().zip(weight_idx.iter()) {
+                match v.parse::<usize>() {
+                    Ok(n) if n == *i => {}
+                    Ok(_) => {
+                        return Err(not_evaluable(format!(
+                            "{} is not gradient of weight {:?}",
+                            symbol, weight_idx
+                        )))
+                    }
+                    Err(_) => scope.set(v, *i as f64),
+                }
+            }
+        }
+        Formula::parse(parts[0])?.eval_with(&mut scope, self)
+    }
+}
+
+impl<'a> Partials for Traced<'a> {
+    fn partial(&self, of: &Leaf, by: &Leaf, at: Option<usize>) -> Result<f64, Error> {
+        match (of, by) {
+            (Leaf::Error(element), Leaf::Element(name, index)) => {
+                let n = self.node(name)?;
+                let flat = Self::flat(self.output(n)?.shape(), index)?;
+                if let Some((e_name, e_index)) = element {
+                    let e_flat = Self::flat(self.output(n)?.shape(), e_index)?;
+                    if e_name != name || e_flat != flat {
+                        return Err(not_evaluable(format!("{:?} is not loss of {:?}", of, by)));
+                    }
+                }
+                if n != self.last_point {
+                    return Err(not_evaluable(format!("{} is not output of loss", name)));
+                }
+                Ok(self.head[flat])
+            }
+            (Leaf::Error(None), Leaf::Vector(name)) if self.node(name)? == self.last_point => {
+                let at = at.ok_or_else(|| not_evaluable(format!("{} is vector", name)))?;
+                Ok(self.head[at])
+            }
+            (Leaf::Element(a, a_index), Leaf::Element(b, b_index)) => {
+                let (n, input) = (self.node(a)?, self.node(b)?);
+                let at = Self::flat(self.output(n)?.shape(), a_index)?;
+                let by = Self::flat(self.output(input)?.shape(), b_index)?;
+                let mut sum = 0.0;
+                for slot in self.slots(n, input)? {
+                    sum += self.row(n, slot, at)?[by];
+                }
+                Ok(sum)
+            }
+            (Leaf::Element(a, a_index), Leaf::Weight(layer, l_index, w_index)) if a == layer => {
+                let n = self.node(a)?;
+                let shape = self.output(n)?.shape().to_vec();
+                if Self::flat(&shape, a_index)? != Self::flat(&shape, l_index)? {
+                    return Err(not_evaluable(format!("{:?} is not weight of {:?}", by, of)));
+                }
+                let slot = self.symbol_result.weight_slot(n);
+                let weight = self
+                    .inputs(n)?
+                    .get(slot)
+                    .map(|w| w.shape().to_vec())
+                    .ok_or_else(|| not_evaluable(format!("node {} has no weight", n)))?;
+                let by = Self::flat(&weight, w_index)?;
+                Ok(self.row(n, slot, Self::flat(&shape, l_index)?)?[by])
+            }
+            _ => Err(not_evaluable(format!(
+                "partial of {:?} by {:?} is not evaluable",
+                of, by
+            ))),
+        }
+    }
+    fn vjp(&self, of: &str, by: &str, delta: &[f64], at: usize) -> Result<f64, Error> {
+        let (n, input) = (self.node(of)?, self.node(by)?);
+        if !self.vjps.borrow().contains_key(&(n, input)) {
+            let inputs = self.inputs(n)?;
+            let shape = self.output(n)?.shape().to_vec();
+            let mut sum = vec![0.0; self.output(input)?.len()];
+            for slot in self.slots(n, input)? {
+                let grad = formula_vjp(self.math_ops[n].as_ref(), &inputs, &shape, delta, slot)
+                    .map_err(|e| not_evaluable(format!("gradient of node {}: {}", n, e)))?;
+                sum.iter_mut().zip(grad.iter()).for_each(|(a, b)| *a += b);
+            }
+            self.vjps.borrow_mut().insert((n, input), sum);
+        }
+        self.vjps.borrow()[&(n, input)]
+            .get(at)
+            .cloned()
+            .ok_or_else(|| not_evaluable(format!("{} has no element {}", by, at)))
+    }
+}
+
+// vector jacobian product on input slot by central difference, for ops without formula
+fn local_vjp(
+    node: &InferenceNode,
+    inputs: &TVec<Arc<Tensor>>,
+    slot: usize,
+    upstream: &[f64],
+    eps: f32,
+) -> TractResult<Vec<f64>> {
+    let mut session_state = SessionState::default();
+    let mut result = Vec::new();
+    for i in 0..inputs[slot].len() {
+        let mut out = Vec::new();
+        for delta in [eps, -eps].iter() {
+            let mut moved = inputs.clone();
+            moved[slot] = Arc::new(perturbed(&inputs[slot], i, *delta)?);
+            let o = eval_node(&mut session_state, node, moved)?;
+            out.push(tensor_values(&o[0]).unwrap_or_default());
+        }
+        let d: f64 = out[0]
+            .iter()
+            .zip(out[1].iter())
+            .zip(upstream.iter())
+            .map(|((p, m), u)| (p - m) / (2.0 * eps as f64) * u)
+            .sum();
+        result.push(d);
+    }
+    Ok(result)
+}
+
+impl LatexEngine {
+    // derivative of loss by each output element
+    fn loss_head_values(&self, output: &[f64], target: &[f64]) -> Result<Vec<f64>, Error> {
+        let loss = self.loss.clone().unwrap_or(Loss::Mse);
+        let (y, t) = loss_symbols();
+        let diff = Formula::parse(&loss.gen_diff(&self.symbol_library, y, t)?)?;
+        let mut scope = Scope::loss(output, target);
+        let mut head = Vec::with_capacity(output.len());
+        for i in 0..output.len() {
+            scope.set("i", i as f64);
+            head.push(diff.eval(&mut scope)?);
+        }
+        Ok(head)
+    }
+    // propagate derivative of loss from output of last point back to every node in order,
+    // input gradient formulas of each op are evaluated with traced values
+    pub(crate) fn eval_deltas(
+        &self,
+        model: &InferenceModel,
+        order: &[usize],
+        values: &[Option<TVec<Arc<Tensor>>>],
+        last_point: usize,
+        target: &[f64],
+        eps: f32,
+    ) -> TractResult<Deltas> {
+        let not_found = |m: &str| Error::new(ErrorKind::NotFound, m.to_string());
+        let value = |n: usize| -> Result<&TVec<Arc<Tensor>>, Error> {
+            values[n].as_ref().ok_or(not_found("node is not evaluated"))
+        };
+        let output = tensor_values(&value(last_point)?[0]).unwrap_or_default();
+        let head = self.loss_head_values(&output, target)?;
+
+        let math_ops = Self::math_op_vecs(model);
+        let mut deltas: Vec<Option<Vec<f64>>> = vec![None; model.nodes().len()];
+        deltas[last_point] = Some(head);
+        let end = order
+            .iter()
+            .position(|n| *n == last_point)
+            .ok_or(not_found("error node is not in order"))?;
+        for n in order[..=end].iter().rev() {
+            if self.cancelled() {
+                return Err(ModelError::Cancelled.into());
+            }
+            let delta = match deltas[*n] {
+                Some(ref d) => d.clone(),
+                None => continue,
+            };
+            let node = model.node(*n);
+            let inputs = node
+                .inputs
+                .iter()
+                .map(|o| value(o.node).map(|v| v[o.slot].clone()))
+                .collect::<Result<TVec<_>, Error>>()?;
+            let output_shape = value(*n)?[0].shape().to_vec();
+            for (slot, outlet) in node.inputs.iter().enumerate() {
+                if outlet.slot != 0 || !inputs[slot].datum_type().is_float() {
+                    continue;
+                }
+                let grad = match formula_vjp(
+                    math_ops[*n].as_ref(),
+                    &inputs,
+                    &output_shape,
+                    &delta,
+                    slot,
+                ) {
+                    Ok(g) => g,
+                    Err(_) => local_vjp(node, &inputs, slot, &delta, eps)?,
+                };
+                // sum over every consumer of input
+                match deltas[outlet.node] {
+                    Some(ref mut d) => d.iter_mut().zip(grad.iter()).for_each(|(a, b)| *a += b),
+                    None => deltas[outlet.node] = Some(grad),
+                }
+            }
+        }
+        Ok(Deltas { values: deltas })
+    }
+    // compare backward output of weight element of layer with finite difference of loss,
+    // partial derivatives in output are evaluated from input gradient formulas of ops
+    // and output which can not be evaluated is error
+    pub fn verify_backward(
+        &self,
+        model: &InferenceModel,
+        symbol_result: &LatexResult,
+        layer_node: usize,
+        indexes: &Indexes,
+        option: &VerifyOption,
+    ) -> TractResult<GradCheck> {
+        let not_found = |m: &str| Error::new(ErrorKind::NotFound, m.to_string());
+        let order = model.eval_order()?;
+        let inputs = Self::trace_inputs(model, &option.input)?;
+        let values = eval_values(model, &order, &inputs)?;
+        let last_point = *symbol_result
+            .senario
+            .last()
+            .ok_or(not_found("model has no layer"))?;
+        let sym_node = symbol_result.symbol_map[layer_node]
+            .as_ref()
+            .ok_or(not_found("not found index"))?;
+
+        // weight input of layer, second input if not named
+        let weight_slot = symbol_result.weight_slot(layer_node);
+        let weight_node = *sym_node
+            .inputs
+            .get(weight_slot)
+            .ok_or(not_found("layer has no weight"))?;
+        let weight = values[weight_node]
+            .as_ref()
+            .ok_or(not_found("weight is not evaluated"))?[0]
+            .clone();
+        let at = flat_index(weight.shape(), &indexes.weight_idx).ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "weight index {:?} is out of shape {:?}",
+                indexes.weight_idx,
+                weight.shape()
+            ),
+        ))?;
+        let output = values[last_point]
+            .as_ref()
+            .ok_or(not_found("error node is not evaluated"))?[0]
+            .clone();
+        let target = target_values(&option.target, output.len())?;
+        let loss = self.loss.clone().unwrap_or(Loss::Mse);
+
+        // finite difference of loss
+        let eps = option.eps;
+        let mut errors = Vec::new();
+        for delta in [eps, -eps].iter() {
+            let mut moved = model.clone();
+            let t = perturbed(&weight, at, *delta)?;
+            moved.node_mut(weight_node).op = Const(Arc::new(t)).into();
+            let moved_values = eval_values(&moved, &order, &inputs)?;
+            let out = moved_values[last_point]
+                .as_ref()
+                .ok_or(not_found("error node is not evaluated"))?;
+            errors.push(loss.eval(&tensor_values(&out[0]).unwrap_or_default(), &target)?);
+        }
+        let numeric = (errors[0] - errors[1]) / (2.0 * eps as f64);
+
+        let math_ops = Self::math_op_vecs(model);
+        let traced = Traced {
+            model,
+            symbol_result,
+            math_ops: &math_ops,
+            values: &values,
+            last_point,
+            head: self.loss_head_values(&tensor_values(&output).unwrap_or_default(), &target)?,
+            rows: RefCell::new(HashMap::new()),
+            vjps: RefCell::new(HashMap::new()),
+        };
+        // element chain is for one output element of layer, correlation covers all of them
+        let kind = math_ops[layer_node].get_symbol_type(sym_node.extra_symbol.clone());
+        let func_idxs: Vec<Vec<usize>> = match kind {
+            FormulKind::Cnn | FormulKind::MaxPool | FormulKind::SumPool => vec![Vec::new()],
+            _ => {
+                let shape = traced.output(layer_node)?.shape().to_vec();
+                (0..shape.iter().product())
+                    .map(|at| coord(&shape, at))
+                    .collect()
+            }
+        };
+        let mut symbolic = 0.0;
+        for func_idx in func_idxs.into_iter() {
+            if self.cancelled() {
+                return Err(ModelError::Cancelled.into());
+            }
+            let element = Indexes::new(indexes.weight_idx.clone(), func_idx);
+            let (symbol, value) = self.gen_each_back(
+                &math_ops,
+                model,
+                symbol_result,
+                (layer_node, last_point),
+                &element,
+                None,
+            )?;
+            symbolic += traced.eval_backward(&symbol, &value, &indexes.weight_idx, &target)?;
+        }
+
+        let abs_error = (numeric - symbolic).abs();
+        Ok(GradCheck {
+            node: layer_node,
+            weight_node,
+            weight_idx: indexes.weight_idx.clone(),
+            numeric,
+            symbolic,
+            abs_error,
+            rel_error: abs_error / numeric.abs().max(symbolic.abs()).max(1e-12),
+        })
+    }
+}
+
+#[test]
+fn verify_two_layer_test() {
+    let mut engine = LatexEngine::new();
+    let model = engine
+        .model_from_file(&mut std::io::Cursor::new(crate::test_model::two_layer()))
+        .unwrap();
+    let result = engine
+        .parse_from_file(
+            &mut std::io::Cursor::new(crate::test_model::two_layer()),
+            Some(4),
+        )
+        .unwrap();
+    let layers: Vec<usize> = result
+        .senario
+        .iter()
+        .cloned()
+        .filter(|n| result.symbol_map[*n].as_ref().unwrap().op_name == "Gemm")
+        .collect();
+    let option = VerifyOption::new(TraceInput::Random(0));
+    for (layer, weight_idx) in [(layers[0], vec![2, 1]), (layers[1], vec![3, 0])].iter() {
+        let indexes = Indexes::new(weight_idx.clone(), vec![]);
+        let check = engine
+            .verify_backward(&model, &result, *layer, &indexes, &option)
+            .unwrap();
+        assert!(check.rel_error < 1e-2, "{:?}", check);
+        assert!(check.numeric.abs() > 1e-6, "{:?}", check);
+    }
+
+    // loss of engine is used for both sides
+    engine.loss = Some(Loss::L1);
+    let mut option = VerifyOption::new(TraceInput::Random(1));
+    option.target = Some(tensor2(&[[0.5f32, -0.5]]));
+    let indexes = Indexes::new(vec![0, 0], vec![]);
+    let check = engine
+        .verify_backward(&model, &result, layers[0], &indexes, &option)
+        .unwrap();
+    assert!(check.rel_error < 1e-2, "{:?}", check);
+}
+
+#[test]
+fn verify_residual_test() {
+    let bytes = crate::test_model::residual(2);
+    let mut engine = LatexEngine::new();
+    let model = engine
+        .model_from_file(&mut std::io::Cursor::new(bytes.clone()))
+        .unwrap();
+    let result = engine
+        .parse_from_file(&mut std::io::Cursor::new(bytes), Some(4))
+        .unwrap();
+    let layers: Vec<usize> = result
+        .senario
+        .iter()
+        .cloned()
+        .filter(|n| result.symbol_map[*n].as_ref().unwrap().op_name == "Gemm")
+        .collect();
+    // gradient of first layer goes through delta of each add node
+    let option = VerifyOption::new(TraceInput::Random(2));
+    for layer in layers.iter() {
+        let indexes = Indexes::new(vec![2, 1], vec![]);
+        let check = engine
+            .verify_backward(&model, &result, *layer, &indexes, &option)
+            .unwrap();
+        assert!(check.rel_error < 1e-2, "{:?}", check);
+    }
+
+    // matrix form is not evaluated, check fails instead of using finite difference
+    engine.backward_form = crate::BackwardForm::Matrix;
+    let indexes = Indexes::new(vec![2, 1], vec![]);
+    assert!(engine
+        .verify_backward(&model, &result, layers[0], &indexes, &option)
+        .is_err());
+}
+
+#[test]
+fn flat_index_test() {
+    assert_eq!(flat_index(&[2, 3], &[1, 2]), Some(5));
+    assert_eq!(flat_index(&[2, 3], &[2, 0]), None);
+    assert_eq!(flat_index(&[2, 3], &[1]), None);
+}
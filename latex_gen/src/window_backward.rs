@@ -0,0 +1,188 @@
+use std::io::{Error, ErrorKind};
+
+use tract_onnx::{
+    prelude::*,
+    tract_hir::utils::{element_symbol, FormulKind, MathGen},
+};
+
+use crate::{LatexEngine, LatexResult};
+
+// gradient of error on node output ex) \delta^{(f_{1})}
+pub(crate) fn delta_symbol(symbol: &str) -> String {
+    format!(r#"\delta^{{({})}}"#, symbol)
+}
+
+impl LatexEngine {
+    // delta of node defined by delta of next nodes on path, summed over branches
+    fn delta_step(
+        &self,
+        math_opvec: &Vec<Box<dyn MathGen>>,
+        symbol_result: &LatexResult,
+        node: usize,
+        nexts: &[usize],
+    ) -> Result<String, Error> {
+        let not_found = || Error::new(ErrorKind::NotFound, "not found index");
+        let symbol = symbol_result.symbol_map[node]
+            .as_ref()
+            .ok_or(not_found())?
+            .symbol
+            .clone();
+        let delta = delta_symbol(&symbol);
+        let next = match nexts.first() {
+            Some(n) if nexts.len() == 1 => *n,
+            Some(_) => {
+                let terms = nexts
+                    .iter()
+                    .map(|n| self.delta_term(math_opvec, symbol_result, node, *n))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                // element form only when every branch gives same index
+                if let Some(Some((index, _))) = terms.first() {
+                    if terms
+                        .iter()
+                        .all(|t| t.as_ref().map(|t| &t.0) == Some(index))
+                    {
+                        let sum: Vec<String> =
+                            terms.iter().flatten().map(|t| t.1.clone()).collect();
+                        return Ok(format!(
+                            "{}={}",
+                            element_symbol(&delta, index),
+                            sum.join("+")
+                        ));
+                    }
+                }
+                let value = nexts
+                    .iter()
+                    .map(|n| self.delta_transpose(math_opvec, symbol_result, node, *n))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                return Ok(format!("{}={}", delta, value.join("+")));
+            }
+            None => {
+                let value = match self.loss {
+                    Some(ref loss) => {
+                        loss.gen_diff(&self.symbol_library, symbol, "t".to_string())?
+                    }
+                    None => {
+                        let e_symbol = self
+                            .symbol_library
+                            .gen_error_symbol(vec!["total".to_string(), "".to_string()]);
+                        math_opvec[node].gen_backward(e_symbol, symbol)
+                    }
+                };
+                return Ok(format!("{}={}", delta, value));
+            }
+        };
+        match self.delta_term(math_opvec, symbol_result, node, next)? {
+            Some((index, formula)) => Ok(format!("{}={}", element_symbol(&delta, &index), formula)),
+            None => Ok(format!(
+                "{}={}",
+                delta,
+                self.delta_transpose(math_opvec, symbol_result, node, next)?
+            )),
+        }
+    }
+    // contribution of next node to delta of node, element index if op has input gradient
+    fn delta_term(
+        &self,
+        math_opvec: &Vec<Box<dyn MathGen>>,
+        symbol_result: &LatexResult,
+        node: usize,
+        next: usize,
+    ) -> Result<Option<(Vec<String>, String)>, Error> {
+        let next_node = symbol_result.symbol_map[next]
+            .as_ref()
+            .ok_or(Error::new(ErrorKind::NotFound, "not found index"))?;
+        let slot = next_node
+            .inputs
+            .iter()
+            .position(|i| *i == node)
+            .ok_or(Error::new(
+                ErrorKind::NotFound,
+                "backward chain is not connected",
+            ))?;
+        let (inputs, input_shapes) = symbol_result.input_info(next)?;
+        let next_delta = delta_symbol(&next_node.symbol);
+        Ok(math_opvec[next].gen_input_grad(next_delta, inputs, input_shapes, slot, None))
+    }
+    // generic contribution, ex) \left(\frac{\partial f_{2}}{\partial f_{1}}\right)^{\top}\delta^{(f_{2})}
+    fn delta_transpose(
+        &self,
+        math_opvec: &Vec<Box<dyn MathGen>>,
+        symbol_result: &LatexResult,
+        node: usize,
+        next: usize,
+    ) -> Result<String, Error> {
+        let not_found = || Error::new(ErrorKind::NotFound, "not found index");
+        let symbol = &symbol_result.symbol_map[node]
+            .as_ref()
+            .ok_or(not_found())?
+            .symbol;
+        let next_node = symbol_result.symbol_map[next].as_ref().ok_or(not_found())?;
+        Ok(format!(
+            r#"\left({}\right)^{{\top}}{}"#,
+            math_opvec[next].gen_backward(next_node.symbol.clone(), symbol.clone()),
+            delta_symbol(&next_node.symbol)
+        ))
+    }
+    // delta of each node from node to last point
+    pub(crate) fn delta_chain(
+        &self,
+        math_opvec: &Vec<Box<dyn MathGen>>,
+        model: &InferenceModel,
+        symbol_result: &LatexResult,
+        index: usize,
+        last_point: usize,
+    ) -> Result<String, Error> {
+        let path = self.chain_path(symbol_result, model, index, last_point)?;
+        let mut result = String::new();
+        for n in path.iter() {
+            let nexts = symbol_result.next_on_path(*n, &path);
+            let step = self.delta_step(math_opvec, symbol_result, *n, &nexts)?;
+            result += &format!(r#",\quad {}"#, step);
+        }
+        Ok(result)
+    }
+    // gradient of conv weight or pooling input by correlation with delta of output
+    pub fn gen_window_back(
+        &self,
+        math_opvec: &Vec<Box<dyn MathGen>>,
+        model: &InferenceModel,
+        symbol_result: &LatexResult,
+        n_indxs: (usize, usize),
+    ) -> Result<(String, String), Error> {
+        let (index, last_point) = n_indxs;
+        let sym_node = symbol_result.symbol_map[index]
+            .as_ref()
+            .ok_or(Error::new(ErrorKind::NotFound, "not found index"))?;
+        let (inputs, input_shapes) = symbol_result.input_info(index)?;
+        let math_op = &math_opvec[index];
+        let slot = match math_op.get_symbol_type(sym_node.extra_symbol.clone()) {
+            FormulKind::Cnn => symbol_result.weight_slot(index),
+            _ => 0,
+        };
+        let (index_vars, formula) = math_op
+            .gen_input_grad(
+                delta_symbol(&sym_node.symbol),
+                inputs.clone(),
+                input_shapes,
+                slot,
+                None,
+            )
+            .ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                format!("backward formula is not supported for {}", sym_node.op_name),
+            ))?;
+        let e_symbol = self
+            .symbol_library
+            .gen_error_symbol(vec!["total".to_string(), "".to_string()]);
+        let symbol = math_op.gen_backward(e_symbol, element_symbol(&inputs[slot], &index_vars));
+
+        let value =
+            formula + &self.delta_chain(math_opvec, model, symbol_result, index, last_point)?;
+        Ok((symbol, value))
+    }
+}
+
+#[test]
+fn delta_symbol_test() {
+    assert_eq!(delta_symbol("f_{1}"), r#"\delta^{(f_{1})}"#);
+}
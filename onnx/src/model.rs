@@ -84,9 +84,12 @@ impl<'a> ParsingContext<'a> {
         for output in graph.output.iter() {
             trace!("Model output: {:?}", output);
         }
-        for (name, t) in initializers.into_iter() {
-            let id = model.add_const(name, t)?;
-            outlets_by_name.insert(name.to_string(), id);
+        // proto order keeps node ids same between parses of one file
+        for init in graph.initializer.iter() {
+            if let Some(t) = initializers.remove(&*init.name) {
+                let id = model.add_const(&*init.name, t)?;
+                outlets_by_name.insert(init.name.to_string(), id);
+            }
         }
         let consts = model.nodes().len();
         for pbnode in graph.node.iter() {
@@ -4,6 +4,10 @@ use tract_hir::internal::*;
 use tract_hir::ops;
 use tract_hir::utils::FormulKind;
 use tract_hir::utils::MathGen;
+use tract_hir::utils::{
+    broadcast_grad, broadcast_index, broadcast_out_index, element_symbol, input_index, to_strings,
+    unit_vars,
+};
 
 pub fn gemm(
     _ctx: &ParsingContext,
@@ -27,6 +31,15 @@ pub struct Gemm {
     trans_b: bool,
 }
 
+// coefficient symbol of alpha and beta
+fn coefficient(s: f32) -> String {
+    match s {
+        -1.0 => "-".to_string(),
+        1.0 => "".to_string(),
+        x @ _ => format!(r#"{} \times"#, x),
+    }
+}
+
 impl_dyn_hash!(Gemm);
 impl MathGen for Gemm {
     fn get_original_type(&self) -> FormulKind {
@@ -38,20 +51,150 @@ impl MathGen for Gemm {
         _input_shape: Option<Vec<usize>>,
         _output_shape: Option<Vec<usize>>,
     ) -> String {
-        let f = |s: f32| match s {
-            -1.0 => "-".to_string(),
-            1.0 => "".to_string(),
-            x @ _ => format!(r#"{} \times"#, x),
-        };
         format!(
             r#"{a1}{}\cdot {}+{a2}\times {}"#,
             inputs[0],
             inputs[1],
             inputs[2],
-            a1 = f(self.alpha),
-            a2 = f(self.beta)
+            a1 = coefficient(self.alpha),
+            a2 = coefficient(self.beta)
         )
     }
+    fn gen_element_value(
+        &self,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        coord: Vec<usize>,
+        expand: Option<usize>,
+    ) -> Option<String> {
+        let a_shape = input_shapes.get(0)?;
+        let c_shape = input_shapes.get(2)?;
+        let k = if self.trans_a { a_shape[0] } else { a_shape[1] };
+        let (i, j) = (coord[0].to_string(), coord[1].to_string());
+        let a_index = |k: String| {
+            if self.trans_a {
+                vec![k, i.clone()]
+            } else {
+                vec![i.clone(), k]
+            }
+        };
+        let b_index = |k: String| {
+            if self.trans_b {
+                vec![j.clone(), k]
+            } else {
+                vec![k, j.clone()]
+            }
+        };
+        let product = |k: String| {
+            format!(
+                r#"{}\cdot {}"#,
+                element_symbol(&inputs[0], &a_index(k.clone())),
+                element_symbol(&inputs[1], &b_index(k))
+            )
+        };
+        // empty reduced dimension sums to 0
+        let sum = if k == 0 {
+            "0".to_string()
+        } else if expand.map(|l| k <= l).unwrap_or(false) {
+            let terms: Vec<String> = (0..k).map(|s| product(s.to_string())).collect();
+            format!("({})", terms.join("+"))
+        } else {
+            format!(r#"\sum_{{k=0}}^{{{}}}{}"#, k - 1, product("k".to_string()))
+        };
+        let c = element_symbol(&inputs[2], &to_strings(&broadcast_index(c_shape, &coord)));
+        Some(format!(
+            r#"{a1}{}+{a2}{}"#,
+            sum,
+            c,
+            a1 = coefficient(self.alpha),
+            a2 = coefficient(self.beta)
+        ))
+    }
+    fn gen_input_grad(
+        &self,
+        delta: String,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        slot: usize,
+        index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        let a_shape = input_shapes.get(0)?;
+        let b_shape = input_shapes.get(1)?;
+        let m = if self.trans_a { a_shape[1] } else { a_shape[0] };
+        let n = if self.trans_b { b_shape[0] } else { b_shape[1] };
+        let a_index = |i: &str, k: &str| -> Vec<String> {
+            let (i, k) = (i.to_string(), k.to_string());
+            if self.trans_a {
+                vec![k, i]
+            } else {
+                vec![i, k]
+            }
+        };
+        let b_index = |k: &str, j: &str| -> Vec<String> {
+            let (k, j) = (k.to_string(), j.to_string());
+            if self.trans_b {
+                vec![j, k]
+            } else {
+                vec![k, j]
+            }
+        };
+        let d_index = |i: &str, j: &str| vec![i.to_string(), j.to_string()];
+        match slot {
+            0 => {
+                let index = input_index(index, a_index("i", "k").iter().map(|s| s.as_str()))?;
+                let (i, k) = if self.trans_a {
+                    (&index[1], &index[0])
+                } else {
+                    (&index[0], &index[1])
+                };
+                if n == 0 {
+                    return Some((index, "0".to_string()));
+                }
+                let formula = format!(
+                    r#"{}\sum_{{j=0}}^{{{}}}{}\cdot {}"#,
+                    coefficient(self.alpha),
+                    n - 1,
+                    element_symbol(&delta, &d_index(i, "j")),
+                    element_symbol(&inputs[1], &b_index(k, "j"))
+                );
+                Some((index, formula))
+            }
+            1 => {
+                let index = input_index(index, b_index("k", "j").iter().map(|s| s.as_str()))?;
+                let (k, j) = if self.trans_b {
+                    (&index[1], &index[0])
+                } else {
+                    (&index[0], &index[1])
+                };
+                if m == 0 {
+                    return Some((index, "0".to_string()));
+                }
+                let formula = format!(
+                    r#"{}\sum_{{i=0}}^{{{}}}{}\cdot {}"#,
+                    coefficient(self.alpha),
+                    m - 1,
+                    element_symbol(&inputs[0], &a_index("i", k)),
+                    element_symbol(&delta, &d_index("i", j))
+                );
+                Some((index, formula))
+            }
+            2 => {
+                let c_shape = input_shapes.get(2)?;
+                let out_vars = unit_vars(&d_index("i", "j"), &[m, n]);
+                let (c_vars, sums) = broadcast_grad(c_shape, &out_vars, &[m, n]);
+                let index = input_index(index, c_vars.iter().map(|s| s.as_str()))?;
+                let out = broadcast_out_index(c_shape, &index, &out_vars);
+                let formula = format!(
+                    "{}{}{}",
+                    coefficient(self.beta),
+                    sums,
+                    element_symbol(&delta, &out)
+                );
+                Some((index, formula))
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Expansion for Gemm {
@@ -124,3 +267,29 @@ impl Expansion for Gemm {
         Ok(tvec!(wire))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_dimension() {
+        let gemm = Gemm::new(1.0, 1.0, false, false);
+        let inputs = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let shapes = vec![vec![2, 0], vec![0, 3], vec![3]];
+        for expand in [None, Some(2)].iter() {
+            assert_eq!(
+                gemm.gen_element_value(inputs.clone(), shapes.clone(), vec![1, 2], *expand),
+                Some("0+{C}_{(2)}".to_string())
+            );
+        }
+        // no output row or column, weight gets no gradient
+        let shapes = vec![vec![0, 2], vec![2, 0], vec![0]];
+        for slot in 0..2 {
+            let (_, formula) = gemm
+                .gen_input_grad("d".to_string(), inputs.clone(), shapes.clone(), slot, None)
+                .unwrap();
+            assert_eq!(formula, "0");
+        }
+    }
+}
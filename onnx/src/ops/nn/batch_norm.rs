@@ -1,6 +1,6 @@
 use tract_hir::internal::*;
 use tract_hir::ops::nn::DataFormat;
-use tract_hir::utils::MathGen;
+use tract_hir::utils::{axis_vars, element_symbol, input_index, MathGen};
 use tract_num_traits::AsPrimitive;
 
 #[derive(Debug, Clone, new, Default, Educe)]
@@ -13,7 +13,73 @@ pub struct BatchNorm {
 }
 
 impl_dyn_hash!(BatchNorm);
-impl MathGen for BatchNorm {}
+impl MathGen for BatchNorm {
+    // inference mode, mean and variance are constants
+    fn gen_input_grad(
+        &self,
+        delta: String,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        slot: usize,
+        index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        if self.data_format != DataFormat::NCHW {
+            return None;
+        }
+        let x_shape = input_shapes.get(0)?;
+        // channel is axis 1 of input
+        if x_shape.len() < 2 {
+            return None;
+        }
+        let vars = axis_vars(x_shape.len())?;
+        let std = |c: &str| {
+            format!(
+                r#"\sqrt{{{}+{}}}"#,
+                element_symbol(&inputs[4], &[c.to_string()]),
+                self.epsilon
+            )
+        };
+        match slot {
+            0 => {
+                let index = input_index(index, vars.iter().map(|s| s.as_str()))?;
+                let formula = format!(
+                    r#"{}\frac{{{}}}{{{}}}"#,
+                    element_symbol(&delta, &index),
+                    element_symbol(&inputs[1], &[index[1].clone()]),
+                    std(&index[1])
+                );
+                Some((index, formula))
+            }
+            1 | 2 => {
+                let index = input_index(index, ["c"].iter().cloned())?;
+                let mut out = vars.clone();
+                out[1] = index[0].clone();
+                let sums: String = vars
+                    .iter()
+                    .zip(x_shape.iter())
+                    .enumerate()
+                    .filter(|(i, _)| *i != 1)
+                    .map(|(_, (v, n))| format!(r#"\sum_{{{}=0}}^{{{}}}"#, v, n - 1))
+                    .collect();
+                let d = element_symbol(&delta, &out);
+                let formula = if slot == 1 {
+                    format!(
+                        r#"{}{}\frac{{{}-{}}}{{{}}}"#,
+                        sums,
+                        d,
+                        element_symbol(&inputs[0], &out),
+                        element_symbol(&inputs[3], &index),
+                        std(&index[0])
+                    )
+                } else {
+                    format!("{}{}", sums, d)
+                };
+                Some((index, formula))
+            }
+            _ => None,
+        }
+    }
+}
 
 impl BatchNorm {
     fn to_slope_and_inter<T>(
@@ -1,7 +1,9 @@
 use tract_hir::internal::*;
 use tract_hir::ops;
 use tract_hir::ops::{cnn, nn};
-use tract_hir::utils::MathGen;
+use tract_hir::utils::{
+    axis_vars, broadcast_grad, broadcast_out_index, element_symbol, input_index, unit_vars, MathGen,
+};
 
 use crate::model::{OnnxOpRegister, ParsingContext};
 use crate::pb::NodeProto;
@@ -334,7 +336,49 @@ pub fn parametric_softplus(
 #[derive(Debug, Clone, Hash)]
 struct Prelu;
 impl_dyn_hash!(Prelu);
-impl MathGen for Prelu {}
+impl MathGen for Prelu {
+    fn gen_input_grad(
+        &self,
+        delta: String,
+        inputs: Vec<String>,
+        input_shapes: Vec<Vec<usize>>,
+        slot: usize,
+        index: Option<Vec<String>>,
+    ) -> Option<(Vec<String>, String)> {
+        let x_shape = input_shapes.get(0)?;
+        let s_shape = input_shapes.get(1)?;
+        let vars = axis_vars(x_shape.len())?;
+        let out_vars = unit_vars(&vars, x_shape);
+        let (s_vars, sums) = broadcast_grad(s_shape, &out_vars, x_shape);
+        match slot {
+            0 => {
+                let index = input_index(index, vars.iter().map(|s| s.as_str()))?;
+                let x = element_symbol(&inputs[0], &index);
+                let (s_index, _) = broadcast_grad(s_shape, &index, x_shape);
+                let formula = format!(
+                    r#"{}([{x}>0]+{}[{x}\le 0])"#,
+                    element_symbol(&delta, &index),
+                    element_symbol(&inputs[1], &s_index),
+                    x = x
+                );
+                Some((index, formula))
+            }
+            1 => {
+                let index = input_index(index, s_vars.iter().map(|s| s.as_str()))?;
+                let out = broadcast_out_index(s_shape, &index, &out_vars);
+                let x = element_symbol(&inputs[0], &out);
+                let formula = format!(
+                    r#"{}{}\cdot {x}[{x}\le 0]"#,
+                    sums,
+                    element_symbol(&delta, &out),
+                    x = x
+                );
+                Some((index, formula))
+            }
+            _ => None,
+        }
+    }
+}
 
 impl Expansion for Prelu {
     fn name(&self) -> Cow<str> {
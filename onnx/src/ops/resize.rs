@@ -125,7 +125,12 @@ impl Resize {
                     .collect());
             }
         }
-        bail!("Neither shape not scale makes sense: input_shape: {:?}, scale: {:?}, sizes: {:?}")
+        bail!(
+            "Neither shape not scale makes sense: input_shape: {:?}, scale: {:?}, sizes: {:?}",
+            input_shape,
+            input_scale,
+            input_sizes
+        )
     }
 }
 
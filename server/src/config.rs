@@ -0,0 +1,236 @@
+use std::{
+    env,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::Deserialize;
+use structopt::StructOpt;
+
+// read when --config and LATEX_SERVER_CONFIG are not given
+const DEFAULT_FILE: &str = "server.toml";
+const ENV_PREFIX: &str = "LATEX_SERVER_";
+
+// server settings, file < env < flags
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub address: String,
+    pub cors_origins: Vec<String>,
+    // bytes of one multipart request and of one field
+    pub upload_limit: usize,
+    pub field_limit: usize,
+    // fields over threshold bytes are spooled to files in spool_dir
+    pub spool_threshold: usize,
+    pub spool_dir: PathBuf,
+    // actix default, one per core, if not given
+    pub workers: Option<usize>,
+    // seconds kept model lives without request
+    pub session_ttl: u64,
+    // depth of parse_model without depth param
+    pub default_depth: Option<usize>,
+    // directory of ron files replacing bundled notation
+    pub library: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            address: "0.0.0.0:8080".to_string(),
+            cors_origins: vec!["http://localhost:3000".to_string()],
+            upload_limit: 256 << 20,
+            field_limit: 200 << 20,
+            spool_threshold: 8 << 20,
+            spool_dir: env::temp_dir().join("latex_server"),
+            workers: None,
+            session_ttl: 30 * 60,
+            default_depth: None,
+            library: None,
+        }
+    }
+}
+
+// command line flags of server
+#[derive(StructOpt, Debug)]
+#[structopt(name = "server")]
+pub struct Args {
+    /// toml config file, server.toml if exists
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// bind address ex) 0.0.0.0:8080
+    #[structopt(long)]
+    address: Option<String>,
+    /// allowed cors origin, repeat for each origin
+    #[structopt(long = "cors-origin")]
+    cors_origins: Vec<String>,
+    /// max bytes of one upload request
+    #[structopt(long)]
+    upload_limit: Option<usize>,
+    /// max bytes of one upload field
+    #[structopt(long)]
+    field_limit: Option<usize>,
+    /// bytes of field kept in memory before spooling to file
+    #[structopt(long)]
+    spool_threshold: Option<usize>,
+    /// directory of spooled upload files
+    #[structopt(long, parse(from_os_str))]
+    spool_dir: Option<PathBuf>,
+    /// number of http workers
+    #[structopt(long)]
+    workers: Option<usize>,
+    /// seconds kept model lives without request
+    #[structopt(long)]
+    session_ttl: Option<u64>,
+    /// depth of forward formula when request has none
+    #[structopt(long)]
+    default_depth: Option<usize>,
+    /// directory of notation ron files
+    #[structopt(long, parse(from_os_str))]
+    library: Option<PathBuf>,
+}
+
+// value of LATEX_SERVER_{key}
+fn env_value<T: FromStr>(key: &str) -> Result<Option<T>, Error>
+where
+    T::Err: std::fmt::Display,
+{
+    match env::var(format!("{}{}", ENV_PREFIX, key)) {
+        Ok(v) => v.parse().map(Some).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{}{}: {}", ENV_PREFIX, key, e),
+            )
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, Error> {
+        Self::from_args(Args::from_args())
+    }
+    pub fn from_args(args: Args) -> Result<Self, Error> {
+        let file = args
+            .config
+            .clone()
+            .or(env_value::<PathBuf>("CONFIG")?)
+            .or_else(|| Some(PathBuf::from(DEFAULT_FILE)).filter(|p| p.exists()));
+        let mut config = match file {
+            Some(path) => Self::from_file(path)?,
+            None => Config::default(),
+        };
+        config.apply_env()?;
+        config.apply_args(args);
+        Ok(config)
+    }
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))
+    }
+    fn apply_env(&mut self) -> Result<(), Error> {
+        if let Some(x) = env_value("ADDRESS")? {
+            self.address = x;
+        }
+        // comma separated
+        if let Some(x) = env_value::<String>("CORS_ORIGINS")? {
+            self.cors_origins = x
+                .split(',')
+                .map(|o| o.trim().to_string())
+                .filter(|o| !o.is_empty())
+                .collect();
+        }
+        if let Some(x) = env_value("UPLOAD_LIMIT")? {
+            self.upload_limit = x;
+        }
+        if let Some(x) = env_value("FIELD_LIMIT")? {
+            self.field_limit = x;
+        }
+        if let Some(x) = env_value("SPOOL_THRESHOLD")? {
+            self.spool_threshold = x;
+        }
+        if let Some(x) = env_value("SPOOL_DIR")? {
+            self.spool_dir = x;
+        }
+        if let Some(x) = env_value("WORKERS")? {
+            self.workers = Some(x);
+        }
+        if let Some(x) = env_value("SESSION_TTL")? {
+            self.session_ttl = x;
+        }
+        if let Some(x) = env_value("DEFAULT_DEPTH")? {
+            self.default_depth = Some(x);
+        }
+        if let Some(x) = env_value("LIBRARY")? {
+            self.library = Some(x);
+        }
+        Ok(())
+    }
+    fn apply_args(&mut self, args: Args) {
+        if let Some(x) = args.address {
+            self.address = x;
+        }
+        if !args.cors_origins.is_empty() {
+            self.cors_origins = args.cors_origins;
+        }
+        if let Some(x) = args.upload_limit {
+            self.upload_limit = x;
+        }
+        if let Some(x) = args.field_limit {
+            self.field_limit = x;
+        }
+        if let Some(x) = args.spool_threshold {
+            self.spool_threshold = x;
+        }
+        if let Some(x) = args.spool_dir {
+            self.spool_dir = x;
+        }
+        if let Some(x) = args.workers {
+            self.workers = Some(x);
+        }
+        if let Some(x) = args.session_ttl {
+            self.session_ttl = x;
+        }
+        if let Some(x) = args.default_depth {
+            self.default_depth = Some(x);
+        }
+        if let Some(x) = args.library {
+            self.library = Some(x);
+        }
+    }
+    pub fn session_ttl(&self) -> Duration {
+        Duration::from_secs(self.session_ttl)
+    }
+}
+
+#[test]
+fn precedence_test() {
+    let path = env::temp_dir().join(format!("latex_server_{:016x}.toml", rand::random::<u64>()));
+    std::fs::write(
+        &path,
+        "address = \"127.0.0.1:9000\"\nworkers = 1\nupload_limit = 100\nsession_ttl = 5\n",
+    )
+    .unwrap();
+    // env over file, flags over env
+    env::set_var("LATEX_SERVER_UPLOAD_LIMIT", "200");
+    env::set_var("LATEX_SERVER_WORKERS", "2");
+    let args = Args::from_iter(&[
+        "server",
+        "--config",
+        path.to_str().unwrap(),
+        "--workers",
+        "3",
+    ]);
+    let config = Config::from_args(args);
+    env::remove_var("LATEX_SERVER_UPLOAD_LIMIT");
+    env::remove_var("LATEX_SERVER_WORKERS");
+    std::fs::remove_file(&path).unwrap();
+    let config = config.unwrap();
+    assert_eq!(config.address, "127.0.0.1:9000");
+    assert_eq!(config.session_ttl, 5);
+    assert_eq!(config.upload_limit, 200);
+    assert_eq!(config.workers, Some(3));
+    // not in file, env or flags
+    assert_eq!(config.field_limit, Config::default().field_limit);
+}
@@ -0,0 +1,208 @@
+use std::error::Error as StdError;
+
+use actix_web::{dev::HttpResponseBuilder, error, http::StatusCode, HttpResponse};
+use derive_more::Display;
+use latex_gen::{LatexResult, ModelError, ParseControl, TractError};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// define network error
+#[derive(Debug, Display, Clone, Copy, PartialEq)]
+pub enum NetworkError {
+    #[display(fmt = "internal error")]
+    InternalError,
+
+    #[display(fmt = "bad request")]
+    BadClientData,
+
+    #[display(fmt = "timeout")]
+    Timeout,
+    #[display(fmt = "parse error")]
+    ParseError,
+
+    #[display(fmt = "new file failed")]
+    NewFile,
+
+    #[display(fmt = "not found")]
+    NotFound,
+
+    #[display(fmt = "malformed model")]
+    MalformedModel,
+
+    #[display(fmt = "unsupported op")]
+    UnsupportedOp,
+
+    #[display(fmt = "server busy")]
+    Busy,
+
+    #[display(fmt = "payload too large")]
+    TooLarge,
+}
+
+impl NetworkError {
+    fn code(&self) -> &'static str {
+        match *self {
+            NetworkError::InternalError => "internal_error",
+            NetworkError::BadClientData => "bad_request",
+            NetworkError::Timeout => "timeout",
+            NetworkError::ParseError => "parse_error",
+            NetworkError::NewFile => "new_file",
+            NetworkError::NotFound => "not_found",
+            NetworkError::MalformedModel => "malformed_model",
+            NetworkError::UnsupportedOp => "unsupported_op",
+            NetworkError::Busy => "server_busy",
+            NetworkError::TooLarge => "payload_too_large",
+        }
+    }
+    fn status(&self) -> StatusCode {
+        match *self {
+            NetworkError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            NetworkError::BadClientData => StatusCode::BAD_REQUEST,
+            NetworkError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            NetworkError::ParseError => StatusCode::UNPROCESSABLE_ENTITY,
+            NetworkError::NewFile => StatusCode::NOT_ACCEPTABLE,
+            NetworkError::NotFound => StatusCode::NOT_FOUND,
+            NetworkError::MalformedModel => StatusCode::BAD_REQUEST,
+            NetworkError::UnsupportedOp => StatusCode::UNPROCESSABLE_ENTITY,
+            NetworkError::Busy => StatusCode::SERVICE_UNAVAILABLE,
+            NetworkError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+    // keep message and source chain of underlying error
+    pub fn with(self, e: &(dyn StdError + 'static)) -> ApiError {
+        let mut causes = Vec::new();
+        let mut source = e.source();
+        while let Some(s) = source {
+            causes.push(s.to_string());
+            source = s.source();
+        }
+        ApiError {
+            message: e.to_string(),
+            causes,
+            ..self.into()
+        }
+    }
+    pub fn message<S: Into<String>>(self, message: S) -> ApiError {
+        ApiError {
+            message: message.into(),
+            ..self.into()
+        }
+    }
+}
+
+// node where request failed
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ErrorNode {
+    pub id: usize,
+    pub name: String,
+    pub op: String,
+}
+
+// parsed nodes when request stopped
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+}
+
+// json problem response
+#[derive(Debug, Display, Serialize, JsonSchema)]
+#[display(fmt = "{}", message)]
+pub struct ApiError {
+    #[serde(skip)]
+    kind: NetworkError,
+    code: &'static str,
+    message: String,
+    node: Option<ErrorNode>,
+    progress: Option<Progress>,
+    // original error chain, outer first
+    causes: Vec<String>,
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+    // op of node where request failed
+    pub fn node_op(&self) -> Option<&str> {
+        self.node.as_ref().map(|n| n.op.as_str())
+    }
+    // attach node of symbol map
+    pub fn at(mut self, symbol: &LatexResult, n: usize) -> Self {
+        self.node = symbol
+            .symbol_map
+            .get(n)
+            .and_then(|s| s.as_ref())
+            .map(|s| ErrorNode {
+                id: n,
+                name: s.name.clone(),
+                op: s.op_name.clone(),
+            });
+        self
+    }
+    // attach progress of parse, none if parse not started
+    pub fn progress(mut self, control: &ParseControl) -> Self {
+        let (done, total) = control.progress();
+        if total > 0 {
+            self.progress = Some(Progress { done, total });
+        }
+        self
+    }
+    // model read or parse failure, malformed file and unsupported op are told apart
+    pub fn from_model(e: &TractError) -> Self {
+        match e.downcast_ref::<ModelError>() {
+            Some(ModelError::Malformed) => NetworkError::MalformedModel.with(e.as_ref()),
+            Some(ModelError::Unsupported { node, name, op }) => ApiError {
+                node: Some(ErrorNode {
+                    id: *node,
+                    name: name.clone(),
+                    op: op.clone(),
+                }),
+                ..NetworkError::UnsupportedOp.with(e.as_ref())
+            },
+            Some(ModelError::Cancelled) => NetworkError::Timeout.with(e.as_ref()),
+            None => NetworkError::ParseError.with(e.as_ref()),
+        }
+    }
+    // io error of latex_gen, not found index or invalid request
+    pub fn from_io(e: &std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => NetworkError::NotFound.with(e),
+            std::io::ErrorKind::InvalidInput => NetworkError::BadClientData.with(e),
+            _ => NetworkError::ParseError.with(e),
+        }
+    }
+}
+
+impl From<NetworkError> for ApiError {
+    fn from(kind: NetworkError) -> Self {
+        ApiError {
+            kind,
+            code: kind.code(),
+            message: kind.to_string(),
+            node: None,
+            progress: None,
+            causes: Vec::new(),
+        }
+    }
+}
+
+impl error::ResponseError for ApiError {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponseBuilder::new(self.status_code()).json(self)
+    }
+
+    fn status_code(&self) -> StatusCode {
+        self.kind.status()
+    }
+}
+
+impl error::ResponseError for NetworkError {
+    fn error_response(&self) -> HttpResponse {
+        ApiError::from(*self).error_response()
+    }
+
+    fn status_code(&self) -> StatusCode {
+        self.status()
+    }
+}
@@ -1,208 +1,977 @@
 use actix_cors::Cors;
 use actix_multipart::Multipart;
 use actix_web::{
-    dev::HttpResponseBuilder,
-    error, get,
-    http::{header, StatusCode},
-    post,
-    web::{self, Buf},
-    App, Error, HttpResponse, HttpServer, Responder,
+    delete, dev::Service, get, http::header, post, web, App, Error, HttpRequest, HttpResponse,
+    HttpServer, Responder,
 };
 
-use derive_more::{Display, Error};
-use latex_gen::{Indexes, LatexEngine, LatexResult};
+use latex_gen::{
+    BackwardForm, GradientOption, GraphOption, Indexes, InferenceModel, JacobianForm, LatexEngine,
+    LatexNode, LatexResult, Loss, MathGen, OptimizerOption, OutputFormat, ParseControl, ParseMode,
+    SymbolLibrary, TraceInput, ValueOption,
+};
 
 use std::{
     collections::HashMap,
-    io::{Cursor, Seek, SeekFrom, Write},
+    io::{Seek, SeekFrom},
+    time::{Duration, Instant},
     usize,
 };
 
+use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
 
-use futures::{future::ok, stream::once, StreamExt, TryStreamExt};
+use futures::{channel::mpsc, future::ok, stream::once, FutureExt, StreamExt};
 
-// define network error 
-#[derive(Debug, Display, Error)]
-enum NetworkError {
-    #[display(fmt = "internal error")]
-    InternalError,
+mod config;
+mod error;
+mod metrics;
+mod openapi;
+mod pool;
+mod store;
+mod upload;
 
-    #[display(fmt = "bad request")]
-    BadClientData,
+use config::Config;
+use error::{ApiError, NetworkError};
+use metrics::Metrics;
+use pool::BlockingPool;
+use store::{lock_session, ModelStore};
+use upload::{read_multipart, take_upload};
 
-    #[display(fmt = "timeout")]
-    Timeout,
-    #[display(fmt = "parse error")]
-    ParseError,
+// parsed models are dropped over limits or after idle time of config
+const MAX_MODELS: usize = 32;
+const MAX_MODEL_BYTES: usize = 1 << 30;
+// parse and backward jobs at once and time budget of each request
+const MAX_JOBS: usize = 8;
+const REQUEST_BUDGET: Duration = Duration::from_secs(60);
+// id of kept model in parse_model answer, exposed to browser clients
+const MODEL_ID_HEADER: &str = "X-Model-Id";
 
-    #[display(fmt = "new file failed")]
-    NewFile,
+// parse model param
+#[derive(Deserialize, JsonSchema)]
+struct ParseParam {
+    depth: Option<usize>,
+    // attach numeric gradient of constants with random input from seed
+    gradient_seed: Option<u64>,
+    full_gradient: Option<bool>,
+    // json, compact, tex, markdown, html, dot, mermaid, over Accept header
+    format: Option<String>,
+    // embed onnx model proto in json answer
+    include_proto: Option<bool>,
+    // render weight and bias values as matrix or statistics
+    values: Option<bool>,
+    // use value matrix instead of symbol in forward formula
+    inline_values: Option<bool>,
+    // digits after decimal point of rendered values
+    precision: Option<usize>,
+    // rows and cols of value matrix shown before dots
+    max_rows: Option<usize>,
+    max_cols: Option<usize>,
+    // tensors with more elements only get statistics
+    max_elements: Option<usize>,
 }
 
-impl error::ResponseError for NetworkError {
-    fn error_response(&self) -> HttpResponse {
-        HttpResponseBuilder::new(self.status_code())
-            .set_header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-            .body(self.to_string())
-    }
-
-    fn status_code(&self) -> StatusCode {
-        match *self {
-            NetworkError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
-            NetworkError::BadClientData => StatusCode::BAD_REQUEST,
-            NetworkError::Timeout => StatusCode::GATEWAY_TIMEOUT,
-            NetworkError::ParseError => StatusCode::CONFLICT,
-            NetworkError::NewFile => StatusCode::NOT_ACCEPTABLE,
+impl ParseParam {
+    // value rendering of request, defaults for limits not given
+    fn value_option(&self) -> Option<ValueOption> {
+        let inline = self.inline_values.unwrap_or(false);
+        if !self.values.unwrap_or(false) && !inline {
+            return None;
         }
+        let default = ValueOption::default();
+        Some(ValueOption {
+            precision: self.precision.unwrap_or(default.precision),
+            max_rows: self.max_rows.unwrap_or(default.max_rows),
+            max_cols: self.max_cols.unwrap_or(default.max_cols),
+            max_elements: self.max_elements.unwrap_or(default.max_elements),
+            inline,
+        })
     }
 }
-type OnFile = Cursor<Vec<u8>>;
-
-// get file from multipart 
-async fn mutlipart_filelist(payload: &mut Multipart) -> Result<HashMap<String, OnFile>, Error> {
-    let mut file_list = HashMap::new();
-
-    while let Ok(Some(field)) = payload.try_next().await {
-        let content_type = field.content_disposition().unwrap();
-        let filename = content_type.get_name().unwrap();
 
-        // File::create is blocking operation, use threadpool
-
-        // Field in turn is stream of *Bytes* object
-        let data: Vec<u8> = field
-            .into_stream()
-            .filter_map(move |chunk| async { chunk.ok() })
-            .fold(Vec::new(), |mut acc, x| {
-                acc.extend_from_slice(x.bytes());
-                async { acc }
-            })
-            .await;
-
-        let mut f = Cursor::new(data);
+// media type of output format
+fn content_type(format: &OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Json | OutputFormat::CompactJson => "application/json",
+        OutputFormat::Tex => "application/x-tex; charset=utf-8",
+        OutputFormat::Markdown => "text/markdown; charset=utf-8",
+        OutputFormat::Html => "text/html; charset=utf-8",
+        OutputFormat::Dot => "text/vnd.graphviz; charset=utf-8",
+        OutputFormat::Mermaid => "text/vnd.mermaid; charset=utf-8",
+    }
+}
 
-        f.seek(SeekFrom::Start(0)).unwrap();
-        file_list.insert(filename.to_string(), f);
+// output format of media type in Accept header
+fn accept_format(media: &str) -> Option<OutputFormat> {
+    match media {
+        "application/json" | "application/*" | "*/*" => Some(OutputFormat::Json),
+        "application/x-tex" | "text/x-tex" | "application/x-latex" => Some(OutputFormat::Tex),
+        "text/markdown" => Some(OutputFormat::Markdown),
+        "text/html" => Some(OutputFormat::Html),
+        "text/vnd.graphviz" => Some(OutputFormat::Dot),
+        "text/vnd.mermaid" => Some(OutputFormat::Mermaid),
+        _ => None,
     }
-    Ok(file_list)
 }
 
-// parse model param
-#[derive(Deserialize)]
-struct ParseParam {
-    depth: Option<usize>,
+// format param first, then highest q of Accept header, pretty json if none matches
+fn negotiate(format: Option<&str>, accept: Option<&str>) -> Result<OutputFormat, ApiError> {
+    if let Some(f) = format {
+        return f
+            .parse()
+            .map_err(|e: std::io::Error| NetworkError::BadClientData.message(e.to_string()));
+    }
+    let mut ranges: Vec<(f32, &str)> = accept
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let media = parts.next().filter(|m| !m.is_empty())?;
+            let q = parts
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            Some((q, media))
+        })
+        .filter(|(q, _)| *q > 0.0)
+        .collect();
+    // stable sort keeps header order among equal q
+    ranges.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(ranges
+        .iter()
+        .find_map(|(_, media)| accept_format(media))
+        .unwrap_or(OutputFormat::Json))
 }
 
 #[post("/parse_model")]
 async fn parse_file(
     web::Query(info): web::Query<ParseParam>,
+    req: HttpRequest,
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+    config: web::Data<Config>,
+    library: web::Data<SymbolLibrary>,
+    metrics: web::Data<Metrics>,
     mut payload: Multipart,
 ) -> Result<HttpResponse, Error> {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|h| h.to_str().ok());
+    let format = negotiate(info.format.as_deref(), accept)?;
+
     // iterate over multipart stream
     //  only one file
 
-    let mut file_list = mutlipart_filelist(&mut payload).await?;
+    let mut file_list = read_multipart(&mut payload, &config, &["model"]).await?;
 
-    let mut engine = LatexEngine::new();
+    let mut engine = LatexEngine::with_library(library.get_ref().clone());
+    engine.value_option = info.value_option();
+    if let Some(seed) = info.gradient_seed {
+        let mut option = GradientOption::new(TraceInput::Random(seed));
+        option.full = info.full_gradient.unwrap_or(false);
+        engine.gradient_option = Some(option);
+    }
+    let control = ParseControl::new();
+    engine.control = Some(control.clone());
 
-    let model = file_list
-        .get_mut(&"model".to_string())
-        .ok_or(NetworkError::BadClientData)?;
+    let mut model = take_upload(&mut file_list, "model")?;
 
     // parsing model file with depth 
-    let result = engine
-        .parse_from_file(model, info.depth)
-        .map_err(|_e| NetworkError::ParseError)?;
+    let size = model.size();
+    let depth = info.depth.or(config.default_depth);
+    let include_proto = info.include_proto.unwrap_or(false)
+        && matches!(format, OutputFormat::Json | OutputFormat::CompactJson);
+    let timer = Instant::now();
+    let parsed = pool
+        .run(control, move || {
+            let session = engine
+                .session_from_file(&mut model, ParseMode::Full(depth))
+                .map_err(|e| ApiError::from_model(&e))?;
+            if !include_proto {
+                return Ok((session, None));
+            }
+            model
+                .seek(SeekFrom::Start(0))
+                .map_err(|e| ApiError::from_io(&e))?;
+            let proto = latex_gen::parse_proto_from_file(&mut model)
+                .map_err(|e| ApiError::from_model(&e))?;
+            Ok((session, Some(proto)))
+        })
+        .await;
+    metrics.observe_parse(size, timer.elapsed());
+    let (session, proto) = parsed?;
+
+    // json is serialized with id of kept model, documents are rendered before keeping it
+    let json = matches!(format, OutputFormat::Json | OutputFormat::CompactJson);
+    let (mut answer, document) = if json {
+        let answer = serde_json::to_value(&session.result)
+            .map_err(|e| NetworkError::InternalError.with(&e))?;
+        (answer, String::new())
+    } else {
+        let document = session.result.render(&format, &GraphOption::default());
+        (serde_json::Value::Null, document)
+    };
+    let id = store.insert(session, size);
+    let text = if json {
+        if let Some(proto) = proto {
+            answer["model"] =
+                serde_json::to_value(&proto).map_err(|e| NetworkError::InternalError.with(&e))?;
+        }
+        answer["model_id"] = serde_json::Value::String(id.clone());
+        let text = match format {
+            OutputFormat::Json => serde_json::to_string_pretty(&answer),
+            _ => serde_json::to_string(&answer),
+        };
+        text.map_err(|e| NetworkError::InternalError.with(&e))?
+    } else {
+        document
+    };
+    let body = once(ok::<_, Error>(web::Bytes::from(text)));
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type(&format))
+        .header(header::VARY, "Accept")
+        .header(MODEL_ID_HEADER, id)
+        .streaming(body))
+}
+
+// one server sent event
+fn sse_event<T: Serialize>(event: &str, data: &T) -> web::Bytes {
+    let data = serde_json::to_string(data).unwrap_or_default();
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+// node event of streaming parse
+#[derive(Serialize, JsonSchema)]
+struct NodeEvent<'a> {
+    id: usize,
+    name: &'a str,
+    op: &'a str,
+    symbol: &'a str,
+    forward: &'a str,
+    shape: &'a [usize],
+    done: usize,
+    total: usize,
+}
+
+// last event of streaming parse, nodes are read from kept model
+#[derive(Serialize, JsonSchema)]
+struct ParseSummary {
+    model_id: String,
+    senario: Vec<usize>,
+    nodes: usize,
+}
+
+// parse model with node events as each forward formula is generated
+#[post("/parse_model/stream")]
+async fn parse_stream(
+    web::Query(info): web::Query<ParseParam>,
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+    config: web::Data<Config>,
+    library: web::Data<SymbolLibrary>,
+    metrics: web::Data<Metrics>,
+    mut payload: Multipart,
+) -> Result<HttpResponse, Error> {
+    let mut file_list = read_multipart(&mut payload, &config, &["model"]).await?;
+    let mut model = take_upload(&mut file_list, "model")?;
+    let size = model.size();
+
+    let (tx, rx) = mpsc::unbounded::<web::Bytes>();
+    let node_tx = tx.clone();
+    // closed stream means client is gone, stop parse
+    let control = ParseControl::with_listener(move |node: &LatexNode, (done, total)| {
+        let event = NodeEvent {
+            id: node.index,
+            name: &node.name,
+            op: &node.op_name,
+            symbol: &node.symbol,
+            forward: &node.forward_value,
+            shape: &node.output_shape,
+            done,
+            total,
+        };
+        node_tx.unbounded_send(sse_event("node", &event)).is_ok()
+    });
 
-    model.seek(SeekFrom::Start(0)).unwrap();
-    //  to json 
-    let body = once(ok::<_, Error>(web::Bytes::copy_from_slice(
-        result.gen_json().as_bytes(),
-    )));
+    let mut engine = LatexEngine::with_library(library.get_ref().clone());
+    engine.value_option = info.value_option();
+    if let Some(seed) = info.gradient_seed {
+        let mut option = GradientOption::new(TraceInput::Random(seed));
+        option.full = info.full_gradient.unwrap_or(false);
+        engine.gradient_option = Some(option);
+    }
+    engine.control = Some(control.clone());
+    let depth = info.depth.or(config.default_depth);
 
-    Ok(HttpResponse::Ok().streaming(body))
+    actix_web::rt::spawn(async move {
+        let timer = Instant::now();
+        let parsed = pool
+            .run(control, move || {
+                engine
+                    .session_from_file(&mut model, ParseMode::Full(depth))
+                    .map_err(|e| ApiError::from_model(&e))
+            })
+            .await;
+        metrics.observe_parse(size, timer.elapsed());
+        let last = match parsed {
+            Ok(session) => {
+                let summary = ParseSummary {
+                    senario: session.result.senario.clone(),
+                    nodes: session.result.symbol_map.len(),
+                    model_id: store.insert(session, size),
+                };
+                sse_event("summary", &summary)
+            }
+            Err(e) => {
+                metrics.observe_failure(&e);
+                sse_event("error", &e)
+            }
+        };
+        let _ = tx.unbounded_send(last);
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .streaming(rx.map(Ok::<_, Error>)))
 }
 
 // backward params
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct BackwardParam {
     layer_node: usize,
     layer_idxs: Vec<usize>,
     weight_idxs: Vec<usize>,
     depth: Option<usize>,
+    // mse, cross_entropy, bce, l1
+    loss: Option<String>,
+    // custom ron template, #_0 is output and #_1 is target
+    loss_template: Option<String>,
+    // element or matrix
+    form: Option<String>,
+    // when given, layer_node is bias, normalization, slope or input node with this element index
+    param_idxs: Option<Vec<usize>>,
+    // sgd, momentum, rmsprop, adam
+    optimizer: Option<String>,
+    // symbol or value of learning rate
+    learning_rate: Option<String>,
+    // symbol or value of momentum, 0<=x<1
+    momentum: Option<String>,
+    // symbol or value of rmsprop decay, 0<=x<1
+    rho: Option<String>,
+    // symbol or value of adam decays, 0<=x<1
+    beta1: Option<String>,
+    beta2: Option<String>,
+    // symbol or value of epsilon, x>0
+    epsilon: Option<String>,
 }
 
 // response json struct
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, JsonSchema)]
 struct BackwardAnswer {
     node: usize,
     layer_idxs: Vec<usize>,
     weight_idxs: Vec<usize>,
     symbol: String,
     value: String,
+    loss: Option<String>,
+    update: Vec<String>,
 }
 
 // generate backprapogation fomula
 #[post("/backward")]
 async fn backward(
     web::Query(info): web::Query<BackwardParam>,
+    pool: web::Data<BlockingPool>,
+    config: web::Data<Config>,
+    library: web::Data<SymbolLibrary>,
     mut payload: Multipart,
 ) -> Result<HttpResponse, Error> {
-    let file_list = mutlipart_filelist(&mut payload).await?;
+    let mut file_list = read_multipart(&mut payload, &config, &["model", "symbol"]).await?;
 
-    // get model file
-    let mut model_file = file_list
-        .get(&"model".to_string())
-        .ok_or(NetworkError::BadClientData)?
-        .clone();
-    // get symbol map 
-    let mut raw_symbol = file_list
-        .get(&"symbol".to_string())
-        .ok_or(NetworkError::BadClientData)?
-        .clone();
+    // get model file and symbol map
+    let mut model_file = take_upload(&mut file_list, "model")?;
+    let raw_symbol = take_upload(&mut file_list, "symbol")?;
 
-    // reset file seek  
-    model_file.seek(SeekFrom::Start(0)).unwrap();
-    raw_symbol.seek(SeekFrom::Start(0)).unwrap();
+    // timeout of pool cancels engine through control
+    let control = ParseControl::new();
+    let engine_control = control.clone();
+    let result = pool
+        .run(control, move || {
+            //  get inference model
+            let model = LatexEngine::new()
+                .model_from_file(&mut model_file)
+                .map_err(|e| ApiError::from_model(&e))?;
 
-    let engine = LatexEngine::new();
+            let raw_symbol = raw_symbol
+                .into_bytes()
+                .map_err(|e| NetworkError::InternalError.with(&e))?;
+            let symbol = LatexResult::from_reader(raw_symbol)
+                .map_err(|e| NetworkError::BadClientData.with(&e))?;
 
-    //  get inference model 
-    let model = engine
-        .model_from_file(&mut model_file)
-        .map_err(|_e| NetworkError::InternalError)?;
+            // generate math ops
+            let math_ops = latex_gen::LatexEngine::math_op_vecs(&model);
 
-    let symbol =
-        LatexResult::from_reader(raw_symbol.into_inner()).map_err(|_e| NetworkError::InternalError)?;
+            run_backward(&info, &library, &model, &math_ops, &symbol, engine_control)
+        })
+        .await?;
+    // to_json
+    Ok(HttpResponse::Ok().json(result))
+}
 
-    // generate math ops 
-    let math_ops = latex_gen::LatexEngine::math_op_vecs(&model);
+// backward of kept model
+#[post("/models/{id}/backward")]
+async fn model_backward(
+    web::Path(id): web::Path<String>,
+    web::Query(info): web::Query<BackwardParam>,
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+    library: web::Data<SymbolLibrary>,
+) -> Result<HttpResponse, Error> {
+    let session = store.get(&id).ok_or(NetworkError::NotFound)?;
+    let control = ParseControl::new();
+    let engine_control = control.clone();
+    let result = pool
+        .run(control, move || {
+            let session = lock_session(&session)?;
+            run_backward(
+                &info,
+                &library,
+                session.model(),
+                session.math_ops(),
+                &session.result,
+                engine_control,
+            )
+        })
+        .await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+// optimizer of request with learning rate, momentum, rho, beta1, beta2 and epsilon
+fn optimizer_option(
+    optimizer: &Option<String>,
+    hyper: [&Option<String>; 6],
+) -> Result<Option<OptimizerOption>, ApiError> {
+    let optimizer = match optimizer {
+        Some(o) => o
+            .parse()
+            .map_err(|e| NetworkError::BadClientData.with(&e))?,
+        None => return Ok(None),
+    };
+    let [learning_rate, momentum, rho, beta1, beta2, epsilon] = hyper;
+    let option = OptimizerOption {
+        optimizer,
+        learning_rate: learning_rate.clone(),
+        momentum: momentum.clone(),
+        rho: rho.clone(),
+        beta1: beta1.clone(),
+        beta2: beta2.clone(),
+        epsilon: epsilon.clone(),
+    };
+    option
+        .validate()
+        .map_err(|e| NetworkError::BadClientData.with(&e))?;
+    Ok(Some(option))
+}
+
+// engine with loss, backward form and optimizer of request
+fn backward_engine(
+    library: &SymbolLibrary,
+    loss: (&Option<String>, &Option<String>),
+    form: &Option<String>,
+    optimizer: Option<OptimizerOption>,
+) -> Result<LatexEngine, ApiError> {
+    let mut engine = LatexEngine::with_library(library.clone());
+    engine.loss = match (loss.1.clone(), loss.0.as_ref()) {
+        (Some(t), _) => Some(Loss::Custom(t)),
+        (None, Some(l)) => Some(
+            l.parse::<Loss>()
+                .map_err(|e| NetworkError::BadClientData.with(&e))?,
+        ),
+        (None, None) => None,
+    };
+    engine.optimizer = optimizer;
+    if let Some(ref f) = form {
+        engine.backward_form = f
+            .parse::<BackwardForm>()
+            .map_err(|e| NetworkError::BadClientData.with(&e))?;
+    }
+    Ok(engine)
+}
+
+// backward formula with loss, form and optimizer of params, stopped when control is cancelled
+fn run_backward(
+    info: &BackwardParam,
+    library: &SymbolLibrary,
+    model: &InferenceModel,
+    math_ops: &Vec<Box<dyn MathGen>>,
+    symbol: &LatexResult,
+    control: ParseControl,
+) -> Result<BackwardAnswer, ApiError> {
+    let mut engine = backward_engine(
+        library,
+        (&info.loss, &info.loss_template),
+        &info.form,
+        optimizer_option(
+            &info.optimizer,
+            [
+                &info.learning_rate,
+                &info.momentum,
+                &info.rho,
+                &info.beta1,
+                &info.beta2,
+                &info.epsilon,
+            ],
+        )?,
+    )?;
+    engine.control = Some(control);
 
     let indexs = Indexes::new(info.weight_idxs.clone(), info.layer_idxs.clone());
-    let last_point = symbol.senario.last().cloned().unwrap();
+    let last_point = symbol
+        .senario
+        .last()
+        .cloned()
+        .ok_or(NetworkError::BadClientData.message("model has no layer"))?;
+    let loss = engine
+        .gen_loss(symbol, last_point)
+        .map_err(|e| NetworkError::BadClientData.with(&e))?;
     // launch back propagation 
-    let (s, v) = engine
-        .gen_each_back(
-            &math_ops,
-            &model,
-            &symbol,
+    let (s, v) = match info.param_idxs {
+        Some(ref p) => {
+            engine.gen_param_back(math_ops, model, symbol, (info.layer_node, last_point), p)
+        }
+        None => engine.gen_each_back(
+            math_ops,
+            model,
+            symbol,
             (info.layer_node, last_point),
             &indexs,
             info.depth,
-        )
-        .map_err(|_x| NetworkError::ParseError)?;
-    
+        ),
+    }
+    .map_err(|e| ApiError::from_io(&e).at(symbol, info.layer_node))?;
+    let update = engine
+        .gen_node_update(symbol, info.layer_node, &s)
+        .unwrap_or_default();
 
-    let result = BackwardAnswer {
+    Ok(BackwardAnswer {
         node: info.layer_node,
-        layer_idxs: info.layer_idxs,
-        weight_idxs: info.weight_idxs,
+        layer_idxs: info.layer_idxs.clone(),
+        weight_idxs: info.weight_idxs.clone(),
         symbol: s,
         value: v,
+        loss,
+        update,
+    })
+}
+
+// backward of every layer params
+#[derive(Deserialize, JsonSchema)]
+struct BatchParam {
+    layer_idxs: Vec<usize>,
+    weight_idxs: Vec<usize>,
+    depth: Option<usize>,
+    // mse, cross_entropy, bce, l1
+    loss: Option<String>,
+    // custom ron template, #_0 is output and #_1 is target
+    loss_template: Option<String>,
+    // element or matrix
+    form: Option<String>,
+    // sgd, momentum, rmsprop, adam
+    optimizer: Option<String>,
+    // symbol or value of learning rate
+    learning_rate: Option<String>,
+    // symbol or value of momentum, 0<=x<1
+    momentum: Option<String>,
+    // symbol or value of rmsprop decay, 0<=x<1
+    rho: Option<String>,
+    // symbol or value of adam decays, 0<=x<1
+    beta1: Option<String>,
+    beta2: Option<String>,
+    // symbol or value of epsilon, x>0
+    epsilon: Option<String>,
+    // server sent event of each node when true
+    stream: Option<bool>,
+}
+
+// backward of one node in batch
+#[derive(Serialize, JsonSchema)]
+struct NodeBackward<'a> {
+    node: usize,
+    symbol: &'a str,
+    value: &'a str,
+    update: &'a [String],
+}
+
+impl<'a> From<&'a LatexNode> for NodeBackward<'a> {
+    fn from(n: &'a LatexNode) -> Self {
+        NodeBackward {
+            node: n.index,
+            symbol: &n.backward_symbol,
+            value: &n.backward_value,
+            update: &n.update,
+        }
+    }
+}
+
+// response json struct
+#[derive(Serialize, JsonSchema)]
+struct BatchAnswer<'a> {
+    loss: Option<String>,
+    nodes: Vec<NodeBackward<'a>>,
+}
+
+// backward of every layer and parameter of kept model
+#[post("/models/{id}/backward_all")]
+async fn model_backward_all(
+    web::Path(id): web::Path<String>,
+    web::Query(info): web::Query<BatchParam>,
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+    library: web::Data<SymbolLibrary>,
+    metrics: web::Data<Metrics>,
+) -> Result<HttpResponse, Error> {
+    let session = store.get(&id).ok_or(NetworkError::NotFound)?;
+    let mut engine = backward_engine(
+        &library,
+        (&info.loss, &info.loss_template),
+        &info.form,
+        optimizer_option(
+            &info.optimizer,
+            [
+                &info.learning_rate,
+                &info.momentum,
+                &info.rho,
+                &info.beta1,
+                &info.beta2,
+                &info.epsilon,
+            ],
+        )?,
+    )?;
+    let stream = info.stream.unwrap_or(false);
+    let (tx, rx) = mpsc::unbounded::<web::Bytes>();
+    let node_tx = tx.clone();
+    let control = if stream {
+        // closed stream means client is gone, stop backward
+        ParseControl::with_listener(move |node: &LatexNode, _| {
+            node_tx
+                .unbounded_send(sse_event("node", &NodeBackward::from(node)))
+                .is_ok()
+        })
+    } else {
+        ParseControl::new()
     };
-    // to_json
-    Ok(HttpResponse::Ok().json(result))
+    engine.control = Some(control.clone());
+
+    let work = move || {
+        let session = lock_session(&session)?;
+        let mut result = session.result.clone();
+        let indexs = Indexes::new(info.weight_idxs.clone(), info.layer_idxs.clone());
+        engine
+            .gen_back_model(
+                &mut result,
+                session.model(),
+                session.math_ops(),
+                &indexs,
+                info.depth,
+            )
+            .map_err(|e| ApiError::from_io(&e))?;
+        Ok(result)
+    };
+    if !stream {
+        let result = pool.run(control, work).await?;
+        return Ok(HttpResponse::Ok().json(BatchAnswer {
+            loss: result.loss.clone(),
+            nodes: result
+                .symbol_map
+                .iter()
+                .flatten()
+                .filter(|n| !n.backward_symbol.is_empty())
+                .map(NodeBackward::from)
+                .collect(),
+        }));
+    }
+
+    actix_web::rt::spawn(async move {
+        let last = match pool.run(control, work).await {
+            Ok(result) => sse_event("summary", &serde_json::json!({ "loss": result.loss })),
+            Err(e) => {
+                metrics.observe_failure(&e);
+                sse_event("error", &e)
+            }
+        };
+        let _ = tx.unbounded_send(last);
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .streaming(rx.map(Ok::<_, Error>)))
+}
+
+// forward and backward of one node of kept model
+#[get("/models/{id}/nodes/{n}")]
+async fn model_node(
+    web::Path((id, n)): web::Path<(String, usize)>,
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+) -> Result<HttpResponse, Error> {
+    let session = store.get(&id).ok_or(NetworkError::NotFound)?;
+    // session may be held by long backward, wait off async workers
+    let node = pool
+        .run(ParseControl::new(), move || {
+            let session = lock_session(&session)?;
+            session
+                .result
+                .symbol_map
+                .get(n)
+                .cloned()
+                .flatten()
+                .ok_or(NetworkError::NotFound.message(format!("model has no node {}", n)))
+        })
+        .await?;
+    Ok(HttpResponse::Ok().json(node))
+}
+
+// input of node to expand or collapse
+#[derive(Deserialize, JsonSchema)]
+struct SlotParam {
+    slot: usize,
+}
+
+// expand input of node by one level, return changed node
+#[post("/models/{id}/nodes/{n}/expand")]
+async fn expand_node(
+    web::Path((id, n)): web::Path<(String, usize)>,
+    web::Query(info): web::Query<SlotParam>,
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+) -> Result<HttpResponse, Error> {
+    let session = store.get(&id).ok_or(NetworkError::NotFound)?;
+    let node = pool
+        .run(ParseControl::new(), move || {
+            let mut session = lock_session(&session)?;
+            let expanded = session.expand(n, info.slot);
+            expanded.map_err(|e| ApiError::from_io(&e).at(&session.result, n))?;
+            Ok(session.result.symbol_map[n].clone())
+        })
+        .await?;
+    Ok(HttpResponse::Ok().json(node))
+}
+
+// collapse input of node by one level, return changed node
+#[post("/models/{id}/nodes/{n}/collapse")]
+async fn collapse_node(
+    web::Path((id, n)): web::Path<(String, usize)>,
+    web::Query(info): web::Query<SlotParam>,
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+) -> Result<HttpResponse, Error> {
+    let session = store.get(&id).ok_or(NetworkError::NotFound)?;
+    let node = pool
+        .run(ParseControl::new(), move || {
+            let mut session = lock_session(&session)?;
+            let collapsed = session.collapse(n, info.slot);
+            collapsed.map_err(|e| ApiError::from_io(&e).at(&session.result, n))?;
+            Ok(session.result.symbol_map[n].clone())
+        })
+        .await?;
+    Ok(HttpResponse::Ok().json(node))
+}
+
+#[delete("/models/{id}")]
+async fn delete_model(
+    web::Path(id): web::Path<String>,
+    store: web::Data<ModelStore>,
+) -> Result<HttpResponse, Error> {
+    if store.remove(&id) {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(NetworkError::NotFound.into())
+    }
+}
+
+// jacobian params
+#[derive(Deserialize, JsonSchema)]
+struct JacobianParam {
+    // differentiated node, last layer if not given
+    output: Option<usize>,
+    wrt: usize,
+    // chain or matrix
+    form: Option<String>,
+}
+
+// response json struct
+#[derive(Serialize, Debug, JsonSchema)]
+struct JacobianAnswer {
+    output: usize,
+    wrt: usize,
+    symbol: String,
+    value: String,
+}
+
+// generate jacobian of output with respect to input or intermediate node
+#[post("/jacobian")]
+async fn jacobian(
+    web::Query(info): web::Query<JacobianParam>,
+    pool: web::Data<BlockingPool>,
+    config: web::Data<Config>,
+    library: web::Data<SymbolLibrary>,
+    mut payload: Multipart,
+) -> Result<HttpResponse, Error> {
+    let mut file_list = read_multipart(&mut payload, &config, &["model", "symbol"]).await?;
+
+    let mut model_file = take_upload(&mut file_list, "model")?;
+    let raw_symbol = take_upload(&mut file_list, "symbol")?;
+
+    let form = match info.form {
+        Some(ref f) => f
+            .parse::<JacobianForm>()
+            .map_err(|e| NetworkError::BadClientData.with(&e))?,
+        None => JacobianForm::default(),
+    };
+    let answer = pool
+        .run(ParseControl::new(), move || {
+            let engine = LatexEngine::with_library(library.get_ref().clone());
+            let model = engine
+                .model_from_file(&mut model_file)
+                .map_err(|e| ApiError::from_model(&e))?;
+            let raw_symbol = raw_symbol
+                .into_bytes()
+                .map_err(|e| NetworkError::InternalError.with(&e))?;
+            let symbol = LatexResult::from_reader(raw_symbol)
+                .map_err(|e| NetworkError::BadClientData.with(&e))?;
+            let math_ops = latex_gen::LatexEngine::math_op_vecs(&model);
+
+            let output = info
+                .output
+                .or(symbol.senario.last().cloned())
+                .ok_or(NetworkError::BadClientData.message("model has no layer"))?;
+            let (s, v) = engine
+                .gen_jacobian(&math_ops, &model, &symbol, (output, info.wrt), &form)
+                .map_err(|e| ApiError::from_io(&e).at(&symbol, output))?;
+            Ok(JacobianAnswer {
+                output,
+                wrt: info.wrt,
+                symbol: s,
+                value: v,
+            })
+        })
+        .await?;
+
+    Ok(HttpResponse::Ok().json(answer))
+}
+
+// jacobian of kept model
+#[post("/models/{id}/jacobian")]
+async fn model_jacobian(
+    web::Path(id): web::Path<String>,
+    web::Query(info): web::Query<JacobianParam>,
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+) -> Result<HttpResponse, Error> {
+    let session = store.get(&id).ok_or(NetworkError::NotFound)?;
+    let form = match info.form {
+        Some(ref f) => f
+            .parse::<JacobianForm>()
+            .map_err(|e| NetworkError::BadClientData.with(&e))?,
+        None => JacobianForm::default(),
+    };
+    let answer = pool
+        .run(ParseControl::new(), move || {
+            let session = lock_session(&session)?;
+            let output = info
+                .output
+                .or(session.result.senario.last().cloned())
+                .ok_or(NetworkError::BadClientData.message("model has no layer"))?;
+            let (s, v) = session
+                .jacobian(output, info.wrt, &form)
+                .map_err(|e| ApiError::from_io(&e).at(&session.result, output))?;
+            Ok(JacobianAnswer {
+                output,
+                wrt: info.wrt,
+                symbol: s,
+                value: v,
+            })
+        })
+        .await?;
+    Ok(HttpResponse::Ok().json(answer))
+}
+
+// openapi document of /v1 routes
+#[get("/openapi.json")]
+async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(openapi::document())
+}
+
+// api routes, served under /v1 and at root for old clients
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(openapi_json)
+        .service(backward)
+        .service(jacobian)
+        .service(model_jacobian)
+        .service(parse_file)
+        .service(parse_stream)
+        .service(model_backward)
+        .service(model_backward_all)
+        .service(model_node)
+        .service(expand_node)
+        .service(collapse_node)
+        .service(delete_model);
+}
+
+// cors for react client, which reads id of kept model from header
+fn cors(config: &Config) -> Cors {
+    config
+        .cors_origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .expose_headers(vec![MODEL_ID_HEADER])
+}
+
+// liveness of process
+#[get("/healthz")]
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+// ready when jobs and kept models have room and uploads can be spooled
+#[get("/readyz")]
+async fn readyz(
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let checks = [
+        ("jobs", !pool.is_full()),
+        ("store", store.has_room()),
+        ("spool_dir", config.spool_dir.is_dir()),
+    ];
+    let ready = checks.iter().all(|(_, ok)| *ok);
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "checks": checks.iter().cloned().collect::<HashMap<&str, bool>>(),
+    });
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+// prometheus text exposition
+#[get("/metrics")]
+async fn metrics_text(
+    metrics: web::Data<Metrics>,
+    store: web::Data<ModelStore>,
+    pool: web::Data<BlockingPool>,
+) -> Result<HttpResponse, Error> {
+    let body = metrics
+        .render(&store, &pool)
+        .map_err(|e| NetworkError::InternalError.with(&e))?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body))
 }
 
 #[get("/")]
@@ -219,20 +988,133 @@ async fn echo(req_body: String) -> impl Responder {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info");
-    std::fs::create_dir_all("./tmp").unwrap();
+    if std::env::var("RUST_LOG").is_err() {
+        std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info");
+    }
+    let config = Config::load()?;
+    std::fs::create_dir_all(&config.spool_dir)?;
+    let library = match config.library {
+        Some(ref dir) => SymbolLibrary::from_dir(dir)?,
+        None => LatexEngine::new().symbol_library,
+    };
 
-    HttpServer::new(|| {
-        // cors for react client 
-        let cors = Cors::default().allowed_origin("http://localhost:3000");
+    let store = web::Data::new(ModelStore::new(
+        config.session_ttl(),
+        MAX_MODELS,
+        MAX_MODEL_BYTES,
+    ));
+    let pool = web::Data::new(BlockingPool::new(MAX_JOBS, REQUEST_BUDGET));
+    let library = web::Data::new(library);
+    let address = config.address.clone();
+    let workers = config.workers;
+    let config = web::Data::new(config);
+    let metrics = web::Data::new(
+        Metrics::new().map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
+    );
+    let mut server = HttpServer::new(move || {
+        let cors = cors(&config);
+        let request_metrics = metrics.clone();
         App::new()
             .wrap(cors)
+            // count and time every request by route pattern
+            .wrap_fn(move |req, srv| {
+                let timer = Instant::now();
+                let method = req.method().to_string();
+                let route = req
+                    .match_pattern()
+                    .unwrap_or_else(|| "unmatched".to_string());
+                let metrics = request_metrics.clone();
+                srv.call(req).map(move |res| {
+                    if let Ok(ref r) = res {
+                        metrics.observe_request(
+                            &method,
+                            &route,
+                            r.status().as_u16(),
+                            timer.elapsed(),
+                        );
+                        let error = r.response().error();
+                        if let Some(e) = error.and_then(|e| e.as_error::<ApiError>()) {
+                            metrics.observe_failure(e);
+                        } else if let Some(e) = error.and_then(|e| e.as_error::<NetworkError>()) {
+                            metrics.observe_failure(&ApiError::from(*e));
+                        }
+                    }
+                    res
+                })
+            })
+            .app_data(store.clone())
+            .app_data(pool.clone())
+            .app_data(config.clone())
+            .app_data(library.clone())
+            .app_data(metrics.clone())
+            .service(healthz)
+            .service(readyz)
+            .service(metrics_text)
             .service(hello)
             .service(echo)
-            .service(backward)
-            .service(parse_file)
-    })
-    .bind("0.0.0.0:8080")?
-    .run()
-    .await
+            .service(web::scope("/v1").configure(routes))
+            .configure(routes)
+    });
+    if let Some(n) = workers {
+        server = server.workers(n);
+    }
+    server.bind(address)?.run().await
+}
+
+#[test]
+fn negotiate_test() {
+    // format param wins over Accept header
+    assert_eq!(
+        negotiate(Some("tex"), Some("text/html")).unwrap(),
+        OutputFormat::Tex
+    );
+    assert!(negotiate(Some("pdf"), None).is_err());
+    assert_eq!(negotiate(None, None).unwrap(), OutputFormat::Json);
+    // highest q first, zero q is refused
+    assert_eq!(
+        negotiate(None, Some("text/html;q=0.5, text/markdown")).unwrap(),
+        OutputFormat::Markdown
+    );
+    assert_eq!(
+        negotiate(None, Some("text/markdown;q=0, text/html;q=0.2")).unwrap(),
+        OutputFormat::Html
+    );
+    // header order among equal q, unknown media skipped
+    assert_eq!(
+        negotiate(None, Some("image/png, text/vnd.mermaid, text/html")).unwrap(),
+        OutputFormat::Mermaid
+    );
+    assert_eq!(
+        negotiate(None, Some("image/png")).unwrap(),
+        OutputFormat::Json
+    );
+}
+
+#[test]
+fn cors_test() {
+    use actix_web::test;
+    let config = Config {
+        cors_origins: vec!["http://localhost:3000".to_string()],
+        ..Config::default()
+    };
+    actix_web::rt::System::new("test").block_on(async move {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(cors(&config))
+                .route("/", web::get().to(|| HttpResponse::Ok().finish())),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(header::ORIGIN, "http://localhost:3000")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        let exposed = res
+            .headers()
+            .get(header::ACCESS_CONTROL_EXPOSE_HEADERS)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_lowercase();
+        assert!(exposed.contains("x-model-id"), "{}", exposed);
+    });
 }
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+
+use crate::{error::ApiError, pool::BlockingPool, store::ModelStore};
+
+// prometheus metrics of server
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latency: HistogramVec,
+    parse_seconds: Histogram,
+    model_bytes: Histogram,
+    failures: IntCounterVec,
+    kept_models: IntGauge,
+    kept_bytes: IntGauge,
+    running_jobs: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("latex".to_string()), None)?;
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "requests by route and status"),
+            &["method", "route", "status"],
+        )?;
+        let latency = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "latency by route"),
+            &["method", "route"],
+        )?;
+        let parse_seconds = Histogram::with_opts(
+            HistogramOpts::new("parse_duration_seconds", "time to parse one model")
+                .buckets(exponential_buckets(0.05, 2.0, 12)?),
+        )?;
+        let model_bytes = Histogram::with_opts(
+            HistogramOpts::new("model_size_bytes", "size of uploaded models")
+                .buckets(exponential_buckets(1024.0, 4.0, 11)?),
+        )?;
+        let failures = IntCounterVec::new(
+            Opts::new("failures_total", "failed requests by op of failed node"),
+            &["op", "code"],
+        )?;
+        let kept_models = IntGauge::new("kept_models", "models kept in session store")?;
+        let kept_bytes = IntGauge::new("kept_model_bytes", "upload bytes of kept models")?;
+        let running_jobs = IntGauge::new("running_jobs", "parse and backward jobs running")?;
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(latency.clone()))?;
+        registry.register(Box::new(parse_seconds.clone()))?;
+        registry.register(Box::new(model_bytes.clone()))?;
+        registry.register(Box::new(failures.clone()))?;
+        registry.register(Box::new(kept_models.clone()))?;
+        registry.register(Box::new(kept_bytes.clone()))?;
+        registry.register(Box::new(running_jobs.clone()))?;
+        Ok(Metrics {
+            registry,
+            requests,
+            latency,
+            parse_seconds,
+            model_bytes,
+            failures,
+            kept_models,
+            kept_bytes,
+            running_jobs,
+        })
+    }
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.latency
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+    // op is none when error is not on a node, ex) malformed model
+    pub fn observe_failure(&self, e: &ApiError) {
+        self.failures
+            .with_label_values(&[e.node_op().unwrap_or("none"), e.code()])
+            .inc();
+    }
+    pub fn observe_parse(&self, size: usize, elapsed: Duration) {
+        self.model_bytes.observe(size as f64);
+        self.parse_seconds.observe(elapsed.as_secs_f64());
+    }
+    // text exposition with store and pool usage at scrape time
+    pub fn render(&self, store: &ModelStore, pool: &BlockingPool) -> prometheus::Result<String> {
+        let (models, bytes) = store.usage();
+        self.kept_models.set(models as i64);
+        self.kept_bytes.set(bytes as i64);
+        self.running_jobs.set(pool.running() as i64);
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
@@ -0,0 +1,371 @@
+use latex_gen::{DebugValue, LatexNode, LatexResult};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    error::ApiError, BackwardAnswer, BackwardParam, BatchAnswer, BatchParam, JacobianAnswer,
+    JacobianParam, NodeBackward, NodeEvent, ParseParam, ParseSummary, SlotParam,
+};
+
+// request body of operation
+enum Body {
+    Empty,
+    // uploaded files of multipart fields
+    Multipart(&'static [&'static str]),
+}
+
+// openapi document built from request and response types
+struct Document {
+    gen: SchemaGenerator,
+    paths: Map<String, Value>,
+}
+
+impl Document {
+    fn new() -> Self {
+        Document {
+            gen: SchemaSettings::openapi3().into_generator(),
+            paths: Map::new(),
+        }
+    }
+    // reference to component schema of type
+    fn schema<T: JsonSchema>(&mut self) -> Value {
+        serde_json::to_value(self.gen.subschema_for::<T>()).unwrap_or_default()
+    }
+    // fields of type as query parameters
+    fn query<T: JsonSchema>(&mut self) -> Vec<Value> {
+        let root = serde_json::to_value(self.gen.root_schema_for::<T>().schema).unwrap_or_default();
+        let required = root["required"].as_array().cloned().unwrap_or_default();
+        root["properties"]
+            .as_object()
+            .map(|props| {
+                props
+                    .iter()
+                    .map(|(name, schema)| {
+                        json!({
+                            "name": name,
+                            "in": "query",
+                            "required": required.contains(&json!(name)),
+                            "schema": schema,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    fn json<T: JsonSchema>(&mut self) -> Value {
+        json!({
+            "description": "ok",
+            "content": { "application/json": { "schema": self.schema::<T>() } },
+        })
+    }
+    fn add(
+        &mut self,
+        method: &str,
+        path: &str,
+        summary: &str,
+        parameters: Vec<Value>,
+        body: Body,
+        response: Value,
+    ) {
+        let error = self.json::<ApiError>();
+        let mut op = json!({
+            "summary": summary,
+            "parameters": parameters,
+            "responses": { "200": response, "default": error },
+        });
+        if let Body::Multipart(fields) = body {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|f| (f.to_string(), json!({"type": "string", "format": "binary"})))
+                .collect();
+            op["requestBody"] = json!({
+                "required": true,
+                "content": {
+                    "multipart/form-data": {
+                        "schema": {"type": "object", "required": fields, "properties": properties}
+                    }
+                },
+            });
+        }
+        self.paths
+            .entry(path.to_string())
+            .or_insert_with(|| json!({}))[method] = op;
+    }
+}
+
+fn path_param(name: &str) -> Value {
+    let schema = if name == "id" {
+        json!({"type": "string"})
+    } else {
+        json!({"type": "integer", "minimum": 0})
+    };
+    json!({"name": name, "in": "path", "required": true, "schema": schema})
+}
+
+// parameters of route under /models/{id}
+fn model_params(params: Vec<Value>) -> Vec<Value> {
+    let mut result = vec![path_param("id")];
+    result.extend(params);
+    result
+}
+
+// server sent events with data schemas of each event
+fn events(doc: &mut Document, events: Vec<(&str, Value)>) -> Value {
+    let data: Vec<Value> = events
+        .into_iter()
+        .map(|(name, schema)| json!({"title": name, "allOf": [schema]}))
+        .collect();
+    json!({
+        "description": "server sent events, data of each event is json",
+        "content": { "text/event-stream": { "schema": {"oneOf": data} } },
+        "x-error-event": doc.schema::<ApiError>(),
+    })
+}
+
+// openapi 3 document of routes, served under /v1
+pub fn document() -> Value {
+    let mut doc = Document::new();
+
+    let parse_params = doc.query::<ParseParam>();
+    let parsed = doc.schema::<LatexResult>();
+    let mut response = json!({
+        "description": "ok, format param or Accept header picks content, X-Model-Id has id of kept model",
+        "content": { "application/json": { "schema": { "allOf": [
+            parsed,
+            {"type": "object", "properties": {
+                "model_id": {"type": "string"},
+                "model": {"type": "object", "description": "onnx model proto, with include_proto"},
+            }},
+        ]}}},
+    });
+    for media in [
+        "application/x-tex",
+        "text/markdown",
+        "text/html",
+        "text/vnd.graphviz",
+        "text/vnd.mermaid",
+    ]
+    .iter()
+    {
+        response["content"][*media] = json!({"schema": {"type": "string"}});
+    }
+    doc.add(
+        "post",
+        "/parse_model",
+        "parse model and keep it for later requests",
+        parse_params.clone(),
+        Body::Multipart(&["model"]),
+        response,
+    );
+    let node_event = doc.schema::<NodeEvent<'static>>();
+    let summary = doc.schema::<ParseSummary>();
+    let stream = events(&mut doc, vec![("node", node_event), ("summary", summary)]);
+    doc.add(
+        "post",
+        "/parse_model/stream",
+        "parse model with event of each node",
+        parse_params,
+        Body::Multipart(&["model"]),
+        stream,
+    );
+
+    let backward_params = doc.query::<BackwardParam>();
+    let answer = doc.json::<BackwardAnswer>();
+    doc.add(
+        "post",
+        "/backward",
+        "backward formula of one node from uploaded model and symbol map",
+        backward_params.clone(),
+        Body::Multipart(&["model", "symbol"]),
+        answer.clone(),
+    );
+    let params = model_params(backward_params);
+    doc.add(
+        "post",
+        "/models/{id}/backward",
+        "backward formula of one node of kept model",
+        params,
+        Body::Empty,
+        answer,
+    );
+
+    let batch_params = doc.query::<BatchParam>();
+    let params = model_params(batch_params);
+    let mut batch = doc.json::<BatchAnswer<'static>>();
+    let node_backward = doc.schema::<NodeBackward<'static>>();
+    let batch_summary = json!({"type": "object", "properties": {"loss": {"type": "string"}}});
+    let batch_stream = events(
+        &mut doc,
+        vec![("node", node_backward), ("summary", batch_summary)],
+    );
+    batch["content"]["text/event-stream"] = batch_stream["content"]["text/event-stream"].clone();
+    doc.add(
+        "post",
+        "/models/{id}/backward_all",
+        "backward of every layer and parameter of kept model, events when stream is true",
+        params,
+        Body::Empty,
+        batch,
+    );
+
+    let jacobian_params = doc.query::<JacobianParam>();
+    let jacobian = doc.json::<JacobianAnswer>();
+    doc.add(
+        "post",
+        "/jacobian",
+        "jacobian of output node with respect to input or intermediate node",
+        jacobian_params.clone(),
+        Body::Multipart(&["model", "symbol"]),
+        jacobian.clone(),
+    );
+    let params = model_params(jacobian_params);
+    doc.add(
+        "post",
+        "/models/{id}/jacobian",
+        "jacobian of output node with respect to input or intermediate node of kept model",
+        params,
+        Body::Empty,
+        jacobian,
+    );
+
+    let node = doc.json::<LatexNode>();
+    let params = model_params(vec![path_param("n")]);
+    doc.add(
+        "get",
+        "/models/{id}/nodes/{n}",
+        "forward and backward of one node of kept model",
+        params.clone(),
+        Body::Empty,
+        node.clone(),
+    );
+    let mut slot_params = params;
+    slot_params.extend(doc.query::<SlotParam>());
+    for (action, summary) in [
+        ("expand", "expand input of node by one level"),
+        ("collapse", "collapse input of node by one level"),
+    ]
+    .iter()
+    {
+        doc.add(
+            "post",
+            &format!("/models/{{id}}/nodes/{{n}}/{}", action),
+            summary,
+            slot_params.clone(),
+            Body::Empty,
+            node.clone(),
+        );
+    }
+    let params = model_params(vec![]);
+    doc.add(
+        "delete",
+        "/models/{id}",
+        "drop kept model",
+        params,
+        Body::Empty,
+        json!({"description": "removed"}),
+    );
+    let removed = &mut doc.paths["/models/{id}"]["delete"]["responses"];
+    removed["204"] = removed["200"].take();
+    if let Some(r) = removed.as_object_mut() {
+        r.remove("200");
+    }
+    // attribute values of nodes, listed for clients reading op_attributes
+    doc.schema::<DebugValue>();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "onnx latex server",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{"url": "/v1"}],
+        "paths": doc.paths,
+        "components": { "schemas": doc.gen.take_definitions() },
+    })
+}
+
+// every "$ref" in value
+#[cfg(test)]
+fn refs(value: &Value, found: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map.iter() {
+                match (k.as_str(), v) {
+                    ("$ref", Value::String(r)) => found.push(r.clone()),
+                    _ => refs(v, found),
+                }
+            }
+        }
+        Value::Array(list) => list.iter().for_each(|v| refs(v, found)),
+        _ => {}
+    }
+}
+
+#[test]
+fn document_test() {
+    use actix_web::{test, App};
+    let doc = document();
+    let mut operations: Vec<(String, String)> = doc["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, ops)| {
+            ops.as_object()
+                .unwrap()
+                .keys()
+                .map(move |m| (m.clone(), path.clone()))
+        })
+        .collect();
+    operations.sort();
+    // same as routes of server
+    let mut expected: Vec<(String, String)> = [
+        ("delete", "/models/{id}"),
+        ("get", "/models/{id}/nodes/{n}"),
+        ("post", "/backward"),
+        ("post", "/jacobian"),
+        ("post", "/models/{id}/backward"),
+        ("post", "/models/{id}/backward_all"),
+        ("post", "/models/{id}/jacobian"),
+        ("post", "/models/{id}/nodes/{n}/collapse"),
+        ("post", "/models/{id}/nodes/{n}/expand"),
+        ("post", "/parse_model"),
+        ("post", "/parse_model/stream"),
+    ]
+    .iter()
+    .map(|(m, p)| (m.to_string(), p.to_string()))
+    .collect();
+    expected.sort();
+    assert_eq!(operations, expected);
+
+    let mut found = Vec::new();
+    refs(&doc, &mut found);
+    assert!(!found.is_empty());
+    for r in found.iter() {
+        let name = r.trim_start_matches("#/components/schemas/");
+        assert!(
+            doc["components"]["schemas"].get(name).is_some(),
+            "{} is not in components",
+            r
+        );
+    }
+
+    // documented paths are served, handler without app data fails but is matched
+    actix_web::rt::System::new("test").block_on(async move {
+        let mut app = test::init_service(App::new().configure(crate::routes)).await;
+        for (method, path) in operations.iter() {
+            let uri = path.replace("{id}", "0").replace("{n}", "0");
+            let req = match method.as_str() {
+                "get" => test::TestRequest::get(),
+                "delete" => test::TestRequest::delete(),
+                _ => test::TestRequest::post(),
+            };
+            let res = test::call_service(&mut app, req.uri(&uri).to_request()).await;
+            assert_ne!(res.status(), 404, "{} {} is not routed", method, path);
+        }
+        let req = test::TestRequest::get().uri("/not_routed").to_request();
+        assert_eq!(test::call_service(&mut app, req).await.status(), 404);
+    });
+}
@@ -0,0 +1,89 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use actix_web::{error::BlockingError, rt::time::timeout, web};
+use latex_gen::ParseControl;
+
+use crate::error::{ApiError, NetworkError};
+
+// frees job slot when blocking work really ends
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// cancels work when request is dropped, ex) client disconnect or timeout
+struct CancelGuard {
+    control: ParseControl,
+    finished: bool,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.control.cancel();
+        }
+    }
+}
+
+// parse and backward work off async workers, with job limit and time budget
+pub struct BlockingPool {
+    running: Arc<AtomicUsize>,
+    max_jobs: usize,
+    budget: Duration,
+}
+
+impl BlockingPool {
+    pub fn new(max_jobs: usize, budget: Duration) -> Self {
+        BlockingPool {
+            running: Arc::new(AtomicUsize::new(0)),
+            max_jobs,
+            budget,
+        }
+    }
+    pub fn running(&self) -> usize {
+        self.running.load(Ordering::SeqCst)
+    }
+    pub fn is_full(&self) -> bool {
+        self.running() >= self.max_jobs
+    }
+    pub async fn run<F, T>(&self, control: ParseControl, f: F) -> Result<T, ApiError>
+    where
+        F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+        T: Send + 'static,
+    {
+        if self.running.fetch_add(1, Ordering::SeqCst) >= self.max_jobs {
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            return Err(NetworkError::Busy.message(format!("{} jobs are running", self.max_jobs)));
+        }
+        let slot = Slot(self.running.clone());
+        let mut guard = CancelGuard {
+            control: control.clone(),
+            finished: false,
+        };
+        let job = web::block(move || {
+            let _slot = slot;
+            f()
+        });
+        let result = match timeout(self.budget, job).await {
+            Ok(Ok(t)) => Ok(t),
+            Ok(Err(BlockingError::Error(e))) => Err(e),
+            Ok(Err(BlockingError::Canceled)) => {
+                Err(NetworkError::InternalError.message("blocking job canceled"))
+            }
+            Err(_) => Err(NetworkError::Timeout
+                .message(format!("time budget of {:?} exceeded", self.budget))
+                .progress(&control)),
+        };
+        guard.finished = result.is_ok();
+        result
+    }
+}
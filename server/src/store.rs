@@ -0,0 +1,104 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use latex_gen::ExpandSession;
+
+use crate::error::{ApiError, NetworkError};
+
+// session of kept model, panic while holding it poisons the lock
+pub fn lock_session(
+    session: &Mutex<ExpandSession>,
+) -> Result<MutexGuard<'_, ExpandSession>, ApiError> {
+    session
+        .lock()
+        .map_err(|_| NetworkError::InternalError.message("model session is poisoned"))
+}
+
+// parsed model with its upload size
+struct StoredModel {
+    session: Arc<Mutex<ExpandSession>>,
+    size: usize,
+    last_used: Instant,
+}
+
+// parsed models kept between requests, oldest are dropped over limits
+pub struct ModelStore {
+    models: Mutex<HashMap<String, StoredModel>>,
+    ttl: Duration,
+    max_models: usize,
+    max_bytes: usize,
+}
+
+impl ModelStore {
+    pub fn new(ttl: Duration, max_models: usize, max_bytes: usize) -> Self {
+        ModelStore {
+            models: Mutex::new(HashMap::new()),
+            ttl,
+            max_models,
+            max_bytes,
+        }
+    }
+    // store session and return its id
+    pub fn insert(&self, session: ExpandSession, size: usize) -> String {
+        let mut models = self.models.lock().unwrap();
+        let ttl = self.ttl;
+        models.retain(|_, m| m.last_used.elapsed() < ttl);
+        // least recently used first
+        while !models.is_empty()
+            && (models.len() >= self.max_models
+                || models.values().map(|m| m.size).sum::<usize>() + size > self.max_bytes)
+        {
+            let oldest = models
+                .iter()
+                .min_by_key(|(_, m)| m.last_used)
+                .map(|(id, _)| id.clone())
+                .unwrap();
+            models.remove(&oldest);
+        }
+        let mut id = format!("{:016x}", rand::random::<u64>());
+        while models.contains_key(&id) {
+            id = format!("{:016x}", rand::random::<u64>());
+        }
+        models.insert(
+            id.clone(),
+            StoredModel {
+                session: Arc::new(Mutex::new(session)),
+                size,
+                last_used: Instant::now(),
+            },
+        );
+        id
+    }
+    // session of id, None if not found or expired
+    pub fn get(&self, id: &str) -> Option<Arc<Mutex<ExpandSession>>> {
+        let mut models = self.models.lock().unwrap();
+        let expired = models
+            .get(id)
+            .map(|m| m.last_used.elapsed() >= self.ttl)
+            .unwrap_or(false);
+        if expired {
+            models.remove(id);
+            return None;
+        }
+        models.get_mut(id).map(|m| {
+            m.last_used = Instant::now();
+            m.session.clone()
+        })
+    }
+    // (kept models, upload bytes of kept models)
+    pub fn usage(&self) -> (usize, usize) {
+        let models = self.models.lock().unwrap();
+        (models.len(), models.values().map(|m| m.size).sum())
+    }
+    // room for one more model without eviction
+    pub fn has_room(&self) -> bool {
+        let (models, bytes) = self.usage();
+        models < self.max_models && bytes < self.max_bytes
+    }
+    pub fn remove(&self, id: &str) -> bool {
+        self.models.lock().unwrap().remove(id).is_some()
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use actix_multipart::Multipart;
+use actix_web::web;
+use futures::{StreamExt, TryStreamExt};
+
+use crate::{
+    config::Config,
+    error::{ApiError, NetworkError},
+};
+
+// uploaded field, kept in memory or spooled to temp file over threshold
+pub enum Upload {
+    Memory(Cursor<Vec<u8>>),
+    Disk {
+        file: File,
+        path: PathBuf,
+        size: usize,
+    },
+}
+
+impl Upload {
+    pub fn size(&self) -> usize {
+        match self {
+            Upload::Memory(c) => c.get_ref().len(),
+            Upload::Disk { size, .. } => *size,
+        }
+    }
+    pub fn into_bytes(mut self) -> io::Result<Vec<u8>> {
+        if let Upload::Memory(ref mut c) = self {
+            return Ok(std::mem::take(c.get_mut()));
+        }
+        let mut data = Vec::with_capacity(self.size());
+        self.seek(SeekFrom::Start(0))?;
+        self.read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+impl Read for Upload {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Upload::Memory(c) => c.read(buf),
+            Upload::Disk { file, .. } => file.read(buf),
+        }
+    }
+}
+
+impl Seek for Upload {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Upload::Memory(c) => c.seek(pos),
+            Upload::Disk { file, .. } => file.seek(pos),
+        }
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        if let Upload::Disk { path, .. } = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// temp file of field, named after sanitized upload name, removed on drop
+fn spool_file(config: &Config, filename: Option<&str>) -> Result<Upload, ApiError> {
+    let name = sanitize_filename::sanitize(filename.unwrap_or("upload"));
+    let path = config
+        .spool_dir
+        .join(format!("{:016x}-{}", rand::random::<u64>(), name));
+    // read back by model reader after writing
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(|e| NetworkError::NewFile.with(&e))?;
+    Ok(Upload::Disk {
+        file,
+        path,
+        size: 0,
+    })
+}
+
+// append to spooled file, blocking write on threadpool
+async fn write_chunk(upload: &mut Upload, data: Vec<u8>) -> Result<(), ApiError> {
+    if let Upload::Disk { file, size, .. } = upload {
+        let len = data.len();
+        let mut file = file
+            .try_clone()
+            .map_err(|e| NetworkError::NewFile.with(&e))?;
+        web::block(move || file.write_all(&data))
+            .await
+            .map_err(|e| NetworkError::NewFile.message(e.to_string()))?;
+        *size += len;
+    }
+    Ok(())
+}
+
+// read fields of multipart within limits of config, only names in fields are accepted
+pub async fn read_multipart(
+    payload: &mut Multipart,
+    config: &Config,
+    fields: &[&str],
+) -> Result<HashMap<String, Upload>, ApiError> {
+    let mut file_list = HashMap::new();
+    let mut total = 0;
+    let bad =
+        |e: actix_multipart::MultipartError| NetworkError::BadClientData.message(e.to_string());
+
+    while let Some(mut field) = payload.try_next().await.map_err(bad)? {
+        let content_type = field
+            .content_disposition()
+            .ok_or(NetworkError::BadClientData.message("field without content disposition"))?;
+        let name = content_type
+            .get_name()
+            .ok_or(NetworkError::BadClientData.message("field without name"))?
+            .to_string();
+        if !fields.contains(&name.as_str()) {
+            return Err(NetworkError::BadClientData.message(format!(
+                "unknown field {}, expected {}",
+                name,
+                fields.join(", ")
+            )));
+        }
+        if file_list.contains_key(&name) {
+            return Err(NetworkError::BadClientData.message(format!("duplicate field {}", name)));
+        }
+
+        let mut size = 0;
+        let mut data: Vec<u8> = Vec::new();
+        let mut spool: Option<Upload> = None;
+        // Field in turn is stream of *Bytes* object
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(bad)?;
+            size += chunk.len();
+            total += chunk.len();
+            if size > config.field_limit {
+                return Err(NetworkError::TooLarge.message(format!(
+                    "field {} is over {} bytes",
+                    name, config.field_limit
+                )));
+            }
+            if total > config.upload_limit {
+                return Err(NetworkError::TooLarge
+                    .message(format!("upload is over {} bytes", config.upload_limit)));
+            }
+            data.extend_from_slice(&chunk);
+            if spool.is_none() && data.len() > config.spool_threshold {
+                spool = Some(spool_file(config, content_type.get_filename())?);
+            }
+            if let Some(ref mut upload) = spool {
+                write_chunk(upload, std::mem::take(&mut data)).await?;
+            }
+        }
+
+        let mut upload = spool.unwrap_or(Upload::Memory(Cursor::new(data)));
+        upload
+            .seek(SeekFrom::Start(0))
+            .map_err(|e| NetworkError::NewFile.with(&e))?;
+        file_list.insert(name, upload);
+    }
+    Ok(file_list)
+}
+
+// uploaded file of field
+pub fn take_upload(
+    file_list: &mut HashMap<String, Upload>,
+    name: &str,
+) -> Result<Upload, ApiError> {
+    file_list
+        .remove(name)
+        .ok_or(NetworkError::BadClientData.message(format!("missing {} field", name)))
+}
+
+// multipart request of (name, filename, data) fields, sent in small chunks
+#[cfg(test)]
+fn multipart(fields: &[(&str, &str, Vec<u8>)]) -> Multipart {
+    use actix_web::{
+        error::PayloadError,
+        http::{header, HeaderMap, HeaderValue},
+    };
+    let mut body = Vec::new();
+    for (name, filename, data) in fields.iter() {
+        body.extend_from_slice(
+            format!(
+                "--b0\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\r\n",
+                name, filename
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"--b0--\r\n");
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("multipart/form-data; boundary=b0"),
+    );
+    let chunks: Vec<Result<web::Bytes, PayloadError>> = body
+        .chunks(64)
+        .map(|c| Ok(web::Bytes::copy_from_slice(c)))
+        .collect();
+    Multipart::new(&headers, futures::stream::iter(chunks))
+}
+
+#[cfg(test)]
+fn test_config() -> Config {
+    let spool_dir =
+        std::env::temp_dir().join(format!("latex_upload_{:016x}", rand::random::<u64>()));
+    fs::create_dir_all(&spool_dir).unwrap();
+    Config {
+        upload_limit: 1000,
+        field_limit: 600,
+        spool_threshold: 100,
+        spool_dir,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn spool_test() {
+    let config = test_config();
+    let large: Vec<u8> = (0..500).map(|i| (i % 251) as u8).collect();
+    let mut payload = multipart(&[
+        ("symbol", "symbol.json", b"{}".to_vec()),
+        ("model", "../model.onnx", large.clone()),
+    ]);
+    let read_config = config.clone();
+    let mut file_list = actix_web::rt::System::new("test")
+        .block_on(
+            async move { read_multipart(&mut payload, &read_config, &["model", "symbol"]).await },
+        )
+        .unwrap();
+    let symbol = take_upload(&mut file_list, "symbol").unwrap();
+    assert!(matches!(symbol, Upload::Memory(_)));
+    let model = take_upload(&mut file_list, "model").unwrap();
+    let path = match model {
+        // sanitized name stays in spool dir
+        Upload::Disk { ref path, .. } => path.clone(),
+        _ => panic!("large field is not spooled"),
+    };
+    assert_eq!(path.parent(), Some(config.spool_dir.as_path()));
+    assert_eq!(model.size(), large.len());
+    assert_eq!(model.into_bytes().unwrap(), large);
+    // spooled file is removed with upload
+    assert!(!path.exists());
+    assert!(take_upload(&mut file_list, "model").is_err());
+    fs::remove_dir_all(&config.spool_dir).unwrap();
+}
+
+#[test]
+fn limit_test() {
+    let config = test_config();
+    let read = |fields: &[(&str, &str, Vec<u8>)]| {
+        let mut payload = multipart(fields);
+        let config = config.clone();
+        actix_web::rt::System::new("test")
+            .block_on(
+                async move { read_multipart(&mut payload, &config, &["model", "symbol"]).await },
+            )
+            .err()
+            .map(|e| e.code())
+    };
+    assert_eq!(
+        read(&[("model", "m", vec![0; 700])]),
+        Some("payload_too_large")
+    );
+    assert_eq!(
+        read(&[("model", "m", vec![0; 500]), ("symbol", "s", vec![0; 550])]),
+        Some("payload_too_large")
+    );
+    assert_eq!(read(&[("other", "m", vec![0; 10])]), Some("bad_request"));
+    assert_eq!(
+        read(&[("model", "m", vec![0; 10]), ("model", "m", vec![0; 10])]),
+        Some("bad_request")
+    );
+    assert_eq!(read(&[("model", "m", vec![0; 10])]), None);
+    // spooled files of failed requests are removed
+    assert_eq!(fs::read_dir(&config.spool_dir).unwrap().count(), 0);
+    fs::remove_dir_all(&config.spool_dir).unwrap();
+}